@@ -0,0 +1,79 @@
+//! Benchmarks of the per-page hot path: rendering, the PNG handoff to Tesseract,
+//! OCR and line unwrapping. Rendering and OCR need the pdfium library in
+//! `./pdfium/lib` and Tesseract; `pdf2epub bench` times the same stages on a whole PDF.
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use image::RgbImage;
+use pdfium_render::prelude::*;
+use pdf2epub::ocr::{encode_png, ocr_png, OcrOptions};
+use pdf2epub::{img_source_from_page, LineUnwrapper, RENDER_DPI};
+
+const SAMPLE_PDF: &str = "examples/test-1.pdf";
+
+/// A hard-wrapped page of prose with a hyphenated line end and a page number
+const SAMPLE_PAGE: &str = "\
+The river had risen in the night, and by
+morning the low road to the mill was under
+water. Nobody in the village was surprised;
+they had seen it happen every spring since
+the dam was built, and had long since stop-
+ped complaining about it.
+
+The miller, for his part, simply waited.
+
+17
+";
+
+fn pdfium() -> Pdfium {
+    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./pdfium/lib"))
+        .expect("pdfium library in ./pdfium/lib");
+    Pdfium::new(bindings)
+}
+
+fn first_page_image(pdfium: &Pdfium) -> RgbImage {
+    let pdf = pdfium.load_pdf_from_file(SAMPLE_PDF, None).expect("sample pdf");
+    let page = pdf.pages().get(0).expect("first page");
+    img_source_from_page(&page, RENDER_DPI, true).expect("render")
+}
+
+fn render(c: &mut Criterion) {
+    let pdfium = pdfium();
+    let pdf = pdfium.load_pdf_from_file(SAMPLE_PDF, None).expect("sample pdf");
+    let page = pdf.pages().get(0).expect("first page");
+    let mut group = c.benchmark_group("render");
+    group.sample_size(20);
+    group.bench_function("img_source_from_page", |b| {
+        b.iter(|| img_source_from_page(black_box(&page), RENDER_DPI, true).unwrap())
+    });
+    group.finish();
+}
+
+fn ocr(c: &mut Criterion) {
+    let img = first_page_image(&pdfium());
+    let png = encode_png(&img).unwrap();
+    let opts = OcrOptions::default();
+    let mut group = c.benchmark_group("ocr");
+    group.sample_size(10);
+    group.bench_function("encode_png", |b| b.iter(|| encode_png(black_box(&img)).unwrap()));
+    group.bench_function("ocr_png", |b| b.iter(|| ocr_png(black_box(&png), &opts).unwrap()));
+    group.finish();
+}
+
+fn unwrap_lines(c: &mut Criterion) {
+    let book = SAMPLE_PAGE.repeat(500);
+    let mut group = c.benchmark_group("clean");
+    group.throughput(Throughput::Bytes(book.len() as u64));
+    group.bench_function("LineUnwrapper", |b| {
+        b.iter(|| {
+            let mut cleaner = LineUnwrapper::new();
+            for line in black_box(&book).lines() {
+                cleaner.push_line(line);
+            }
+            cleaner.finish()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, unwrap_lines, render, ocr);
+criterion_main!(benches);
@@ -0,0 +1,159 @@
+//! How close OCR comes to a known-good transcription of the book: the character and word
+//! error rates of each page, for comparing preprocessing and OCR settings by numbers
+//! rather than by eye (`pdf2epub diff --reference book.txt`).
+//!
+//! The reference is plain text. When its pages are separated by form feeds (as
+//! `pdftotext` writes them) page N of the reference is compared with page N of the PDF;
+//! otherwise each recognised page is matched with the stretch of the reference that
+//! follows the previous page and is closest to it, word for word.
+
+/// Error counts of one page against its stretch of the reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageAccuracy {
+    /// 1-based PDF page
+    pub page: usize,
+
+    /// Characters of the reference, whitespace collapsed
+    pub chars: usize,
+
+    /// Characters inserted, deleted or substituted to turn the reference into the OCR
+    pub char_errors: usize,
+
+    /// Words of the reference
+    pub words: usize,
+
+    /// Words inserted, deleted or substituted to turn the reference into the OCR
+    pub word_errors: usize,
+}
+
+impl PageAccuracy {
+    /// Character error rate; above 1 when OCR found far more text than the reference has
+    pub fn cer(&self) -> f64 {
+        self.char_errors as f64 / self.chars.max(1) as f64
+    }
+
+    /// Word error rate
+    pub fn wer(&self) -> f64 {
+        self.word_errors as f64 / self.words.max(1) as f64
+    }
+}
+
+/// Words of `text`
+fn words(text: &str) -> Vec<&str> {
+    text.split_whitespace().collect()
+}
+
+/// Characters of `words` with single spaces between them
+fn collapse(words: &[&str]) -> Vec<char> {
+    words.join(" ").chars().collect()
+}
+
+/// Levenshtein distances between `a` and each start of `b`, from the empty one to all of it
+fn distances<T: PartialEq>(a: &[T], b: &[T]) -> Vec<usize> {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for x in a {
+        let mut diagonal = row[0];
+        row[0] += 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    distances(a, b)[b.len()]
+}
+
+/// The length of the start of `reference` closest to `ocr`, word for word; the shortest
+/// of the closest, so a page does not take the first words of the next
+fn matching_prefix(ocr: &[&str], reference: &[&str]) -> usize {
+    let row = distances(ocr, reference);
+    row.iter().enumerate().min_by_key(|&(_, d)| *d).map_or(0, |(j, _)| j)
+}
+
+/// Error counts of `ocr` against `reference`, both one page
+fn page_accuracy(page: usize, ocr: &[&str], reference: &[&str]) -> PageAccuracy {
+    let chars = collapse(reference);
+    PageAccuracy {
+        page,
+        chars: chars.len(),
+        char_errors: edit_distance(&collapse(ocr), &chars),
+        words: reference.len(),
+        word_errors: edit_distance(ocr, reference),
+    }
+}
+
+/// Compare the recognised `pages` (PDF page and text) with the `reference` text
+pub fn compare(pages: &[(usize, String)], reference: &str) -> Vec<PageAccuracy> {
+    if reference.contains('\u{c}') {
+        let reference: Vec<&str> = reference.split('\u{c}').collect();
+        return pages
+            .iter()
+            .map(|(page, text)| {
+                let expected = reference.get(page - 1).map_or(Vec::new(), |r| words(r));
+                page_accuracy(*page, &words(text), &expected)
+            })
+            .collect();
+    }
+    let reference = words(reference);
+    let mut start = 0;
+    pages
+        .iter()
+        .map(|(page, text)| {
+            let ocr = words(text);
+            // the page's stretch is looked for among twice as many words as it has
+            let window = &reference[start..(start + 2 * ocr.len() + 20).min(reference.len())];
+            let end = matching_prefix(&ocr, window);
+            let accuracy = page_accuracy(*page, &ocr, &window[..end]);
+            start += end;
+            accuracy
+        })
+        .collect()
+}
+
+/// A table of the error rates of each page and of the whole book
+pub fn summary(pages: &[PageAccuracy]) -> String {
+    let row = |name: &str, p: &PageAccuracy| {
+        format!(
+            "{:>6}  {:>7} chars  {:>6.2}% CER  {:>6} words  {:>6.2}% WER\n",
+            name,
+            p.chars,
+            100.0 * p.cer(),
+            p.words,
+            100.0 * p.wer(),
+        )
+    };
+    let mut out: String = pages.iter().map(|p| row(&p.page.to_string(), p)).collect();
+    let total = PageAccuracy {
+        page: 0,
+        chars: pages.iter().map(|p| p.chars).sum(),
+        char_errors: pages.iter().map(|p| p.char_errors).sum(),
+        words: pages.iter().map(|p| p.words).sum(),
+        word_errors: pages.iter().map(|p| p.word_errors).sum(),
+    };
+    out.push_str(&row("total", &total));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_to_each_start() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(distances(&chars("abc"), &chars("abd")), vec![3, 2, 1, 1]);
+        assert_eq!(distances(&chars(""), &chars("ab")), vec![0, 1, 2]);
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+    }
+
+    #[test]
+    fn prefix_of_the_page() {
+        assert_eq!(matching_prefix(&["the", "cat"], &["the", "cat", "sat", "down"]), 2);
+        assert_eq!(matching_prefix(&[], &["the", "cat"]), 0);
+    }
+}
@@ -0,0 +1,87 @@
+//! Highlights and comments of the source PDF, so a reader's markup survives conversion
+use pdfium_render::prelude::*;
+use crate::document::{self, Block, Inline, PageText};
+use crate::Pdf2EPubErr;
+
+/// Where the PDF's annotations go in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AnnotationMode {
+    /// Mark the highlighted text where it occurs, with the comment as its tooltip
+    Inline,
+    /// Collect them in an "Annotations" chapter at the end, by page
+    Chapter,
+}
+
+/// A highlight, underline, strike-out or comment of a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageAnnotation {
+    /// The text marked, for highlights and other text markup
+    pub text: Option<String>,
+
+    /// The comment attached
+    pub note: Option<String>,
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!s.is_empty()).then_some(s)
+}
+
+/// The text markup and comment annotations of `page`, in annotation order
+pub fn page_annotations(page: &PdfPage) -> Result<Vec<PageAnnotation>, Pdf2EPubErr> {
+    let text = page.text()?;
+    let mut found = Vec::new();
+    for annotation in page.annotations().iter() {
+        let marked = match annotation.annotation_type() {
+            PdfPageAnnotationType::Highlight
+            | PdfPageAnnotationType::Underline
+            | PdfPageAnnotationType::Squiggly
+            | PdfPageAnnotationType::Strikeout => text.for_annotation(&annotation).ok().and_then(non_empty),
+            PdfPageAnnotationType::Text | PdfPageAnnotationType::FreeText => None,
+            _ => continue,
+        };
+        let note = annotation.contents().and_then(non_empty);
+        if marked.is_some() || note.is_some() {
+            found.push(PageAnnotation { text: marked, note });
+        }
+    }
+    Ok(found)
+}
+
+/// Mark the highlighted text of `annotations` in `blocks`, see `AnnotationMode::Inline`
+pub fn mark_blocks(blocks: Vec<Block>, annotations: &[PageAnnotation]) -> Vec<Block> {
+    let marks = annotations
+        .iter()
+        .filter_map(|a| {
+            let text = a.text.clone()?;
+            Some((text.clone(), Inline::Highlight { text, note: a.note.clone() }))
+        })
+        .collect();
+    document::mark_text(blocks, marks)
+}
+
+/// The "Annotations" chapter: a heading line per annotated page, then its annotations.
+/// With `notes_only`, only comments without marked text are listed (the highlights being
+/// marked inline). Empty when there is nothing to list.
+pub fn annotations_blocks(pages: &[PageText], notes_only: bool) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for page in pages {
+        let listed: Vec<&PageAnnotation> =
+            page.annotations.iter().filter(|a| !notes_only || a.text.is_none()).collect();
+        if listed.is_empty() {
+            continue;
+        }
+        let name = page.page_name().unwrap_or_else(|| page.page.to_string());
+        blocks.push(Block::Paragraph(vec![Inline::Text(format!("Page {}", name))]));
+        for a in listed {
+            let line = match (&a.text, &a.note) {
+                (Some(text), Some(note)) => format!("“{}” — {}", text, note),
+                (Some(text), None) => format!("“{}”", text),
+                (None, Some(note)) => note.clone(),
+                (None, None) => continue,
+            };
+            blocks.push(Block::Paragraph(vec![Inline::Text(line)]));
+        }
+    }
+    blocks
+}
@@ -0,0 +1,54 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use crate::Pdf2EPubErr;
+
+/// One work of an omnibus scan, as described in the anthology manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkSpec {
+    /// PDF pages (1-based, inclusive) of the work
+    pub pages: RangeInclusive<usize>,
+
+    pub title: String,
+
+    /// Falls back to `--author` when not given
+    pub author: Option<String>,
+}
+
+/// Parse one `FIRST-LAST | Title | Author` manifest line
+fn parse_line(line: &str) -> Result<WorkSpec, String> {
+    let mut fields = line.split('|').map(str::trim);
+    let range = fields.next().unwrap_or_default();
+    let (first, last) = range.split_once('-').unwrap_or((range, range));
+    let page = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid page range `{}`", range));
+    let (first, last) = (page(first)?, page(last)?);
+    if first == 0 || last < first {
+        return Err(format!("invalid page range `{}`", range));
+    }
+
+    let title = fields.next().filter(|t| !t.is_empty()).ok_or("missing title")?;
+    let author = fields.next().filter(|a| !a.is_empty());
+    Ok(WorkSpec { pages: first..=last, title: title.to_string(), author: author.map(str::to_string) })
+}
+
+/// Read an anthology manifest: one work per line as `FIRST-LAST | Title | Author`
+/// (author optional), blank lines and `#` comments ignored. Works must not overlap.
+pub fn read_manifest(path: &Path) -> Result<Vec<WorkSpec>, Pdf2EPubErr> {
+    let text = std::fs::read_to_string(path)?;
+    let mut works: Vec<WorkSpec> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: String| Pdf2EPubErr::ManifestError(format!("{}:{}: {}", path.display(), n + 1, msg));
+        let work = parse_line(line).map_err(err)?;
+        if let Some(other) = works.iter().find(|w| w.pages.start() <= work.pages.end() && work.pages.start() <= w.pages.end()) {
+            return Err(err(format!("pages overlap with `{}`", other.title)));
+        }
+        works.push(work);
+    }
+    if works.is_empty() {
+        return Err(Pdf2EPubErr::ManifestError(format!("{}: no works listed", path.display())));
+    }
+    Ok(works)
+}
@@ -0,0 +1,49 @@
+//! Sections at the back of the book found by their heading, such as the notes section and
+//! the reference list
+use std::ops::Range;
+use crate::document::PageText;
+
+/// The non-blank lines of `text`, trimmed
+pub fn content_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|l| !l.is_empty())
+}
+
+/// A kind of back matter section
+pub struct Section {
+    /// First lines of the section (matched lowercase)
+    pub headings: &'static [&'static str],
+
+    /// Share of lines that are entries for a page to continue the section
+    pub entry_ratio: f32,
+
+    /// Whether a line looks like an entry of the section
+    pub is_entry: fn(&str) -> bool,
+}
+
+impl Section {
+    pub fn is_heading(&self, line: &str) -> bool {
+        self.headings.contains(&line.trim_end_matches(['.', ':']).to_lowercase().as_str())
+    }
+
+    /// Whether the page `text` is like the section, with enough entry lines
+    pub fn is_like(&self, text: &str) -> bool {
+        let (total, entries) =
+            content_lines(text).fold((0, 0), |(t, e), l| (t + 1, e + (self.is_entry)(l) as usize));
+        total > 0 && entries as f32 >= self.entry_ratio * total as f32
+    }
+
+    /// Pages of the section: the last page in the second half of the book that starts with
+    /// its heading, and the pages like it following it
+    pub fn find(&self, pages: &[PageText]) -> Option<Range<usize>> {
+        let start = (pages.len() / 2..pages.len()).rev().find(|&i| {
+            content_lines(&pages[i].text).next().is_some_and(|l| self.is_heading(l)) && self.is_like(&pages[i].text)
+        })?;
+        let len = 1 + pages[start + 1..].iter().take_while(|p| self.is_like(&p.text)).count();
+        Some(start..start + len)
+    }
+
+    /// The lines of `pages`, less the headings they start with
+    pub fn lines<'a>(&'a self, pages: &'a [PageText]) -> impl Iterator<Item = &'a str> {
+        pages.iter().flat_map(|p| content_lines(&p.text)).skip_while(move |l| self.is_heading(l))
+    }
+}
@@ -0,0 +1,92 @@
+//! Paragraphs with unbalanced quotation marks or brackets, which OCR leaves behind by
+//! dropping or misreading one of a pair
+use crate::document::PageText;
+use crate::sentences::{Ending, SentenceRules};
+
+/// Characters of the paragraph shown to find it by
+const CONTEXT: usize = 60;
+
+/// Marks that open and close, checked by count
+const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('“', '”'), ('«', '»')];
+
+/// A paragraph whose quotation marks or brackets do not pair up
+#[derive(Debug, Clone)]
+pub struct Unbalanced {
+    /// 1-based PDF page the paragraph starts on
+    pub page: usize,
+
+    /// The marks left open or closed without being opened, e.g. `(` or `”`
+    pub marks: String,
+
+    /// The start of the paragraph
+    pub context: String,
+}
+
+/// "a)" or "12)" enumerating a list, closing no bracket
+fn is_enumerator(word: &str) -> bool {
+    word.strip_suffix(')').is_some_and(|w| (1..=2).contains(&w.chars().count()) && w.chars().all(char::is_alphanumeric))
+}
+
+/// The marks of `text` that do not pair up; a quotation running on into the `next`
+/// paragraph, which opens it again, is not closed before
+fn unbalanced(text: &str, next: Option<&str>) -> String {
+    let mut marks = String::new();
+    // German quotes open with „ and close with “
+    let german = text.contains('„');
+    for &(open, close) in PAIRS {
+        let (open, close) = if german && open == '“' { ('„', '“') } else { (open, close) };
+        let opened = text.matches(open).count();
+        let mut closed = text.matches(close).count();
+        if close == ')' {
+            closed -= text.split_whitespace().filter(|w| is_enumerator(w)).count();
+        }
+        if opened > closed {
+            let quote = !matches!(open, '(' | '[');
+            let runs_on = quote && opened == closed + 1 && next.is_some_and(|n| n.starts_with(open));
+            if !runs_on {
+                marks.push(open);
+            }
+        } else if closed > opened {
+            marks.push(close);
+        }
+    }
+    // straight quotes open and close alike
+    if text.matches('"').count() % 2 == 1 {
+        marks.push('"');
+    }
+    marks
+}
+
+/// The paragraphs of `pages` with unbalanced quotation marks or brackets. A paragraph
+/// left mid-sentence at the end of a page is continued by the first of the next.
+pub fn check_pages(pages: &[PageText]) -> Vec<Unbalanced> {
+    let sentences = SentenceRules::default();
+    let mut paragraphs: Vec<(usize, String)> = Vec::new();
+    let mut runs_on = false;
+    for page in pages {
+        let page_paragraphs = page.text.split("\n\n").map(str::trim).filter(|p| !p.is_empty());
+        for (k, paragraph) in page_paragraphs.enumerate() {
+            let text = paragraph.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+            match paragraphs.last_mut() {
+                Some((_, last)) if k == 0 && runs_on => {
+                    last.push(' ');
+                    last.push_str(&text);
+                }
+                _ => paragraphs.push((page.page, text)),
+            }
+        }
+        runs_on = paragraphs.last().is_some_and(|(_, p)| sentences.ending(p) == Ending::Open);
+    }
+    paragraphs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (page, text))| {
+            let marks = unbalanced(text, paragraphs.get(i + 1).map(|(_, t)| t.as_str()));
+            (!marks.is_empty()).then(|| Unbalanced {
+                page: *page,
+                marks,
+                context: text.chars().take(CONTEXT).collect(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,43 @@
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+use crate::document::PageText;
+use crate::ocr::{encode_png, ocr_png, OcrOptions};
+use crate::preprocess::{preprocess, PreprocessOptions};
+use crate::timings::Timings;
+use crate::paragraphs::ParagraphRules;
+use crate::{blocks_to_markup, img_source_from_page, peel_trailing_page_num, unwrap_pages, Pdf2EPubErr};
+
+/// Run the default pipeline at `dpi` over the first `max_pages` pages of `pdf` (all
+/// when `None`), timing each stage. Returns the timings and the number of pages processed.
+pub fn bench_pdf(
+    pdf: &PdfDocument,
+    max_pages: Option<usize>,
+    dpi: u16,
+    ocr: &OcrOptions,
+) -> Result<(Timings, usize), Pdf2EPubErr> {
+    let mut t = Timings::default();
+    let mut pages: Vec<PageText> = Vec::new();
+
+    for (index, page) in pdf.pages().iter().take(max_pages.unwrap_or(usize::MAX)).enumerate() {
+        let img = t.time("render", || img_source_from_page(&page, dpi, true))?;
+        let rgb = t.time("preprocess", || {
+            let gray = preprocess(image::imageops::grayscale(&img), &PreprocessOptions::default());
+            DynamicImage::ImageLuma8(gray).into_rgb8()
+        });
+        let png = t.time("png handoff", || encode_png(&rgb))?;
+        let (raw_text, confidence) = t.time("ocr", || ocr_png(&png, ocr))?;
+        let (text, number) = t.time("clean", || peel_trailing_page_num(&raw_text));
+        pages.push(PageText {
+            page: index + 1,
+            number,
+            text: text.to_string(),
+            confidence,
+            ..Default::default()
+        });
+    }
+
+    let count = pages.len();
+    let blocks = t.time("clean", || unwrap_pages(pages, false, &ParagraphRules::default()));
+    t.time("markup", || blocks_to_markup(&blocks, |f| f.name.clone(), |f| f.name.clone(), str::to_string));
+    Ok((t, count))
+}
@@ -0,0 +1,249 @@
+//! The OCR of every page, kept between runs with `--cache FILE`, so that a book whose
+//! scan is redone page by page is only recognised again where its pages changed. Pages
+//! are looked up by a hash of the image handed to Tesseract and of the OCR settings, so
+//! moved pages are found too and changing a setting recognises the book afresh.
+//!
+//! The file holds one JSON object per line and page. Pages are added to it as they are
+//! recognised, so that an interrupted run keeps what it read; at the end of a run the file
+//! is written again with the pages of that run only.
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use serde_json::{json, Value};
+use crate::document::Script;
+use crate::ocr::{self, OcrInput, OcrLine, OcrWord, Rect};
+use crate::Pdf2EPubErr;
+
+/// What Tesseract made of a page, before any cleaning up
+#[derive(Debug, Clone)]
+pub enum PageOcr {
+    /// Lines with word boxes, when words are needed
+    Lines(Vec<OcrLine>),
+
+    /// Plain text and its mean confidence (0-100)
+    Text(String, Option<f32>),
+}
+
+impl PageOcr {
+    pub fn confidence(&self) -> Option<f32> {
+        match self {
+            PageOcr::Lines(lines) => ocr::mean_confidence(lines),
+            PageOcr::Text(_, confidence) => *confidence,
+        }
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher stays the same between
+/// builds, as hashes kept in a file must
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Hash of the image `input` hands to Tesseract
+pub fn page_hash(input: &OcrInput) -> Result<String, Pdf2EPubErr> {
+    let hash = match input {
+        OcrInput::Image(img) => fnv1a(fnv1a(FNV_OFFSET, &img.width().to_le_bytes()), img.as_raw()),
+        OcrInput::File(path) => fnv1a(FNV_OFFSET, &std::fs::read(path)?),
+    };
+    Ok(format!("{:016x}", hash))
+}
+
+/// Hash of the contents of the files the OCR settings name, such as the user word list,
+/// so that editing one of them recognises the book afresh
+pub fn files_hash<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Result<String, Pdf2EPubErr> {
+    let mut hash = FNV_OFFSET;
+    for path in paths {
+        let contents = std::fs::read(path)?;
+        hash = fnv1a(fnv1a(hash, &(contents.len() as u64).to_le_bytes()), &contents);
+    }
+    Ok(format!("{:016x}", hash))
+}
+
+/// Key of the page whose image has `hash`, recognised with `settings` (the OCR options,
+/// the hash of the files they name, and whether words were asked for)
+pub fn page_key(hash: &str, settings: &str) -> String {
+    format!("{:016x}", fnv1a(fnv1a(FNV_OFFSET, settings.as_bytes()), hash.as_bytes()))
+}
+
+fn rect_json(r: &Rect) -> Value {
+    json!([r.x, r.y, r.w, r.h])
+}
+
+fn script_name(script: Script) -> &'static str {
+    match script {
+        Script::Super => "super",
+        Script::Sub => "sub",
+    }
+}
+
+fn ocr_json(ocr: &PageOcr) -> Value {
+    match ocr {
+        PageOcr::Text(text, confidence) => json!({ "text": text, "confidence": confidence }),
+        PageOcr::Lines(lines) => json!({
+            "lines": lines.iter().map(|l| json!({
+                "bbox": rect_json(&l.bbox),
+                "paragraph": [l.paragraph.0, l.paragraph.1],
+                "words": l.words.iter().map(|w| json!({
+                    "text": w.text,
+                    "bbox": rect_json(&w.bbox),
+                    "conf": w.conf,
+                    "alternatives": w.alternatives,
+                    "scripts": w.scripts.iter().map(|(r, s)| json!([r.start, r.end, script_name(*s)])).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn rect_from(v: &Value) -> Option<Rect> {
+    let n = |i: usize| v.get(i)?.as_u64().map(|n| n as u32);
+    Some(Rect { x: n(0)?, y: n(1)?, w: n(2)?, h: n(3)? })
+}
+
+fn word_from(v: &Value) -> Option<OcrWord> {
+    let scripts = v["scripts"]
+        .as_array()?
+        .iter()
+        .map(|s| {
+            let script = match s.get(2)?.as_str()? {
+                "super" => Script::Super,
+                "sub" => Script::Sub,
+                _ => return None,
+            };
+            Some((s.get(0)?.as_u64()? as usize..s.get(1)?.as_u64()? as usize, script))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(OcrWord {
+        text: v["text"].as_str()?.to_string(),
+        bbox: rect_from(&v["bbox"])?,
+        conf: v["conf"].as_f64()? as f32,
+        alternatives: v["alternatives"].as_array()?.iter().map(|a| a.as_str().map(str::to_string)).collect::<Option<_>>()?,
+        scripts,
+    })
+}
+
+fn ocr_from(v: &Value) -> Option<PageOcr> {
+    if let Some(text) = v["text"].as_str() {
+        return Some(PageOcr::Text(text.to_string(), v["confidence"].as_f64().map(|c| c as f32)));
+    }
+    let lines = v["lines"]
+        .as_array()?
+        .iter()
+        .map(|l| {
+            Some(OcrLine {
+                words: l["words"].as_array()?.iter().map(word_from).collect::<Option<_>>()?,
+                bbox: rect_from(&l["bbox"])?,
+                paragraph: (l["paragraph"][0].as_u64()? as u32, l["paragraph"][1].as_u64()? as u32),
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some(PageOcr::Lines(lines))
+}
+
+/// The line of the page with `key`, read at `dpi`
+fn entry_json(key: &str, ocr: &PageOcr, dpi: u16) -> String {
+    json!({ "key": key, "dpi": dpi, "ocr": ocr_json(ocr) }).to_string()
+}
+
+fn entry_from(line: &str) -> Option<(String, CachedPage)> {
+    let v: Value = serde_json::from_str(line).ok()?;
+    let dpi = u16::try_from(v["dpi"].as_u64()?).ok()?;
+    Some((v["key"].as_str()?.to_string(), CachedPage { ocr: ocr_from(&v["ocr"])?, dpi }))
+}
+
+/// The OCR of a page and the resolution its page was rendered at, which the word boxes
+/// are of: a page read better on a retry at another resolution is kept at that one
+#[derive(Debug, Clone)]
+pub struct CachedPage {
+    pub ocr: PageOcr,
+    pub dpi: u16,
+}
+
+/// The OCR of the pages of the last run, and of this one
+pub struct PageCache {
+    path: PathBuf,
+
+    /// Pages of the last run by key
+    previous: HashMap<String, CachedPage>,
+
+    /// Pages of this run by key, in order; only these are kept for the next run
+    current: Vec<(String, CachedPage)>,
+
+    /// Pages of this run found in the cache
+    pub hits: usize,
+}
+
+impl PageCache {
+    /// The cache at `path`; empty when there is no file yet or it cannot be read. Lines
+    /// that cannot be read, such as one cut short by an interrupted run, are passed over.
+    pub fn load(path: &Path) -> PageCache {
+        let previous = std::fs::read_to_string(path)
+            .map(|text| text.lines().filter_map(entry_from).collect())
+            .unwrap_or_default();
+        PageCache { path: path.to_path_buf(), previous, current: Vec::new(), hits: 0 }
+    }
+
+    /// The OCR the last run kept for the page with `key`
+    pub fn get(&mut self, key: &str) -> Option<CachedPage> {
+        let page = self.previous.get(key).cloned()?;
+        self.hits += 1;
+        Some(page)
+    }
+
+    /// Keep `ocr` of the page with `key`, rendered at `dpi`, for the next run; it is added
+    /// to the file at once
+    pub fn insert(&mut self, key: String, ocr: PageOcr, dpi: u16) -> Result<(), Pdf2EPubErr> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open the page cache {}", self.path.display()))?;
+        writeln!(file, "{}", entry_json(&key, &ocr, dpi))?;
+        self.current.push((key, CachedPage { ocr, dpi }));
+        Ok(())
+    }
+
+    /// Write the pages of this run over the file, leaving out those of earlier runs
+    pub fn save(&self) -> Result<(), Pdf2EPubErr> {
+        let text: String = self.current.iter().map(|(key, page)| entry_json(key, &page.ocr, page.dpi) + "\n").collect();
+        std::fs::write(&self.path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_round_trip() {
+        let word = OcrWord {
+            text: "x2".to_string(),
+            bbox: Rect { x: 10, y: 20, w: 30, h: 12 },
+            conf: 87.5,
+            alternatives: vec!["x²".to_string()],
+            scripts: vec![(1..2, Script::Super)],
+        };
+        let line = OcrLine { words: vec![word], bbox: Rect { x: 10, y: 20, w: 30, h: 12 }, paragraph: (1, 2) };
+        let ocr = PageOcr::Lines(vec![line]);
+        let (key, page) = entry_from(&entry_json("00ff", &ocr, 600)).unwrap();
+        assert_eq!((key.as_str(), page.dpi), ("00ff", 600));
+        assert_eq!(ocr_json(&page.ocr), ocr_json(&ocr));
+        let PageOcr::Lines(lines) = &page.ocr else { panic!("expected lines") };
+        assert_eq!(lines[0].words[0].scripts, vec![(1..2, Script::Super)]);
+
+        let ocr = PageOcr::Text("Plain text.".to_string(), None);
+        let (_, page) = entry_from(&entry_json("01", &ocr, 300)).unwrap();
+        assert!(matches!(page.ocr, PageOcr::Text(ref text, None) if text == "Plain text."));
+    }
+
+    #[test]
+    fn cut_short_entry() {
+        let line = entry_json("01", &PageOcr::Text("Text".to_string(), Some(90.0)), 300);
+        assert!(entry_from(&line[..line.len() - 3]).is_none());
+    }
+}
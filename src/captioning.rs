@@ -0,0 +1,46 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use anyhow::Context;
+use crate::document::Figure;
+use crate::Pdf2EPubErr;
+
+/// Describe a figure with an external image-captioning model.
+/// `cmd` is run through `sh -c` with the encoded image on stdin and its media type
+/// in `PDF2EPUB_IMAGE_MIME`; the first line of its output becomes the description.
+pub fn describe_image(cmd: &str, fig: &Figure) -> Result<Option<String>, Pdf2EPubErr> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .env("PDF2EPUB_IMAGE_MIME", fig.mime)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run captioning command `{}`", cmd))?;
+
+    child.stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(&fig.data)
+        .context("failed to send image to captioning command")?;
+
+    let output = child.wait_with_output().context("captioning command failed")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("captioning command exited with {}", output.status).into());
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(text.lines().map(str::trim).find(|l| !l.is_empty()).map(str::to_string))
+}
+
+/// Fill in the alt text of figures that have none (no caption) using `cmd`, if given
+pub fn describe_uncaptioned(
+    cmd: Option<&str>,
+    mut figures: Vec<Figure>,
+) -> Result<Vec<Figure>, Pdf2EPubErr> {
+    if let Some(cmd) = cmd {
+        for fig in figures.iter_mut().filter(|f| f.alt.is_none()) {
+            fig.alt = describe_image(cmd, fig)?;
+        }
+    }
+    Ok(figures)
+}
@@ -0,0 +1,176 @@
+//! The settings file and its named conversion profiles.
+//!
+//! Keys are the long flag names of the command line. Top-level keys apply to every
+//! conversion, the keys of a `[profile.NAME]` table only with `--profile NAME`:
+//!
+//! ```toml
+//! ocr-lang = "deu"
+//! dpi = 400
+//!
+//! [profile.novel]
+//! extract-pagenum = true
+//! theme = "classic-serif"
+//! ```
+//!
+//! `true` stands for a bare flag, `false` leaves it out, and an array repeats the flag.
+//! The keys of the profile replace the top-level ones, flags given on the command line
+//! replace the file's (`--dewarp=false` turns off a flag the file turns on), and the
+//! file wins over the environment.
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use crate::Pdf2EPubErr;
+
+/// Profiles available without a settings file; the file can redefine them
+pub const BUILTIN_PROFILES: &str = r#"
+# printed fiction: running page numbers, chapters from the contents page
+[profile.novel]
+extract-pagenum = true
+parse-toc = true
+skip-front-matter = "appendix"
+theme = "classic-serif"
+hyphenate = true
+
+# papers and textbooks: figures, linked index, smaller type needs more pixels
+[profile.academic]
+dpi = 400
+extract-pagenum = true
+parse-toc = true
+index = "link"
+extract-images = true
+extract-vector-graphics = true
+theme = "modern-sans"
+
+# photocopies and old scans: clean the page up as much as possible before OCR
+[profile.bad-scan]
+dpi = 400
+psm = 6
+extract-pagenum = true
+dewarp = true
+remove-gutter = true
+despeckle = "components"
+suppress-bleed-through = true
+"#;
+
+/// `$XDG_CONFIG_HOME/pdf2epub/config.toml`, or `~/.config/pdf2epub/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("pdf2epub").join("config.toml"))
+}
+
+fn parse(text: &str, origin: &str) -> Result<Table, Pdf2EPubErr> {
+    text.parse::<Table>()
+        .map_err(|e| Pdf2EPubErr::ConfigError(format!("{}: {}", origin, e)))
+}
+
+pub fn load(path: &Path) -> Result<Table, Pdf2EPubErr> {
+    parse(&std::fs::read_to_string(path)?, &path.display().to_string())
+}
+
+/// The profile tables of `config`
+fn profiles(config: &Table) -> Result<Option<&Table>, Pdf2EPubErr> {
+    match config.get("profile") {
+        None => Ok(None),
+        Some(Value::Table(t)) => Ok(Some(t)),
+        Some(_) => Err(Pdf2EPubErr::ConfigError("`profile` must be a table of profiles".to_string())),
+    }
+}
+
+/// Command line arguments for the settings in `table`, but those of the keys `given`
+/// says are given on the command line
+fn table_args(table: &Table, origin: &str, given: impl Fn(&str) -> bool) -> Result<Vec<String>, Pdf2EPubErr> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        if key == "profile" || given(key) {
+            continue;
+        }
+        let flag = format!("--{}", key);
+        let values = match value {
+            Value::Array(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(flag.clone()),
+                Value::Boolean(false) => {}
+                Value::String(s) => args.extend([flag.clone(), s.clone()]),
+                Value::Integer(i) => args.extend([flag.clone(), i.to_string()]),
+                Value::Float(f) => args.extend([flag.clone(), f.to_string()]),
+                other => {
+                    return Err(Pdf2EPubErr::ConfigError(format!(
+                        "{}: `{}` cannot be a {}",
+                        origin,
+                        key,
+                        other.type_str()
+                    )))
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Command line arguments for the top-level settings of `config` and those of `profile`,
+/// looked up in `config` first and then among the built-in profiles. The profile's
+/// keys replace the top-level ones, and the keys `given` says are given on the command
+/// line are left out, so that the command line replaces them rather than adds to them.
+pub fn settings_args(
+    config: Option<&Table>,
+    profile: Option<&str>,
+    given: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, Pdf2EPubErr> {
+    let mut settings = config.cloned().unwrap_or_default();
+    settings.remove("profile");
+    let mut origin = "settings file".to_string();
+    if let Some(name) = profile {
+        let builtin = parse(BUILTIN_PROFILES, "built-in profiles")?;
+        let user = config.map(profiles).transpose()?.flatten();
+        let table = user
+            .and_then(|p| p.get(name))
+            .or_else(|| profiles(&builtin).ok().flatten().and_then(|p| p.get(name)))
+            .ok_or_else(|| Pdf2EPubErr::ConfigError(format!("no profile named `{}`", name)))?;
+        let Value::Table(table) = table else {
+            return Err(Pdf2EPubErr::ConfigError(format!("profile `{}` must be a table", name)));
+        };
+        settings.extend(table.iter().map(|(k, v)| (k.clone(), v.clone())));
+        origin = format!("settings file or profile `{}`", name);
+    }
+    table_args(&settings, &origin, given)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+dpi = 300
+ocr-lang = "deu"
+dewarp = true
+despeckle = false
+page-lang = ["1-4=eng", "5-=deu"]
+
+[profile.novel]
+dpi = 600
+theme = "modern-sans"
+"#;
+
+    #[test]
+    fn settings_precedence() {
+        let config = parse(CONFIG, "test").unwrap();
+        // the top-level keys, less the one given on the command line
+        assert_eq!(
+            settings_args(Some(&config), None, |key| key == "ocr-lang").unwrap(),
+            ["--dewarp", "--dpi", "300", "--page-lang", "1-4=eng", "--page-lang", "5-=deu"]
+        );
+        // the file's profile replaces both the top-level keys and the built-in profile
+        assert_eq!(
+            settings_args(Some(&config), Some("novel"), |key| key != "dpi" && key != "theme").unwrap(),
+            ["--dpi", "600", "--theme", "modern-sans"]
+        );
+        let args = settings_args(None, Some("bad-scan"), |_| false).unwrap();
+        assert!(args.windows(2).any(|w| w == ["--psm", "6"]));
+        assert!(settings_args(Some(&config), Some("poetry"), |_| false).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Debug images: how each page was rendered, and what OCR made of it
+use std::path::{Path, PathBuf};
+use image::{Rgb, RgbImage};
+use crate::ocr::{OcrLine, Rect};
+use crate::Pdf2EPubErr;
+
+/// Width of the word box outlines, in pixels
+const OUTLINE: u32 = 3;
+
+/// Share of the box color blended into the words
+const TINT: f32 = 0.25;
+
+/// Name stem of the debug images of a PDF page (1-based), `part` numbering the
+/// halves of a split spread
+fn stem(page: usize, part: Option<usize>) -> String {
+    match part {
+        Some(part) => format!("page-{:04}-{}", page, part + 1),
+        None => format!("page-{:04}", page),
+    }
+}
+
+/// The page as rendered from the PDF
+pub fn render_path(dir: &Path, page: usize) -> PathBuf {
+    dir.join(format!("{}-render.png", stem(page, None)))
+}
+
+/// The page as handed to OCR, with the recognised words marked
+pub fn confidence_path(dir: &Path, page: usize, part: Option<usize>) -> PathBuf {
+    dir.join(format!("{}-confidence.png", stem(page, part)))
+}
+
+/// Red for no confidence through yellow to green for full confidence (0-100)
+pub fn confidence_color(conf: f32) -> Rgb<u8> {
+    let t = (conf / 100.0).clamp(0.0, 1.0);
+    let (r, g) = if t < 0.5 { (1.0, 2.0 * t) } else { (2.0 * (1.0 - t), 1.0) };
+    Rgb([(r * 255.0) as u8, (g * 220.0) as u8, 0])
+}
+
+fn blend(px: &mut Rgb<u8>, color: Rgb<u8>, share: f32) {
+    for (c, t) in px.0.iter_mut().zip(color.0) {
+        *c = (*c as f32 * (1.0 - share) + t as f32 * share) as u8;
+    }
+}
+
+/// Tint and outline `bbox` in `color`
+fn mark(img: &mut RgbImage, bbox: &Rect, color: Rgb<u8>) {
+    let right = bbox.right().min(img.width());
+    let bottom = bbox.bottom().min(img.height());
+    for y in bbox.y..bottom {
+        for x in bbox.x..right {
+            let edge = x < bbox.x + OUTLINE || x + OUTLINE >= right || y < bbox.y + OUTLINE || y + OUTLINE >= bottom;
+            let px = img.get_pixel_mut(x, y);
+            if edge {
+                *px = color;
+            } else {
+                blend(px, color, TINT);
+            }
+        }
+    }
+}
+
+/// `img` with every recognised word boxed in the color of its confidence
+pub fn confidence_overlay(img: &RgbImage, lines: &[OcrLine]) -> RgbImage {
+    let mut out = img.clone();
+    for word in lines.iter().flat_map(|l| &l.words) {
+        mark(&mut out, &word.bbox, confidence_color(word.conf));
+    }
+    out
+}
+
+pub fn save(img: &RgbImage, path: &Path) -> Result<(), Pdf2EPubErr> {
+    img.save(path).map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,410 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use crate::annotations::PageAnnotation;
+use crate::links::PageLink;
+use crate::notes::Note;
+use crate::balance::Unbalanced;
+use crate::order::ReadingIssue;
+
+/// An image lifted out of a page, kept out of the OCR text flow
+#[derive(Debug, Clone)]
+pub struct Figure {
+    /// File name of the image inside the epub (e.g. `page-12-fig-1.png`)
+    pub name: String,
+
+    /// Encoded image bytes
+    pub data: Vec<u8>,
+
+    /// Media type of `data`
+    pub mime: &'static str,
+
+    /// 1-based index of the PDF page the figure was found on
+    pub page: usize,
+
+    /// Caption detected right below the image, if any
+    pub caption: Option<String>,
+
+    /// Text alternative for readers that cannot display the image
+    pub alt: Option<String>,
+
+    /// Full-size image the figure is a thumbnail of, which it links to
+    pub full: Option<Box<Figure>>,
+
+    /// MathML of an equation, shown instead of the image
+    pub mathml: Option<String>,
+}
+
+impl Figure {
+    /// Anchor id of the figure, its file name without extension
+    pub fn id(&self) -> &str {
+        self.name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(&self.name)
+    }
+
+    /// Alt text to emit, falling back to the figure's location when nothing better is known
+    pub fn alt_text(&self) -> String {
+        self.alt.clone().unwrap_or_else(|| format!("Illustration from page {}", self.page))
+    }
+}
+
+/// A piece of paragraph content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+
+    /// Invisible link target, such as the start of a printed page
+    Anchor(String),
+
+    /// Link to an anchor (`#id`) or an external URL
+    Link { href: String, text: String },
+
+    /// Marker linking to an EPUB 3 footnote, see `ParagraphStyle::Footnote`
+    NoteRef { href: String, text: String },
+
+    /// A word recognised with low confidence, with the readings Tesseract also considered
+    Uncertain { text: String, alternatives: Vec<String> },
+
+    /// Text set as a superscript or subscript
+    Script { script: Script, text: String },
+
+    /// Text with its reading set above it, such as kanji with their furigana
+    Ruby { text: String, reading: String },
+
+    /// Text in another script than the book's, with its language tag
+    Foreign { lang: String, text: String },
+
+    /// Text highlighted in the source PDF, with the comment attached to the highlight
+    Highlight { text: String, note: Option<String> },
+}
+
+/// The readable text of a paragraph, without anchors and link targets
+pub fn plain_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|i| match i {
+            Inline::Text(t)
+            | Inline::Link { text: t, .. }
+            | Inline::NoteRef { text: t, .. }
+            | Inline::Uncertain { text: t, .. }
+            | Inline::Script { text: t, .. }
+            | Inline::Ruby { text: t, .. }
+            | Inline::Foreign { text: t, .. }
+            | Inline::Highlight { text: t, .. } => t.as_str(),
+            Inline::Anchor(_) => "",
+        })
+        .collect()
+}
+
+/// How many of the next pieces of text `mark_text` looks for in every text inline, so
+/// that one which did not survive OCR does not stop the ones after it from being found
+const MARK_LOOKAHEAD: usize = 3;
+
+/// Replace the first occurrence of each `(text, inline)` pair's text in the paragraphs
+/// of `blocks` by its inline, looking for them in order
+pub fn mark_text(mut blocks: Vec<Block>, marks: Vec<(String, Inline)>) -> Vec<Block> {
+    let mut pending: Vec<(String, Inline)> = marks.into_iter().filter(|(t, _)| !t.is_empty()).collect();
+    for block in &mut blocks {
+        let Some(inlines) = block.inlines_mut() else { continue };
+        if pending.is_empty() {
+            break;
+        }
+        let mut out = Vec::with_capacity(inlines.len());
+        for inline in inlines.drain(..) {
+            let Inline::Text(text) = inline else {
+                out.push(inline);
+                continue;
+            };
+            let mut rest = text.as_str();
+            loop {
+                let found = pending
+                    .iter()
+                    .take(MARK_LOOKAHEAD)
+                    .enumerate()
+                    .filter_map(|(i, (t, _))| rest.find(t.as_str()).map(|at| (at, i)))
+                    .min();
+                let Some((at, i)) = found else { break };
+                let (found, mark) = pending.drain(..=i).next_back().expect("matched text");
+                if at > 0 {
+                    out.push(Inline::Text(rest[..at].to_string()));
+                }
+                out.push(mark);
+                rest = &rest[at + found.len()..];
+            }
+            if !rest.is_empty() {
+                out.push(Inline::Text(rest.to_string()));
+            }
+        }
+        *inlines = out;
+    }
+    blocks
+}
+
+/// Drop the anchors of `blocks` whose id an earlier one already has, such as a printed
+/// page number that occurs twice, so every id of the book stays unique
+pub fn dedup_anchors<'a>(blocks: impl IntoIterator<Item = &'a mut Block>) {
+    let mut seen: HashSet<String> = HashSet::new();
+    for block in blocks {
+        if let Some(inlines) = block.inlines_mut() {
+            inlines.retain(|i| match i {
+                Inline::Anchor(id) => seen.insert(id.clone()),
+                _ => true,
+            });
+        }
+    }
+}
+
+/// Prefix the anchor ids of `chapters`, and the links to them, with `prefix`, so the works
+/// of an anthology do not share ids. PDF page anchors (`pdf-page-N`) are unique already and
+/// may be linked from other works, so they keep theirs.
+pub fn namespace_ids(chapters: &mut [Chapter], prefix: &str) {
+    let ids: HashSet<String> = chapters
+        .iter()
+        .flat_map(|c| &c.blocks)
+        .filter_map(Block::inlines)
+        .flatten()
+        .filter_map(|i| match i {
+            Inline::Anchor(id) if !id.starts_with("pdf-page-") => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    for inlines in chapters.iter_mut().flat_map(|c| &mut c.blocks).filter_map(Block::inlines_mut) {
+        for inline in inlines {
+            match inline {
+                Inline::Anchor(id) if ids.contains(id.as_str()) => *id = format!("{}{}", prefix, id),
+                Inline::Link { href, .. } | Inline::NoteRef { href, .. } => {
+                    let id = href.strip_prefix('#').filter(|id| ids.contains(*id));
+                    if let Some(href_id) = id.map(|id| format!("#{}{}", prefix, id)) {
+                        *href = href_id;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Anchor id of a printed page number
+pub fn page_anchor(number: u32) -> String {
+    label_anchor(&number.to_string())
+}
+
+/// Anchor id of a page by its name in the print edition (`xii`, `12`), see `PageText::page_name`.
+/// Characters not allowed in ids become `-`, so `12` gets the same anchor as `page_anchor(12)`.
+pub fn label_anchor(label: &str) -> String {
+    let label: String = label
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '-' })
+        .collect();
+    format!("page-{}", label)
+}
+
+/// How a paragraph that is not body text is set, see `Block::Styled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParagraphStyle {
+    /// An entry of a bibliography or reference list, set with a hanging indent
+    Reference,
+    /// A note, in the notes chapter or after the paragraph referring to it
+    Note,
+    /// An EPUB 3 footnote, shown in a popup by readers that can
+    Footnote,
+    /// A quotation at the start of a chapter
+    Epigraph,
+    /// The source of an epigraph, below it
+    Attribution,
+    /// The dedication of the book
+    Dedication,
+}
+
+impl ParagraphStyle {
+    /// Class of the paragraph in the markup
+    pub fn class(self) -> &'static str {
+        match self {
+            ParagraphStyle::Reference => "reference",
+            ParagraphStyle::Note | ParagraphStyle::Footnote => "note",
+            ParagraphStyle::Epigraph => "epigraph",
+            ParagraphStyle::Attribution => "attribution",
+            ParagraphStyle::Dedication => "dedication",
+        }
+    }
+}
+
+/// A unit of the reflowed book content, in reading order.
+/// This is what every output format (epub, html, ssml) renders from.
+#[derive(Debug, Clone)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+
+    /// A paragraph set apart from the body text
+    Styled(ParagraphStyle, Vec<Inline>),
+
+    Figure(Figure),
+
+    /// A section break: a deliberate vertical gap in the text
+    Break,
+}
+
+impl Block {
+    /// The content of a paragraph, plain or styled; `None` for a figure or break
+    pub fn inlines(&self) -> Option<&[Inline]> {
+        match self {
+            Block::Paragraph(p) | Block::Styled(_, p) => Some(p),
+            Block::Figure(_) | Block::Break => None,
+        }
+    }
+
+    pub fn inlines_mut(&mut self) -> Option<&mut Vec<Inline>> {
+        match self {
+            Block::Paragraph(p) | Block::Styled(_, p) => Some(p),
+            Block::Figure(_) | Block::Break => None,
+        }
+    }
+}
+
+/// Iterate the figures contained in `blocks`
+pub fn figures<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> impl Iterator<Item = &'a Figure> {
+    blocks.into_iter().filter_map(|b| match b {
+        Block::Figure(f) => Some(f),
+        _ => None,
+    })
+}
+
+/// A titled part of the book, rendered as its own file in the epub
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub blocks: Vec<Block>,
+
+    /// Mean OCR confidence of the chapter's pages, `None` for generated chapters
+    pub confidence: Option<f32>,
+}
+
+/// The recognised content of one logical page (half of a split spread counts as a page)
+#[derive(Debug, Clone, Default)]
+pub struct PageText {
+    /// 1-based index of the PDF page
+    pub page: usize,
+
+    /// Page number printed on the page, when extracted or taken from a numeric `label`
+    pub number: Option<u32>,
+
+    /// Logical page label of the PDF (`/PageLabels`), such as `xii` or `12`
+    pub label: Option<String>,
+
+    /// OCR text, hard-wrapped as printed
+    pub text: String,
+
+    /// Tesseract's mean confidence (0-100), `None` when no text was found
+    pub confidence: Option<f32>,
+
+    /// Figures lifted out of the page
+    pub figures: Vec<Figure>,
+
+    /// Footnotes split off the bottom of the page, see `--footnotes`
+    pub footnotes: Vec<Note>,
+
+    /// Words of `text` recognised with low confidence, in reading order
+    pub uncertain: Vec<UncertainWord>,
+
+    /// Words of `text` with superscripts or subscripts, in reading order
+    pub scripted: Vec<ScriptedWord>,
+
+    /// Words of `text` with furigana, in reading order
+    pub ruby: Vec<RubyWord>,
+
+    /// Layout of the lines of `text` on the page, in reading order; only filled in on request
+    pub layout: Vec<LineLayout>,
+
+    /// Lines of `text` set apart from the body text, in order, see `epigraphs`
+    pub set_apart: Vec<SetApart>,
+
+    /// Link annotations of the PDF page
+    pub links: Vec<PageLink>,
+
+    /// Whether a link of the PDF points to this page, see `links::mark_targets`
+    pub linked: bool,
+
+    /// Highlights and comments of the PDF page, when `--annotations` is given
+    pub annotations: Vec<PageAnnotation>,
+}
+
+impl PageText {
+    /// Name of the page in the print edition: its PDF page label, or else its printed number
+    pub fn page_name(&self) -> Option<String> {
+        self.label.clone().or_else(|| self.number.map(|n| n.to_string()))
+    }
+}
+
+/// A word Tesseract was unsure of, see `Inline::Uncertain`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UncertainWord {
+    pub text: String,
+    pub alternatives: Vec<String>,
+}
+
+/// How a line of `PageText::text` sits on the page, see `layout::line_layout`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineLayout {
+    pub text: String,
+
+    /// The line starts further right than the others of its column
+    pub indented: bool,
+
+    /// The line ends well short of the right edge of its column
+    pub ends_short: bool,
+}
+
+/// Lines of `PageText::text` making up paragraphs of their own style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetApart {
+    pub lines: Range<usize>,
+    pub style: ParagraphStyle,
+}
+
+/// Raised or lowered text, see `Inline::Script`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    /// Note markers, ordinal suffixes, exponents
+    Super,
+    /// Chemical formulas, indices
+    Sub,
+}
+
+/// A word with superscript or subscript parts, as byte ranges of `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedWord {
+    pub text: String,
+    pub scripts: Vec<(Range<usize>, Script)>,
+}
+
+/// A word with readings set over parts of it, as byte ranges of `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RubyWord {
+    pub text: String,
+    pub readings: Vec<(Range<usize>, String)>,
+}
+
+/// Mean OCR confidence (0-100) of the pages that have text
+pub fn mean_confidence(pages: &[PageText]) -> Option<f32> {
+    let confs: Vec<f32> = pages.iter().filter_map(|p| p.confidence).collect();
+    (!confs.is_empty()).then(|| confs.iter().sum::<f32>() / confs.len() as f32)
+}
+
+/// One book of the output: the whole input, or one work of an anthology
+#[derive(Debug, Clone)]
+pub struct Work {
+    pub title: String,
+    pub author: String,
+    pub chapters: Vec<Chapter>,
+
+    /// Reading-order problems found on the work's pages, see `order::check_pages`
+    pub reading_issues: Vec<ReadingIssue>,
+
+    /// Paragraphs of the work with unbalanced quotation marks or brackets
+    pub unbalanced: Vec<Unbalanced>,
+
+    /// Names of the work's pages in the print edition, in order, for the epub's page-list
+    pub page_names: Vec<String>,
+
+    /// Prefix of the work's anchor ids, see `namespace_ids`
+    pub id_prefix: String,
+}
@@ -0,0 +1,70 @@
+//! Detection of pages scanned twice, by a perceptual hash of the rendered page
+use image::imageops::{self, FilterType};
+use image::{GrayImage, RgbImage};
+
+/// Size of the hash grid: about one cell per printed character at book page sizes
+const GRID_WIDTH: u32 = 96;
+const GRID_HEIGHT: u32 = 128;
+
+/// Brightness below which a pixel counts as ink when finding the printed area
+const INK: u8 = 128;
+
+/// Share of the cells that may differ between two scans of the same page
+const MAX_DIFFERENCE: f32 = 0.02;
+
+/// Least brightness range of the grid for a page to count as not blank;
+/// blank pages all look alike and are never duplicates
+const MIN_CONTRAST: u8 = 24;
+
+/// What to do with a page that repeats the page before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Duplicates {
+    /// Convert it anyway, but print a warning
+    Warn,
+    /// Leave it out of the book
+    Drop,
+}
+
+/// Average hash of the printed area of a page, on a grid fine enough to tell pages of
+/// the same layout apart: one bit per grid cell, set where the cell is darker than the
+/// area on average. Cropping to the printed area makes two scans of a page placed
+/// differently on the scanner hash alike.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageHash(Vec<bool>);
+
+impl PageHash {
+    /// Hash of a rendered page, `None` for blank pages
+    pub fn of(img: &RgbImage) -> Option<PageHash> {
+        let gray = imageops::grayscale(img);
+        let (x, y, w, h) = printed_area(&gray)?;
+        let area = imageops::crop_imm(&gray, x, y, w, h).to_image();
+        let grid: GrayImage = imageops::resize(&area, GRID_WIDTH, GRID_HEIGHT, FilterType::Triangle);
+        let (min, max) = grid.pixels().fold((u8::MAX, 0), |(lo, hi), p| (lo.min(p[0]), hi.max(p[0])));
+        if max.saturating_sub(min) < MIN_CONTRAST {
+            return None;
+        }
+        let mean = grid.pixels().map(|p| p[0] as u32).sum::<u32>() / (GRID_WIDTH * GRID_HEIGHT);
+        Some(PageHash(grid.pixels().map(|p| (p[0] as u32) < mean).collect()))
+    }
+
+    /// Share of the cells that differ
+    pub fn difference(&self, other: &PageHash) -> f32 {
+        let differing = self.0.iter().zip(&other.0).filter(|(a, b)| a != b).count();
+        differing as f32 / self.0.len() as f32
+    }
+
+    pub fn is_duplicate_of(&self, other: &PageHash) -> bool {
+        self.difference(other) <= MAX_DIFFERENCE
+    }
+}
+
+/// Bounding box `(x, y, w, h)` of the ink on `gray`, `None` if there is none
+fn printed_area(gray: &GrayImage) -> Option<(u32, u32, u32, u32)> {
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, p) in gray.enumerate_pixels() {
+        if p[0] < INK {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x), y1.max(y));
+        }
+    }
+    (x0 <= x1).then(|| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
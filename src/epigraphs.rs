@@ -0,0 +1,103 @@
+//! Passages set apart from the body text at the start of a book or chapter: epigraphs
+//! with their attributions, and dedications. They are marked on the page as line
+//! ranges (`PageText::set_apart`), so that the unwrapper never merges them into the
+//! paragraphs around them.
+use crate::document::{PageText, ParagraphStyle, SetApart};
+use crate::sentences::TERMINATORS;
+
+/// Lines at the top of a chapter's first page searched for an attribution
+const SEARCH_LINES: usize = 15;
+
+/// Most words of an epigraph
+const MAX_EPIGRAPH_WORDS: usize = 80;
+
+/// Most words of an attribution line
+const MAX_ATTRIBUTION_WORDS: usize = 8;
+
+/// Longest abbreviation an attribution may end in with its period ("Anon.", "Jr.")
+const MAX_ABBREVIATION_LEN: usize = 4;
+
+/// Closing quotes a quotation may end in
+const CLOSING_QUOTES: &[char] = &['"', '\'', '”', '’', '»', '›'];
+
+/// Most lines of chapter heading above an epigraph
+const MAX_HEADING_LINES: usize = 4;
+
+/// Pages at the start of the book searched for a dedication
+const FRONT_PAGES: usize = 12;
+
+/// Most lines and words of a dedication page
+const MAX_DEDICATION_LINES: usize = 4;
+const MAX_DEDICATION_WORDS: usize = 30;
+
+/// How dedications open, lowercased
+const DEDICATION_OPENINGS: &[&str] = &[
+    "for ", "to ", "in memory", "in memoriam", "dedicated to", "für ", "pour ", "à la mémoire", "para ", "voor ",
+];
+
+/// "— Seneca", "-- Seneca, Letters": a dash and a short name, not a sentence such as a
+/// line of dialogue ("— Yes, she said.")
+fn is_attribution(line: &str) -> bool {
+    let line = line.trim();
+    let Some(name) = ["—", "–", "―", "--"].iter().find_map(|dash| line.strip_prefix(dash)).map(str::trim_start) else {
+        return false;
+    };
+    let last = name.split_whitespace().next_back().unwrap_or_default();
+    let sentence_end = match last.chars().next_back() {
+        Some('.') => last.trim_end_matches('.').chars().count() > MAX_ABBREVIATION_LEN,
+        Some(c) => TERMINATORS.contains(&c) || [';', ':'].contains(&c),
+        None => false,
+    };
+    name.starts_with(char::is_uppercase) && name.split_whitespace().count() <= MAX_ATTRIBUTION_WORDS && !sentence_end
+}
+
+/// Whether `lines` read as a finished quotation: their last ends a sentence or a quote
+fn is_quotation(lines: &[&str]) -> bool {
+    let last = lines.iter().rev().map(|l| l.trim()).find(|l| !l.is_empty()).unwrap_or_default();
+    last.ends_with(|c: char| TERMINATORS.contains(&c) || CLOSING_QUOTES.contains(&c))
+}
+
+/// Mark the epigraph of `page`, the first page of a chapter: a short quotation above an
+/// attribution line, with at most a heading before it
+pub fn mark_epigraph(page: &mut PageText) {
+    let lines: Vec<&str> = page.text.lines().collect();
+    let Some(attribution) = lines.iter().take(SEARCH_LINES).position(|l| is_attribution(l)) else {
+        return;
+    };
+    // the passage runs up from the attribution to a blank line
+    let start = lines[..attribution].iter().rposition(|l| l.trim().is_empty()).map_or(0, |b| b + 1);
+    let words: usize = lines[start..attribution].iter().map(|l| l.split_whitespace().count()).sum();
+    let heading = lines[..start].iter().filter(|l| !l.trim().is_empty()).count();
+    if start == attribution
+        || words > MAX_EPIGRAPH_WORDS
+        || heading > MAX_HEADING_LINES
+        || !is_quotation(&lines[start..attribution])
+    {
+        return;
+    }
+    // "— William Shakespeare," may go on with the work on the next line
+    let mut end = attribution + 1;
+    if lines[attribution].trim_end().ends_with(',') && lines.get(end).is_some_and(|l| !l.trim().is_empty()) {
+        end += 1;
+    }
+    page.set_apart.push(SetApart { lines: start..attribution, style: ParagraphStyle::Epigraph });
+    page.set_apart.push(SetApart { lines: attribution..end, style: ParagraphStyle::Attribution });
+}
+
+/// Mark the dedication among the first pages of the book: a page of a few short lines
+/// opening like one ("For my mother", "In memory of …"). The title page is passed over.
+pub fn mark_dedication(pages: &mut [PageText]) {
+    for page in pages.iter_mut().take(FRONT_PAGES).skip(1) {
+        let lines = page.text.lines().filter(|l| !l.trim().is_empty()).count();
+        let words = page.text.split_whitespace().count();
+        let opening = page.text.trim_start().to_lowercase();
+        if (1..=MAX_DEDICATION_LINES).contains(&lines)
+            && words <= MAX_DEDICATION_WORDS
+            && DEDICATION_OPENINGS.iter().any(|o| opening.starts_with(o))
+        {
+            let end = page.text.lines().count();
+            page.set_apart.push(SetApart { lines: 0..end, style: ParagraphStyle::Dedication });
+            return;
+        }
+    }
+}
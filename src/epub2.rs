@@ -0,0 +1,74 @@
+//! EPUB 2 output for older readers (`--epub-version 2`): content documents in XHTML 1.1,
+//! whose elements and attributes stand in for the HTML5 ones and the `epub:type`
+//! semantics of EPUB 3, which such readers reject or show as unknown
+use std::sync::LazyLock;
+use regex::{Captures, Regex};
+
+/// Document type of the XHTML 1.1 content documents
+pub const XHTML11_DOCTYPE: &str =
+    r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.1//EN" "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd">"#;
+
+/// `<figure id=".." class="..">` and the like, which become `div`s
+static FIGURE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<figure((?: id="[^"]*")?)(?: class="([^"]*)")?>"#).unwrap());
+
+/// The `lang` attribute, which XHTML 1.1 leaves to `xml:lang`
+static LANG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#" lang="[^"]*""#).unwrap());
+
+/// The figure selectors of the stylesheet
+static FIGURE_SELECTOR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bfig(ure|caption)\b").unwrap());
+
+/// The XHTML 1.1 equivalent of the body `markup` of an EPUB 3 content document: figures
+/// and footnotes as `div`s with a class, highlights as `span`s, note references by class
+pub fn downgrade_markup(markup: &str) -> String {
+    let markup = FIGURE_RE.replace_all(markup, |c: &Captures| match c.get(2) {
+        Some(class) => format!(r#"<div{} class="figure {}">"#, &c[1], class.as_str()),
+        None => format!(r#"<div{} class="figure">"#, &c[1]),
+    });
+    let markup = LANG_RE.replace_all(&markup, "");
+    markup
+        .replace("</figure>", "</div>")
+        .replace("<figcaption>", r#"<p class="caption">"#)
+        .replace("</figcaption>", "</p>")
+        .replace(r#"<aside epub:type="footnote""#, r#"<div class="footnote""#)
+        .replace("</aside>", "</div>")
+        .replace(r#" epub:type="noteref""#, r#" class="noteref""#)
+        .replace("<mark", r#"<span class="highlight""#)
+        .replace("</mark>", "</span>")
+}
+
+/// The stylesheet `css` with its figure rules pointed at the `div`s of `downgrade_markup`
+pub fn downgrade_css(css: &str) -> String {
+    FIGURE_SELECTOR_RE
+        .replace_all(css, |c: &Captures| match &c[1] {
+            "ure" => "div.figure",
+            _ => "p.caption",
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup() {
+        let markup = concat!(
+            r#"<figure id="fig-1" class="wide"><img src="a.png" alt=""/><figcaption>A map</figcaption></figure>"#,
+            r##"<p lang="fr" xml:lang="fr">Texte<a epub:type="noteref" href="#fn-1">1</a> <mark>vu</mark></p>"##,
+            r#"<aside epub:type="footnote" id="fn-1"><p>Note</p></aside><figure><img src="b.png" alt=""/></figure>"#,
+        );
+        assert_eq!(
+            downgrade_markup(markup),
+            concat!(
+                r#"<div id="fig-1" class="figure wide"><img src="a.png" alt=""/><p class="caption">A map</p></div>"#,
+                r##"<p xml:lang="fr">Texte<a class="noteref" href="#fn-1">1</a> <span class="highlight">vu</span></p>"##,
+                r#"<div class="footnote" id="fn-1"><p>Note</p></div><div class="figure"><img src="b.png" alt=""/></div>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn css() {
+        assert_eq!(downgrade_css("figure img, figcaption { margin: 0 }"), "div.figure img, p.caption { margin: 0 }");
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::io::Cursor;
+use std::sync::LazyLock;
+use regex::Regex;
+use pdfium_render::prelude::*;
+use image::{DynamicImage, GrayImage, RgbImage, ImageFormat, Rgb};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use anyhow::Context;
+use crate::ocr::{ocr_rgb_lines, OcrLine, OcrOptions, Rect};
+use crate::document::{self, Block, Chapter, Figure, Inline};
+use crate::Pdf2EPubErr;
+
+/// Caption lead-ins such as "Figure 3:", "Fig. 2.1", "PLATE IV", "Map 2"
+static CAPTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(fig\.?|figure|plate|illustration|map|chart|diagram)\s*[0-9ivxlc]+[.:)]?")
+        .expect("caption regex")
+});
+
+/// "Figure 3", "Fig. 2.1", "plate IV" anywhere in a text: group 1 is the kind of figure,
+/// group 2 its number
+static FIGURE_LABEL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(figs?\.|fig\b|figure|plate|illustration|map|chart|diagram)\s*(\d+(?:\.\d+)*|[ivxlc]+)\b")
+        .expect("figure label regex")
+});
+
+/// Printed label of a figure (its kind and number, lowercase) -> its id and new number
+type FigureLabels = HashMap<(String, String), (String, usize)>;
+
+/// Image objects covering more than this fraction of the page are scan backgrounds
+pub const MAX_FIGURE_AREA: f32 = 0.8;
+
+/// Image objects narrower/shorter than this fraction of the page are ornaments
+pub const MIN_FIGURE_SIDE: f32 = 0.05;
+
+/// Width in pixels of the thumbnails standing in for unreadable pages
+const THUMBNAIL_WIDTH: u32 = 300;
+
+/// How far below a figure (as a fraction of page height) a caption is searched for
+const CAPTION_SEARCH_HEIGHT: f32 = 0.2;
+
+/// An embedded image object located on a page
+pub struct PageImage {
+    /// Where the image is drawn, in rendered-page pixels
+    pub region: Rect,
+
+    /// The image's own data and media type, when it can be taken over without re-rasterizing
+    pub original: Option<(Vec<u8>, &'static str)>,
+}
+
+/// Read the encoded stream of a JPEG (DCTDecode) image object without decoding it.
+/// Images that need a mask or are CMYK (poorly supported by readers) are declined.
+fn raw_jpeg_stream(page: &PdfPage, obj: &PdfPageObject) -> Option<Vec<u8>> {
+    let image = obj.as_image_object()?;
+    let filters: Vec<String> = image.filters().iter().map(|f| f.name().to_string()).collect();
+    if filters != ["DCTDecode"] || obj.has_transparency() {
+        return None;
+    }
+    if matches!(image.color_space(), Ok(PdfColorSpace::DeviceCMYK)) {
+        return None;
+    }
+
+    // pdfium-render has no safe accessor for the undecoded stream
+    let bindings = page.bindings();
+    let handle = bindings.get_handle_from_object(obj);
+    let len = bindings.FPDFImageObj_GetImageDataRaw(handle, std::ptr::null_mut(), 0);
+    if len == 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; len as usize];
+    let written = bindings.FPDFImageObj_GetImageDataRaw(handle, buf.as_mut_ptr() as *mut c_void, len);
+    (written == len).then_some(buf)
+}
+
+/// Losslessly encode an image object's pixels, at their native resolution, as PNG.
+/// The object's transform is not applied, so masked images are left to the renderer.
+fn native_png(obj: &PdfPageObject) -> Option<Vec<u8>> {
+    let image = obj.as_image_object()?;
+    if obj.has_transparency() {
+        return None;
+    }
+    let pixels = image.get_raw_image().ok()?;
+    let mut data = Vec::new();
+    pixels.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).ok()?;
+    Some(data)
+}
+
+/// Locate the embedded image objects of `page` in the pixel space of a render at `target_dpi`.
+/// Full-page scans and tiny decorations are ignored.
+pub fn page_images(page: &PdfPage, target_dpi: u16) -> Vec<PageImage> {
+    let scale = target_dpi as f32 / 72.0;
+    let page_w = page.width().value;
+    let page_h = page.height().value;
+
+    page.objects()
+        .iter()
+        .filter(|obj| obj.object_type() == PdfPageObjectType::Image)
+        .filter_map(|obj| obj.bounds().ok().map(|b| (obj, b)))
+        .filter(|(_, b)| {
+            let (w, h) = (b.width().value, b.height().value);
+            w * h < MAX_FIGURE_AREA * page_w * page_h
+                && w > MIN_FIGURE_SIDE * page_w
+                && h > MIN_FIGURE_SIDE * page_h
+        })
+        .map(|(obj, b)| {
+            let left = b.left().value.clamp(0.0, page_w);
+            let top = b.top().value.clamp(0.0, page_h);
+            let right = b.right().value.clamp(0.0, page_w);
+            let bottom = b.bottom().value.clamp(0.0, page_h);
+            // PDF space has its origin in the bottom left corner
+            let region = Rect {
+                x: (left * scale) as u32,
+                y: ((page_h - top) * scale) as u32,
+                w: ((right - left) * scale) as u32,
+                h: ((top - bottom) * scale) as u32,
+            };
+            let original = raw_jpeg_stream(page, &obj)
+                .map(|data| (data, "image/jpeg"))
+                .or_else(|| native_png(&obj).map(|data| (data, "image/png")));
+            PageImage { region, original }
+        })
+        .collect()
+}
+
+/// Keep the leading lines of `lines` that form a caption: the first one must start
+/// with a caption lead-in, and the following ones must be vertically adjacent.
+fn caption_lines(lines: &[OcrLine]) -> Option<(String, Rect)> {
+    let first = lines.first()?;
+    let first_text = first.text();
+    if !CAPTION_RE.is_match(&first_text) {
+        return None;
+    }
+
+    let mut text = first_text;
+    let mut rect = first.bbox;
+    let mut prev = first.bbox;
+    for line in &lines[1..] {
+        let gap = line.bbox.y.saturating_sub(prev.bottom());
+        if gap > prev.h {
+            break;
+        }
+        if text.ends_with('-') {
+            text.pop();
+        } else {
+            text.push(' ');
+        }
+        text.push_str(&line.text());
+        rect = rect.union(&line.bbox);
+        prev = line.bbox;
+    }
+    Some((text, rect))
+}
+
+/// OCR the strip below `region` and return its caption text and extent (in page pixels)
+pub fn find_caption(img: &RgbImage, region: &Rect, ocr: &OcrOptions) -> Result<Option<(String, Rect)>, Pdf2EPubErr> {
+    let top = region.bottom().min(img.height());
+    let h = ((img.height() as f32 * CAPTION_SEARCH_HEIGHT) as u32).min(img.height() - top);
+    if h == 0 {
+        return Ok(None);
+    }
+
+    let strip = image::imageops::crop_imm(img, region.x, top, region.w, h).to_image();
+    let lines = ocr_rgb_lines(&strip, ocr)?;
+
+    // the caption has to start right below the figure, not further down the page
+    let near_top = lines.first().map(|l| l.bbox.y < 2 * l.bbox.h.max(1)).unwrap_or(false);
+    if !near_top {
+        return Ok(None);
+    }
+
+    Ok(caption_lines(&lines).map(|(text, r)| {
+        (text, Rect { x: r.x + region.x, y: r.y + top, ..r })
+    }))
+}
+
+/// Alt text derived from a caption: the lead-in ("Figure 3:") is dropped
+/// unless that is all the caption says.
+pub fn alt_from_caption(caption: &str) -> String {
+    let rest = CAPTION_RE.replace(caption, "");
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || "-–—:.".contains(c)).trim();
+    if rest.is_empty() { caption.trim().to_string() } else { rest.to_string() }
+}
+
+/// Crop `region` out of the rendered page as a PNG figure
+pub fn crop_figure(
+    img: &RgbImage,
+    region: &Rect,
+    name: String,
+    page: usize,
+) -> Result<Figure, Pdf2EPubErr> {
+    let crop = image::imageops::crop_imm(img, region.x, region.y, region.w, region.h).to_image();
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(crop)
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .context("failed to encode figure PNG")?;
+    Ok(Figure { name, data, mime: "image/png", page, caption: None, alt: None, full: None, mathml: None })
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, Pdf2EPubErr> {
+    let mut data = Vec::new();
+    img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .context("failed to encode page PNG")?;
+    Ok(data)
+}
+
+/// A "List of Figures": a link to every captioned figure of `chapters`, by its caption, in
+/// order. Page scans standing in for their text are not listed.
+pub fn list_of_figures<'a>(chapters: impl IntoIterator<Item = &'a Chapter>) -> Vec<Block> {
+    chapters
+        .into_iter()
+        .flat_map(|c| document::figures(&c.blocks))
+        .filter(|f| f.full.is_none())
+        .filter_map(|f| {
+            let caption = f.caption.as_ref()?;
+            Some(Block::Paragraph(vec![Inline::Link { href: format!("#{}", f.id()), text: caption.clone() }]))
+        })
+        .collect()
+}
+
+/// Kind of figure a label lead-in names, as written in numbered captions: "Fig." is a "Figure"
+fn label_kind(word: &str) -> String {
+    let word = word.trim_end_matches('.').to_lowercase();
+    match word.as_str() {
+        "fig" | "figs" | "figure" => "Figure".to_string(),
+        other => {
+            let mut chars = other.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+    }
+}
+
+/// Split `text` at its references to the figures in `labels`, which become links numbered
+/// as their figures now are
+fn link_labels(text: &str, labels: &FigureLabels) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    for m in FIGURE_LABEL_RE.captures_iter(text) {
+        let (Some(whole), Some(number)) = (m.get(0), m.get(2)) else { continue };
+        let Some((id, n)) = labels.get(&(label_kind(&m[1]), number.as_str().to_lowercase())) else { continue };
+        if whole.start() > start {
+            inlines.push(Inline::Text(text[start..whole.start()].to_string()));
+        }
+        let label = format!("{}{}", &text[whole.start()..number.start()], n);
+        inlines.push(Inline::Link { href: format!("#{}", id), text: label });
+        start = whole.end();
+    }
+    if start < text.len() {
+        inlines.push(Inline::Text(text[start..].to_string()));
+    }
+    inlines
+}
+
+/// Number the captioned figures of `blocks` in order, one count per kind ("Figure 1",
+/// "Figure 2", "Plate 1"), whatever their printed numbers, and link the references to
+/// them in the text ("see Fig. 2.3") to the figures, renumbered alike
+pub fn number_figures<'a>(blocks: impl IntoIterator<Item = &'a mut Block>) {
+    let mut blocks: Vec<&mut Block> = blocks.into_iter().collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut labels = FigureLabels::new();
+    for block in blocks.iter_mut() {
+        let Block::Figure(f) = &mut **block else { continue };
+        if f.full.is_some() {
+            continue;
+        }
+        let id = f.id().to_string();
+        let Some(caption) = &mut f.caption else { continue };
+        let Some(m) = FIGURE_LABEL_RE.captures(caption).filter(|m| m.get(0).is_some_and(|w| w.start() == 0)) else {
+            continue;
+        };
+        let (kind, printed, end) = (label_kind(&m[1]), m[2].to_lowercase(), m.get(0).map_or(0, |w| w.end()));
+        let n = counts.entry(kind.clone()).or_default();
+        *n += 1;
+        *caption = format!("{} {}{}", kind, n, &caption[end..]);
+        labels.entry((kind, printed)).or_insert((id, *n));
+    }
+    if labels.is_empty() {
+        return;
+    }
+    for block in blocks {
+        if let Some(inlines) = block.inlines_mut() {
+            *inlines = inlines
+                .drain(..)
+                .flat_map(|i| match i {
+                    Inline::Text(t) => link_labels(&t, &labels),
+                    other => vec![other],
+                })
+                .collect();
+        }
+    }
+}
+
+/// A page whose text could not be used, as a thumbnail for its place in the text that
+/// links to the full page image. `stem` names both images (`page-57-scan-thumb.png`
+/// and `page-57-scan.png`).
+pub fn page_fallback(img: GrayImage, stem: &str, page: usize, caption: String) -> Result<Figure, Pdf2EPubErr> {
+    let img = DynamicImage::ImageLuma8(img);
+    let thumb = img.resize(THUMBNAIL_WIDTH, u32::MAX, FilterType::Triangle);
+    let full = Figure {
+        name: format!("{}.png", stem),
+        data: encode_png(&img)?,
+        mime: "image/png",
+        page,
+        caption: None,
+        alt: Some(format!("Scan of page {}", page)),
+        full: None,
+        mathml: None,
+    };
+    Ok(Figure {
+        name: format!("{}-thumb.png", stem),
+        data: encode_png(&thumb)?,
+        mime: "image/png",
+        page,
+        caption: Some(caption),
+        alt: Some(format!("Scan of page {}", page)),
+        full: Some(Box::new(full)),
+        mathml: None,
+    })
+}
+
+/// Paint `region` white so it is ignored by OCR
+pub fn mask_region(img: &mut RgbImage, region: &Rect) {
+    let x_end = region.right().min(img.width());
+    let y_end = region.bottom().min(img.height());
+    for y in region.y..y_end {
+        for x in region.x..x_end {
+            img.put_pixel(x, y, Rgb([255, 255, 255]));
+        }
+    }
+}
+
+/// Look for a caption below `region`, attach it to `fig` and mask it out of `img`
+pub fn attach_caption(
+    img: &mut RgbImage,
+    region: &Rect,
+    fig: &mut Figure,
+    ocr: &OcrOptions,
+) -> Result<(), Pdf2EPubErr> {
+    if let Some((caption, caption_rect)) = find_caption(img, region, ocr)? {
+        mask_region(img, &caption_rect);
+        fig.alt = Some(alt_from_caption(&caption));
+        fig.caption = Some(caption);
+    }
+    Ok(())
+}
+
+/// Pull the figures (with their captions) out of a rendered page.
+/// Embedded JPEGs are passed through untouched and other images are taken at their
+/// native resolution; only images that need the renderer are cropped from `img`.
+/// The figure and caption areas are masked in `img` so that the subsequent
+/// page OCR neither sees image noise nor merges the caption into the body text.
+pub fn extract_figures(
+    page: &PdfPage,
+    page_index: usize,
+    img: &mut RgbImage,
+    target_dpi: u16,
+    ocr: &OcrOptions,
+) -> Result<Vec<Figure>, Pdf2EPubErr> {
+    let mut figures = Vec::new();
+    for (n, PageImage { region, original }) in page_images(page, target_dpi).into_iter().enumerate() {
+        let stem = format!("page-{}-fig-{}", page_index + 1, n + 1);
+        let mut fig = match original {
+            Some((data, mime)) => {
+                let ext = if mime == "image/jpeg" { "jpg" } else { "png" };
+                Figure {
+                    name: format!("{}.{}", stem, ext),
+                    data,
+                    mime,
+                    page: page_index + 1,
+                    caption: None,
+                    alt: None,
+                    full: None,
+                    mathml: None,
+                }
+            }
+            None => crop_figure(img, &region, format!("{}.png", stem), page_index + 1)?,
+        };
+        attach_caption(img, &region, &mut fig, ocr)?;
+        mask_region(img, &region);
+        figures.push(fig);
+    }
+    Ok(figures)
+}
+
+/// Encoding used for figures in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FigureFormat {
+    Jpeg,
+    Png,
+    /// lossless WebP (the `image` crate has no lossy WebP encoder)
+    Webp,
+}
+
+impl FigureFormat {
+    fn from_mime(mime: &str) -> Option<FigureFormat> {
+        match mime {
+            "image/jpeg" => Some(FigureFormat::Jpeg),
+            "image/png" => Some(FigureFormat::Png),
+            "image/webp" => Some(FigureFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            FigureFormat::Jpeg => "image/jpeg",
+            FigureFormat::Png => "image/png",
+            FigureFormat::Webp => "image/webp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            FigureFormat::Jpeg => "jpg",
+            FigureFormat::Png => "png",
+            FigureFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Size controls applied to raster figures before they are embedded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageOptions {
+    /// Downscale figures wider than this many pixels
+    pub max_width: Option<u32>,
+
+    /// Re-encode figures in this format
+    pub format: Option<FigureFormat>,
+
+    /// JPEG quality (1-100)
+    pub quality: Option<u8>,
+}
+
+/// JPEG quality used when a figure has to be re-encoded and no quality was given
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// Downscale and/or re-encode a raster figure according to `opts`.
+/// Figures that need no change (and vector figures) are returned untouched,
+/// so passed-through images are only recompressed when asked to.
+pub fn recompress(fig: Figure, opts: &ImageOptions) -> Result<Figure, Pdf2EPubErr> {
+    let Some(current) = FigureFormat::from_mime(fig.mime) else {
+        return Ok(fig);
+    };
+    let target = opts.format.unwrap_or(current);
+
+    let mut img = image::load_from_memory(&fig.data).context("failed to decode figure")?;
+    let resize = opts.max_width.map(|w| img.width() > w).unwrap_or(false);
+    let requality = target == FigureFormat::Jpeg && opts.quality.is_some();
+    if !resize && target == current && !requality {
+        return Ok(fig);
+    }
+
+    if let Some(max_w) = opts.max_width.filter(|_| resize) {
+        img = img.resize(max_w, u32::MAX, FilterType::Lanczos3);
+    }
+
+    let mut data = Vec::new();
+    match target {
+        FigureFormat::Jpeg => {
+            let quality = opts.quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+            JpegEncoder::new_with_quality(&mut data, quality)
+                .encode_image(&DynamicImage::ImageRgb8(img.to_rgb8()))
+                .context("failed to encode figure JPEG")?;
+        }
+        FigureFormat::Png => {
+            img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+                .context("failed to encode figure PNG")?;
+        }
+        FigureFormat::Webp => {
+            DynamicImage::ImageRgba8(img.to_rgba8())
+                .write_to(&mut Cursor::new(&mut data), ImageFormat::WebP)
+                .context("failed to encode figure WebP")?;
+        }
+    }
+
+    let name = format!("{}.{}", fig.id(), target.extension());
+    Ok(Figure { name, data, mime: target.mime(), ..fig })
+}
@@ -0,0 +1,75 @@
+use crate::document::PageText;
+
+/// What to do with the detected front matter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FrontMatter {
+    /// Leave it out of the book
+    Drop,
+    /// Move it to an appendix after the main text
+    Appendix,
+}
+
+/// Front matter is only looked for in this many leading pages
+const MAX_FRONT_MATTER_PAGES: usize = 16;
+
+/// Pages with fewer words than this are title, half-title, dedication or blank pages
+const SHORT_PAGE_WORDS: usize = 40;
+
+/// Phrases found on copyright, CIP and printer's pages (matched lowercase)
+const FRONT_MATTER_MARKERS: &[&str] = &[
+    "©",
+    "copyright",
+    "all rights reserved",
+    "isbn",
+    "library of congress",
+    "cataloging-in-publication",
+    "cataloguing in publication",
+    "british library",
+    "first published",
+    "printed in",
+    "printed and bound",
+    "published by",
+    "reproduced, stored",
+    "no part of this",
+    "10 9 8 7 6 5 4 3 2 1",
+];
+
+/// Headings of pages that belong to the front matter
+const FRONT_MATTER_HEADINGS: &[&str] = &["contents", "table of contents", "also by", "dedication"];
+
+fn first_line(text: &str) -> String {
+    text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("").to_lowercase()
+}
+
+/// Whether a page looks like part of the front matter
+pub fn is_front_matter(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    let words = text.split_whitespace().count();
+    let heading = first_line(text);
+    words < SHORT_PAGE_WORDS
+        || FRONT_MATTER_MARKERS.iter().filter(|m| lower.contains(*m)).count() >= 2
+        || FRONT_MATTER_HEADINGS.iter().any(|h| heading.starts_with(h))
+}
+
+/// Whether a page is the copyright (impressum) page
+pub fn is_copyright_page(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    (lower.contains("copyright") || lower.contains('©') || lower.contains("impressum"))
+        && FRONT_MATTER_MARKERS.iter().filter(|m| lower.contains(*m)).count() >= 2
+}
+
+/// Index of the copyright page among the leading pages, if there is one
+pub fn find_copyright_page(pages: &[PageText]) -> Option<usize> {
+    pages.iter().take(MAX_FRONT_MATTER_PAGES).position(|p| is_copyright_page(&p.text))
+}
+
+/// Number of leading pages that are front matter: the run of front-matter
+/// pages at the start of the book, stopping at the first page of running text.
+/// At least one page is always left for the book itself.
+pub fn front_matter_len(pages: &[PageText]) -> usize {
+    pages
+        .iter()
+        .take(MAX_FRONT_MATTER_PAGES.min(pages.len().saturating_sub(1)))
+        .take_while(|p| is_front_matter(&p.text))
+        .count()
+}
@@ -0,0 +1,128 @@
+//! The book's glossary: names and invented words that post-processing commands
+//! (spellcheckers, LLM cleanup) may not lowercase or "correct"
+use std::collections::{HashMap, HashSet};
+use crate::document::{plain_text, Chapter};
+use crate::scripts::WritingScript;
+
+/// Times a capitalised word must occur mid-sentence to be taken for a name
+const MIN_OCCURRENCES: usize = 3;
+
+/// Word pairs above which paragraphs are not aligned word by word
+const MAX_ALIGNMENT: usize = 1_000_000;
+
+/// A word without the punctuation around it
+fn core(word: &str) -> &str {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+/// Capitalised, but not all capitals: "Hermione", not "CHAPTER" or "I"
+fn is_capitalised(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_uppercase) && word.chars().any(char::is_lowercase)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+    terms: HashSet<String>,
+
+    /// The script of the book; words in other scripts are kept too, see `keep_other_scripts`
+    script: Option<WritingScript>,
+}
+
+impl Glossary {
+    /// The capitalised words of `chapters` found at least `MIN_OCCURRENCES` times in the
+    /// middle of a sentence, and the `extra` words (e.g. `--user-words`)
+    pub fn build<'a>(chapters: impl IntoIterator<Item = &'a Chapter>, extra: impl IntoIterator<Item = String>) -> Glossary {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for chapter in chapters {
+            for text in chapter.blocks.iter().filter_map(|b| b.inlines()).map(plain_text) {
+                let words: Vec<&str> = text.split_whitespace().collect();
+                for pair in words.windows(2) {
+                    let sentence_start = pair[0].ends_with(['.', '?', '!', ':', '"', '”', '’']);
+                    let word = core(pair[1]);
+                    if !sentence_start && is_capitalised(word) {
+                        *counts.entry(word.to_string()).or_default() += 1;
+                    }
+                }
+            }
+        }
+        let terms = counts
+            .into_iter()
+            .filter(|(_, n)| *n >= MIN_OCCURRENCES)
+            .map(|(w, _)| w)
+            .chain(extra.into_iter().map(|w| w.trim().to_string()).filter(|w| !w.is_empty()))
+            .collect();
+        Glossary { terms, script: None }
+    }
+
+    /// Also keep every word written in another script than `script`, the book's
+    pub fn keep_other_scripts(self, script: WritingScript) -> Glossary {
+        Glossary { script: Some(script), ..self }
+    }
+
+    fn keeps(&self, word: &str) -> bool {
+        self.terms.contains(word)
+            || self.script.is_some_and(|main| WritingScript::of_word(word).is_some_and(|s| s != main))
+    }
+
+    /// The terms, sorted
+    pub fn terms(&self) -> Vec<&str> {
+        let mut terms: Vec<&str> = self.terms.iter().map(String::as_str).collect();
+        terms.sort_unstable();
+        terms
+    }
+
+    /// `new`, a correction of paragraph `old`, with the glossary words it replaced one
+    /// for one (lowercased, respelled) put back
+    pub fn protect(&self, old: &str, new: &str) -> String {
+        let old_words: Vec<&str> = old.split_whitespace().collect();
+        let mut words: Vec<String> = new.split_whitespace().map(str::to_string).collect();
+        if old_words.len() * words.len() > MAX_ALIGNMENT {
+            return new.to_string();
+        }
+        let mut changed = false;
+        for (i, j) in replaced_words(&old_words, &words) {
+            if self.keeps(core(old_words[i])) {
+                words[j] = old_words[i].to_string();
+                changed = true;
+            }
+        }
+        if changed { words.join(" ") } else { new.to_string() }
+    }
+}
+
+/// Index pairs of the words of `new` that replace a word of `old` one for one, by a
+/// longest common subsequence alignment of the two
+fn replaced_words(old: &[&str], new: &[String]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j]: longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    // start of the current run of differing words in both
+    let (mut run_i, mut run_j) = (0, 0);
+    // a run of differing words is a replacement when both sides are as long
+    fn close_run(i: usize, j: usize, run_i: usize, run_j: usize, pairs: &mut Vec<(usize, usize)>) {
+        if i - run_i == j - run_j {
+            pairs.extend((run_i..i).zip(run_j..j));
+        }
+    }
+    while i < n && j < m {
+        if old[i] == new[j] {
+            close_run(i, j, run_i, run_j, &mut pairs);
+            i += 1;
+            j += 1;
+            (run_i, run_j) = (i, j);
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    close_run(n, m, run_i, run_j, &mut pairs);
+    pairs
+}
@@ -0,0 +1,131 @@
+//! Golden-file tests of the text cleaning pipeline.
+//!
+//! Each `tests/golden/NAME.pages` fixture holds the printed text of a few pages,
+//! separated by `---` lines, page numbers included as they appear on the page.
+//! `NAME.expected` is the cleaned output: the peeled page numbers and the
+//! unwrapped paragraphs. After an intended change of the heuristics, run the
+//! tests with `UPDATE_GOLDEN=1` to rewrite the expected files.
+use std::path::{Path, PathBuf};
+use pdfium_render::prelude::*;
+use crate::document::{plain_text, Block, PageText};
+use crate::ocr::{ocr_rgb_png, OcrOptions};
+use crate::paragraphs::ParagraphRules;
+use crate::{img_source_from_page, peel_trailing_page_num, unwrap_pages, RENDER_DPI};
+
+/// Type size and line spacing of the synthetic PDF pages, in points
+const FONT_SIZE: f32 = 12.0;
+const LEADING: f32 = 16.0;
+const MARGIN: f32 = 54.0;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Name and page texts of every fixture
+fn fixtures() -> Vec<(String, Vec<String>)> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(golden_dir())
+        .expect("tests/golden")
+        .map(|e| e.expect("fixture entry").path())
+        .filter(|p| p.extension().is_some_and(|e| e == "pages"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|p| {
+            let name = p.file_stem().expect("fixture name").to_string_lossy().into_owned();
+            let text = std::fs::read_to_string(&p).expect("fixture text");
+            (name, text.split("\n---\n").map(str::to_string).collect())
+        })
+        .collect()
+}
+
+/// Clean the raw text of consecutive pages as `main` does with `--extract-pagenum`
+fn clean(raw_pages: &[String]) -> String {
+    let pages: Vec<PageText> = raw_pages
+        .iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            let (text, number) = peel_trailing_page_num(raw);
+            PageText {
+                page: i + 1,
+                number,
+                text: text.to_string(),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let numbers: Vec<String> = pages
+        .iter()
+        .map(|p| p.number.map_or("-".to_string(), |n| n.to_string()))
+        .collect();
+
+    let mut out = format!("pages: {}\n", numbers.join(" "));
+    for block in unwrap_pages(pages, false, &ParagraphRules::for_language("en")) {
+        if let Block::Paragraph(p) = block {
+            out.push('\n');
+            out.push_str(&plain_text(&p));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Compare `actual` with the fixture's expected output, or rewrite it with `UPDATE_GOLDEN`
+fn check(name: &str, actual: &str) {
+    let path = golden_dir().join(format!("{}.expected", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).expect("write expected output");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    assert_eq!(actual, expected, "cleaned output of `{}` differs from {}", name, path.display());
+}
+
+/// Typeset the fixture pages into a PDF, one text object per line in Times Roman
+fn make_pdf<'a>(pdfium: &'a Pdfium, pages: &[String]) -> Result<PdfDocument<'a>, PdfiumError> {
+    let mut doc = pdfium.create_new_pdf()?;
+    let font = doc.fonts_mut().times_roman();
+    for text in pages {
+        let mut page = doc.pages_mut().create_page_at_end(PdfPagePaperSize::from_inches(5.5, 8.5))?;
+        let top = page.height().value - MARGIN;
+        for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            page.objects_mut().create_text_object(
+                PdfPoints::new(MARGIN),
+                PdfPoints::new(top - i as f32 * LEADING),
+                line,
+                font,
+                PdfPoints::new(FONT_SIZE),
+            )?;
+        }
+    }
+    Ok(doc)
+}
+
+#[test]
+fn golden_text() {
+    for (name, pages) in fixtures() {
+        check(&name, &clean(&pages));
+    }
+}
+
+/// The whole pipeline: typeset each fixture, render and OCR it, then clean the result
+#[test]
+#[ignore = "needs the pdfium library in ./pdfium/lib and Tesseract"]
+fn golden_pdf() {
+    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./pdfium/lib"))
+        .expect("pdfium library");
+    let pdfium = Pdfium::new(bindings);
+    for (name, pages) in fixtures() {
+        let doc = make_pdf(&pdfium, &pages).expect("synthetic pdf");
+        let raw_pages: Vec<String> = doc
+            .pages()
+            .iter()
+            .map(|page| {
+                let img = img_source_from_page(&page, RENDER_DPI, true).expect("render");
+                ocr_rgb_png(&img, &OcrOptions::default()).expect("ocr").0
+            })
+            .collect();
+        check(&name, &clean(&raw_pages));
+    }
+}
@@ -0,0 +1,178 @@
+//! Post-processing commands run over the cleaned text of every chapter, before the
+//! epub is written.
+//!
+//! A command is run through `sh -c` once per chapter, with the chapter's text on stdin:
+//! one paragraph per blank-line separated block, figures as `[[figure:NAME]]` lines and
+//! section breaks as `[[break]]` lines.
+//! Its stdout, in the same form, replaces the chapter. The chapter title and its 1-based
+//! position are in `PDF2EPUB_CHAPTER_TITLE` and `PDF2EPUB_CHAPTER_INDEX`.
+//! Paragraphs the command leaves unchanged keep their page anchors and links.
+//!
+//! With a glossary (`--protect-names`), its terms are in `PDF2EPUB_GLOSSARY`, one per
+//! line, and words of it the command replaces are put back; so are words in another
+//! script than the book's with `--script-runs`.
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::document::{plain_text, Block, Chapter, Figure, Inline};
+use crate::glossary::Glossary;
+use crate::Pdf2EPubErr;
+
+/// A section break in the text handed to hooks
+const SECTION_BREAK_MARKER: &str = "[[break]]";
+
+fn figure_marker(fig: &Figure) -> String {
+    format!("[[figure:{}]]", fig.name)
+}
+
+/// The chapter in the text form handed to hooks
+pub fn chapter_text(chapter: &Chapter) -> String {
+    let paras: Vec<String> = chapter
+        .blocks
+        .iter()
+        .map(|b| match b {
+            Block::Paragraph(p) | Block::Styled(_, p) => plain_text(p),
+            Block::Figure(f) => figure_marker(f),
+            Block::Break => SECTION_BREAK_MARKER.to_string(),
+        })
+        .collect();
+    let mut text = paras.join("\n\n");
+    text.push('\n');
+    text
+}
+
+/// Rebuild the blocks of `chapter` from a hook's `output`
+pub fn apply_text(chapter: &mut Chapter, output: &str) {
+    let old = std::mem::take(&mut chapter.blocks);
+    let mut figures: Vec<Figure> = Vec::new();
+    let mut paragraphs: Vec<(String, Block)> = Vec::new();
+    for block in old {
+        match block {
+            Block::Figure(f) => figures.push(f),
+            Block::Break => {}
+            para => paragraphs.push((para.inlines().map(plain_text).unwrap_or_default(), para)),
+        }
+    }
+
+    for para in output.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if para == SECTION_BREAK_MARKER {
+            chapter.blocks.push(Block::Break);
+            continue;
+        }
+        let block = match para.strip_prefix("[[figure:").and_then(|p| p.strip_suffix("]]")) {
+            Some(name) => match figures.iter().position(|f| f.name == name) {
+                Some(i) => Block::Figure(figures.remove(i)),
+                None => continue,
+            },
+            None => match paragraphs.iter().position(|(text, _)| text == para) {
+                Some(i) => paragraphs.remove(i).1,
+                None => Block::Paragraph(vec![Inline::Text(para.to_string())]),
+            },
+        };
+        chapter.blocks.push(block);
+    }
+}
+
+/// `output` of a hook run over `input` with the glossary words it replaced put back,
+/// paragraph by paragraph; left alone when the hook merged or split paragraphs
+fn protect_text(glossary: &Glossary, input: &str, output: &str) -> String {
+    let paragraphs = |text: &'_ str| -> Vec<String> {
+        text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect()
+    };
+    let (old, new) = (paragraphs(input), paragraphs(output));
+    if old.len() != new.len() {
+        return output.to_string();
+    }
+    old.iter().zip(&new).map(|(o, n)| glossary.protect(o, n)).collect::<Vec<_>>().join("\n\n")
+}
+
+/// Run `cmd` over the `n`-th (1-based) chapter and return its output
+fn run_hook(cmd: &str, chapter: &Chapter, n: usize, glossary: Option<&Glossary>) -> Result<String, Pdf2EPubErr> {
+    let err = |msg: String| Pdf2EPubErr::HookError(format!("`{}` on chapter {}: {}", cmd, n, msg));
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(cmd)
+        .env("PDF2EPUB_CHAPTER_TITLE", &chapter.title)
+        .env("PDF2EPUB_CHAPTER_INDEX", n.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if let Some(glossary) = glossary {
+        command.env("PDF2EPUB_GLOSSARY", glossary.terms().join("\n"));
+    }
+    let mut child = command.spawn().map_err(|e| err(e.to_string()))?;
+
+    let input = chapter_text(chapter);
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    // a writer thread, so a command writing while it reads cannot fill both pipes
+    let output = std::thread::scope(|s| {
+        let writer = s.spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output();
+        (writer.join().expect("hook input writer"), output)
+    });
+    let output = match output {
+        (_, Err(e)) => return Err(err(e.to_string())),
+        (Err(e), Ok(_)) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(err(e.to_string())),
+        (_, Ok(output)) => output,
+    };
+    if !output.status.success() {
+        return Err(err(format!("exited with {}", output.status)));
+    }
+    String::from_utf8(output.stdout).map_err(|_| err("output is not UTF-8".to_string()))
+}
+
+/// Pass every chapter through each of `cmds` in turn, keeping the words of `glossary`
+pub fn post_process<'a>(
+    cmds: &[String],
+    chapters: impl IntoIterator<Item = &'a mut Chapter>,
+    glossary: Option<&Glossary>,
+) -> Result<(), Pdf2EPubErr> {
+    if cmds.is_empty() {
+        return Ok(());
+    }
+    for (i, chapter) in chapters.into_iter().enumerate() {
+        for cmd in cmds {
+            let mut output = run_hook(cmd, chapter, i + 1, glossary)?;
+            if let Some(glossary) = glossary {
+                output = protect_text(glossary, &chapter_text(chapter), &output);
+            }
+            apply_text(chapter, &output);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_applied() {
+        let figure = Figure {
+            name: "fig-1.png".to_string(),
+            data: Vec::new(),
+            mime: "image/png",
+            page: 3,
+            caption: None,
+            alt: None,
+            full: None,
+            mathml: None,
+        };
+        let mut chapter = Chapter {
+            title: "One".to_string(),
+            blocks: vec![
+                Block::Paragraph(vec![Inline::Anchor("page-3".to_string()), Inline::Text("Kept as is.".to_string())]),
+                Block::Figure(figure),
+                Block::Break,
+                Block::Paragraph(vec![Inline::Text("Teh typo.".to_string())]),
+            ],
+            confidence: None,
+        };
+        assert_eq!(chapter_text(&chapter), "Kept as is.\n\n[[figure:fig-1.png]]\n\n[[break]]\n\nTeh typo.\n");
+
+        // moved, corrected, and a figure that is not there left out
+        apply_text(&mut chapter, "Kept as is.\n\n[[figure:fig-1.png]]\n\nThe typo.\n\n[[break]]\n\n[[figure:gone.png]]\n");
+        assert_eq!(chapter_text(&chapter), "Kept as is.\n\n[[figure:fig-1.png]]\n\nThe typo.\n\n[[break]]\n");
+        // the unchanged paragraph keeps its page anchor
+        assert!(matches!(&chapter.blocks[0], Block::Paragraph(p) if matches!(&p[0], Inline::Anchor(a) if a == "page-3")));
+    }
+}
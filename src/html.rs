@@ -0,0 +1,93 @@
+use html_escape::{encode_text, encode_double_quoted_attribute};
+use crate::blocks_to_markup;
+use crate::document::{Chapter, Figure};
+
+/// Stylesheet inlined into the standalone HTML output
+const STYLE: &str = r#"
+body { max-width: 40em; margin: 2em auto; padding: 0 1em;
+       font-family: Georgia, serif; line-height: 1.5; }
+h1   { text-align: center; margin-bottom: 0.2em; }
+.author { text-align: center; font-style: italic; margin-bottom: 2em; }
+p    { text-align: justify; margin: 0 0 1em 0; }
+figure { margin: 1.5em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption { font-size: 0.9em; font-style: italic; }
+"#;
+
+/// Standard base64 (RFC 4648) with padding
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Inline `data` as a `data:` URI
+pub fn data_uri(mime: &str, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, base64_encode(data))
+}
+
+/// Inline a figure's image as a `data:` URI
+fn figure_uri(fig: &Figure) -> String {
+    data_uri(fig.mime, &fig.data)
+}
+
+/// Render the chapters as a single self-contained HTML page.
+/// Uses the same content model as the EPUB chapters, but with an inlined stylesheet
+/// and base64 images so the file can be opened directly in a browser.
+/// Chapters other than the book's main text get an `<h2>` heading.
+/// The book's stylesheet (`css`, see `style::stylesheet`) is applied on top of the page layout.
+pub fn text_to_html<'a>(
+    title: &str,
+    author: &str,
+    chapters: impl IntoIterator<Item = &'a Chapter>,
+    css: Option<&str>,
+    lang: &str,
+) -> String {
+    let body = chapters
+        .into_iter()
+        .map(|c| {
+            let markup = blocks_to_markup(&c.blocks, figure_uri, figure_uri, str::to_string);
+            if c.title == title {
+                markup
+            } else {
+                format!("<section><h2>{}</h2>{}</section>", encode_text(&c.title), markup)
+            }
+        })
+        .collect::<String>();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+  <head>
+    <meta charset="utf-8"/>
+    <meta name="author" content="{author_attr}"/>
+    <title>{title}</title>
+    <style>{style}{css}</style>
+  </head>
+  <body>
+    <h1>{title}</h1>
+    <div class="author">{author}</div>
+    {paras}
+  </body>
+</html>
+"#,
+        title = encode_text(title),
+        author = encode_text(author),
+        author_attr = encode_double_quoted_attribute(author),
+        style = STYLE,
+        css = css.unwrap_or_default(),
+        paras = body,
+        lang = encode_double_quoted_attribute(lang),
+    )
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::backmatter::Section;
+use crate::document::{page_anchor, Block, Inline, PageText};
+
+/// What to do with the book's index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IndexMode {
+    /// Leave the index out
+    Drop,
+    /// Keep it verbatim, one entry per line, in a chapter of its own
+    Chapter,
+    /// Like `chapter`, with page numbers linked to the pages (needs `--extract-pagenum`)
+    Link,
+}
+
+/// The index: pages headed "Index" with most lines "term, 12, 45" entries ending in
+/// page numbers
+pub static INDEX: Section = Section {
+    headings: &["index", "general index", "subject index", "index of names", "name index"],
+    entry_ratio: 0.5,
+    is_entry: |line| TRAILING_NUMBER_RE.is_match(line),
+};
+
+/// Page locators: `123` or a range like `123-5`, `123–125`
+static LOCATOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{1,4})(?:\s*[-–—]\s*\d{1,4})?\b").unwrap());
+
+static TRAILING_NUMBER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d[\d\s,–—-]*\.?$").unwrap());
+
+/// Link the page locators of an entry to the page anchors in `known`.
+/// Only numbers after the first comma are locators, so numbers in the term stay text.
+fn link_entry(line: &str, known: &HashSet<u32>) -> Vec<Inline> {
+    let Some(comma) = line.find(',') else {
+        return vec![Inline::Text(line.to_string())];
+    };
+
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    for m in LOCATOR_RE.captures_iter(&line[comma..]) {
+        let whole = m.get(0).expect("match");
+        let Ok(page) = m[1].parse::<u32>() else { continue };
+        if !known.contains(&page) {
+            continue;
+        }
+        let (from, to) = (comma + whole.start(), comma + whole.end());
+        if from > start {
+            inlines.push(Inline::Text(line[start..from].to_string()));
+        }
+        inlines.push(Inline::Link { href: format!("#{}", page_anchor(page)), text: whole.as_str().to_string() });
+        start = to;
+    }
+    if start < line.len() {
+        inlines.push(Inline::Text(line[start..].to_string()));
+    }
+    inlines
+}
+
+/// Render the index pages verbatim, one paragraph per entry line. With `link`,
+/// page locators become links to the printed page numbers found in `pages`.
+pub fn index_blocks(index: &[PageText], pages: &[PageText], link: bool) -> Vec<Block> {
+    let known: HashSet<u32> = if link { pages.iter().filter_map(|p| p.number).collect() } else { HashSet::new() };
+    INDEX
+        .lines(index)
+        .map(|l| Block::Paragraph(link_entry(l, &known)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(page: u32, text: &str) -> Inline {
+        Inline::Link { href: format!("#{}", page_anchor(page)), text: text.to_string() }
+    }
+
+    #[test]
+    fn entry_locators() {
+        let known = HashSet::from([12, 45, 120]);
+        assert_eq!(
+            link_entry("Route 66, 12, 45–47, 99", &known),
+            vec![
+                Inline::Text("Route 66, ".to_string()),
+                link(12, "12"),
+                Inline::Text(", ".to_string()),
+                link(45, "45–47"),
+                Inline::Text(", 99".to_string()),
+            ]
+        );
+        assert_eq!(link_entry("see also Rome", &known), vec![Inline::Text("see also Rome".to_string())]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Indic scripts (Devanagari, Bengali, Tamil and the other Brahmic scripts of India) as
+//! Tesseract writes them: vowel signs split off their consonant by a space, and vowels
+//! written in two parts left as two characters where Unicode has one
+
+/// Whether `c` is a sign that belongs to the letter before it: a vowel sign (matra),
+/// virama, nukta or the nasal signs. The Brahmic blocks from Devanagari to Malayalam
+/// share one layout, so the signs sit at the same offsets in each.
+pub fn is_sign(c: char) -> bool {
+    let c = c as u32;
+    (0x0900..0x0d80).contains(&c) && matches!(c & 0x7f, 0x00..=0x03 | 0x3a..=0x3c | 0x3e..=0x4f | 0x51..=0x57 | 0x62..=0x63)
+}
+
+/// Vowels written in two parts, and the nukta letters that are no composition
+/// exclusions, as their canonical composition
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('\u{0928}', '\u{093c}', '\u{0929}'),
+    ('\u{0930}', '\u{093c}', '\u{0931}'),
+    ('\u{0933}', '\u{093c}', '\u{0934}'),
+    ('\u{09c7}', '\u{09be}', '\u{09cb}'),
+    ('\u{09c7}', '\u{09d7}', '\u{09cc}'),
+    ('\u{0b47}', '\u{0b56}', '\u{0b48}'),
+    ('\u{0b47}', '\u{0b3e}', '\u{0b4b}'),
+    ('\u{0b47}', '\u{0b57}', '\u{0b4c}'),
+    ('\u{0b92}', '\u{0bd7}', '\u{0b94}'),
+    ('\u{0bc6}', '\u{0bbe}', '\u{0bca}'),
+    ('\u{0bc7}', '\u{0bbe}', '\u{0bcb}'),
+    ('\u{0bc6}', '\u{0bd7}', '\u{0bcc}'),
+    ('\u{0c46}', '\u{0c56}', '\u{0c48}'),
+    ('\u{0cbf}', '\u{0cd5}', '\u{0cc0}'),
+    ('\u{0cc6}', '\u{0cd5}', '\u{0cc7}'),
+    ('\u{0cc6}', '\u{0cd6}', '\u{0cc8}'),
+    ('\u{0cc6}', '\u{0cc2}', '\u{0cca}'),
+    ('\u{0cca}', '\u{0cd5}', '\u{0ccb}'),
+    ('\u{0d46}', '\u{0d3e}', '\u{0d4a}'),
+    ('\u{0d47}', '\u{0d3e}', '\u{0d4b}'),
+    ('\u{0d46}', '\u{0d57}', '\u{0d4c}'),
+];
+
+/// Nukta letters excluded from composition, as their canonical decomposition
+const DECOMPOSITIONS: &[(char, &str)] = &[
+    ('\u{0958}', "\u{0915}\u{093c}"),
+    ('\u{0959}', "\u{0916}\u{093c}"),
+    ('\u{095a}', "\u{0917}\u{093c}"),
+    ('\u{095b}', "\u{091c}\u{093c}"),
+    ('\u{095c}', "\u{0921}\u{093c}"),
+    ('\u{095d}', "\u{0922}\u{093c}"),
+    ('\u{095e}', "\u{092b}\u{093c}"),
+    ('\u{095f}', "\u{092f}\u{093c}"),
+    ('\u{09dc}', "\u{09a1}\u{09bc}"),
+    ('\u{09dd}', "\u{09a2}\u{09bc}"),
+    ('\u{09df}', "\u{09af}\u{09bc}"),
+    ('\u{0a33}', "\u{0a32}\u{0a3c}"),
+    ('\u{0a36}', "\u{0a38}\u{0a3c}"),
+    ('\u{0a59}', "\u{0a16}\u{0a3c}"),
+    ('\u{0a5a}', "\u{0a17}\u{0a3c}"),
+    ('\u{0a5b}', "\u{0a1c}\u{0a3c}"),
+    ('\u{0a5e}', "\u{0a2b}\u{0a3c}"),
+    ('\u{0b5c}', "\u{0b21}\u{0b3c}"),
+    ('\u{0b5d}', "\u{0b22}\u{0b3c}"),
+];
+
+/// `text` with the spaces before signs dropped and its Indic letters in Unicode
+/// normalization form C; text without Indic letters is returned as is
+pub fn normalize(text: &str) -> String {
+    if !text.chars().any(|c| ('\u{0900}'..'\u{0d80}').contains(&c)) {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_sign(c) {
+            // a sign never starts a word
+            let kept = out.trim_end_matches([' ', '\t']).len();
+            out.truncate(kept);
+        }
+        match DECOMPOSITIONS.iter().find(|&&(composed, _)| composed == c) {
+            Some((_, decomposed)) => out.push_str(decomposed),
+            None => out.push(c),
+        }
+        // two parts of one vowel make one character, which may take a third
+        let mut tail = out.chars().rev();
+        if let (Some(last), Some(prev)) = (tail.next(), tail.next()) {
+            if let Some(&(_, _, composed)) = COMPOSITIONS.iter().find(|&&(a, b, _)| (a, b) == (prev, last)) {
+                out.pop();
+                out.pop();
+                out.push(composed);
+            }
+        }
+    }
+    out
+}
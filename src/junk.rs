@@ -0,0 +1,153 @@
+//! Lines of OCR noise: the punctuation Tesseract reads into pictures, decorations and
+//! specks (`~,.;’` |`), and lines Tesseract was too unsure of, dropped before the lines
+//! are unwrapped. What was dropped is kept for the audit log, see `--dropped-lines`.
+use crate::ocr::OcrLine;
+use crate::quality::Dictionary;
+use crate::titles;
+
+/// Lines with at least this share of symbols among their characters are junk...
+const MAX_SYMBOL_RATIO: f32 = 0.5;
+
+/// ...and with at least this share of symbols and punctuation when their words are not
+/// words either
+const SUSPECT_PUNCTUATION_RATIO: f32 = 0.3;
+
+/// The punctuation of ordinary prose, which dialogue lines are full of
+const PROSE_PUNCTUATION: &str = ".,;:!?'\"()-–—‘’“”«»…";
+
+/// Mean word confidence (0-100) below which a line full of symbols is not trusted
+const LOW_CONFIDENCE: f32 = 50.0;
+
+/// Characters ornamental section breaks are made of ("* * *", "~~~", "⁂")
+const ORNAMENTS: &[char] = &['*', '.', '…', '⁂', '·', '•', '~', '—', '–', '-', '=', '#', '§', '❧', '◆', '○', '●'];
+
+/// A line dropped from a page
+#[derive(Debug, Clone)]
+pub struct DroppedLine {
+    /// 1-based PDF page
+    pub page: usize,
+
+    /// Mean word confidence (0-100) of the line, when known
+    pub confidence: Option<f32>,
+
+    pub text: String,
+}
+
+/// Mean confidence (0-100) of the words of `line`
+fn line_confidence(line: &OcrLine) -> f32 {
+    line.words.iter().map(|w| w.conf).sum::<f32>() / line.words.len().max(1) as f32
+}
+
+/// Drop the lines of `lines` whose mean word confidence is below `min`; returns them
+/// with their confidence
+pub fn drop_unsure_lines(lines: &mut Vec<OcrLine>, min: f32) -> Vec<(Option<f32>, String)> {
+    let mut dropped = Vec::new();
+    lines.retain(|line| {
+        let confidence = line_confidence(line);
+        let keep = confidence >= min;
+        if !keep {
+            dropped.push((Some(confidence), line.text()));
+        }
+        keep
+    });
+    dropped
+}
+
+/// The audit log of `dropped` lines, one per line: `page 12 (31.0%): ~,.;`
+pub fn dropped_text(dropped: &[DroppedLine]) -> String {
+    dropped
+        .iter()
+        .map(|d| match d.confidence {
+            Some(c) => format!("page {} ({:.1}%): {}\n", d.page, c, d.text),
+            None => format!("page {}: {}\n", d.page, d.text),
+        })
+        .collect()
+}
+
+/// Tells lines of noise from text by their share of symbols, whether their words are
+/// in the dictionary and how sure Tesseract was of them
+pub struct JunkFilter<'a> {
+    dictionary: Option<&'a Dictionary>,
+}
+
+impl<'a> JunkFilter<'a> {
+    pub fn new(dictionary: Option<&'a Dictionary>) -> Self {
+        Self { dictionary }
+    }
+
+    /// Whether `line`, recognised with mean word `confidence` when known, is noise
+    pub fn is_junk(&self, line: &str, confidence: Option<f32>) -> bool {
+        let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.is_empty() || chars.iter().all(|c| ORNAMENTS.contains(c)) {
+            return false;
+        }
+        let alphanumeric: String = chars.iter().filter(|c| c.is_alphanumeric()).collect();
+        // page numbers and numbered headings: "— 12 —", "(iv)"
+        if alphanumeric.chars().all(|c| c.is_ascii_digit()) && !alphanumeric.is_empty()
+            || titles::parse_roman(&alphanumeric).is_some()
+        {
+            return false;
+        }
+        let share = |n: usize| n as f32 / chars.len() as f32;
+        let symbols = chars.iter().filter(|&&c| !c.is_alphanumeric() && !PROSE_PUNCTUATION.contains(c)).count();
+        if share(symbols) >= MAX_SYMBOL_RATIO {
+            return true;
+        }
+        if 1.0 - share(alphanumeric.chars().count()) < SUSPECT_PUNCTUATION_RATIO {
+            return false;
+        }
+        let unsure = confidence.is_some_and(|c| c < LOW_CONFIDENCE);
+        let words: Vec<&str> = line
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphabetic()))
+            .filter(|w| w.chars().count() >= 2 && w.chars().all(char::is_alphabetic))
+            .collect();
+        let hits = match self.dictionary {
+            Some(dictionary) if !words.is_empty() => {
+                words.iter().filter(|w| dictionary.contains(w)).count() as f32 / words.len() as f32
+            }
+            _ if !words.is_empty() => 1.0,
+            _ => 0.0,
+        };
+        hits == 0.0 || (unsure && hits < 0.5)
+    }
+
+    /// Drop the lines of noise from `lines`; returns them with their confidence
+    pub fn filter_lines(&self, lines: &mut Vec<OcrLine>) -> Vec<(Option<f32>, String)> {
+        let mut dropped = Vec::new();
+        lines.retain(|line| {
+            let (text, confidence) = (line.text(), line_confidence(line));
+            let junk = self.is_junk(&text, Some(confidence));
+            if junk {
+                dropped.push((Some(confidence), text));
+            }
+            !junk
+        });
+        dropped
+    }
+
+    /// `text` without its lines of noise, and those lines; a line of noise between blank
+    /// lines takes one of them along, so no gap is widened into a section break
+    pub fn filter_text(&self, text: &str) -> (String, Vec<(Option<f32>, String)>) {
+        let mut out: Vec<&str> = Vec::new();
+        let mut dropped = Vec::new();
+        let mut skip_blank = false;
+        for line in text.lines() {
+            if line.trim().is_empty() && std::mem::take(&mut skip_blank) {
+                continue;
+            }
+            skip_blank = false;
+            if self.is_junk(line, None) {
+                skip_blank = out.last().is_some_and(|l| l.trim().is_empty());
+                dropped.push((None, line.to_string()));
+                continue;
+            }
+            out.push(line);
+        }
+        let mut filtered = out.join("\n");
+        if text.ends_with('\n') {
+            filtered.push('\n');
+        }
+        (filtered, dropped)
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::Pdf2EPubErr;
+
+/// External converters we know how to drive, in order of preference
+#[derive(Debug, Clone, Copy)]
+enum Converter {
+    /// Calibre's `ebook-convert`, produces KF8 (AZW3)
+    Calibre,
+    /// Amazon's (discontinued) `kindlegen`, produces MOBI
+    KindleGen,
+}
+
+impl Converter {
+    fn binary(self) -> &'static str {
+        match self {
+            Converter::Calibre => "ebook-convert",
+            Converter::KindleGen => "kindlegen",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Converter::Calibre => "azw3",
+            Converter::KindleGen => "mobi",
+        }
+    }
+}
+
+/// Find `name` in one of the directories listed in `$PATH`
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Pick the converter to use. An explicit `tool` path is classified by its file name,
+/// otherwise `$PATH` is searched for Calibre first and kindlegen second.
+fn locate_converter(tool: Option<&Path>) -> Option<(Converter, PathBuf)> {
+    if let Some(tool) = tool {
+        let name = tool.file_stem()?.to_string_lossy();
+        let conv = if name.contains("kindlegen") { Converter::KindleGen } else { Converter::Calibre };
+        return Some((conv, tool.to_path_buf()));
+    }
+    [Converter::Calibre, Converter::KindleGen]
+        .into_iter()
+        .find_map(|c| find_in_path(c.binary()).map(|p| (c, p)))
+}
+
+/// Convert a generated epub into a Kindle sideloadable file next to it.
+/// Returns the path of the written file.
+pub fn epub_to_kindle(epub: &Path, tool: Option<&Path>) -> Result<PathBuf, Pdf2EPubErr> {
+    let (conv, bin) = locate_converter(tool).ok_or_else(|| {
+        Pdf2EPubErr::KindleExportError(
+            "neither ebook-convert (Calibre) nor kindlegen found in PATH".to_string())
+    })?;
+
+    let outfile = epub.with_extension(conv.extension());
+    let mut cmd = Command::new(&bin);
+    match conv {
+        Converter::Calibre => {
+            cmd.arg(epub).arg(&outfile);
+        }
+        Converter::KindleGen => {
+            // kindlegen only accepts a bare file name and writes next to the input
+            let name = outfile.file_name().expect("output file name");
+            cmd.arg(epub).arg("-o").arg(name);
+        }
+    }
+
+    let status = cmd.status().map_err(|e| {
+        Pdf2EPubErr::KindleExportError(format!("failed to run {}: {}", bin.display(), e))
+    })?;
+
+    // kindlegen exits with 1 when it only emitted warnings
+    let ok = status.success() || (matches!(conv, Converter::KindleGen) && status.code() == Some(1));
+    if !ok || !outfile.exists() {
+        return Err(Pdf2EPubErr::KindleExportError(
+            format!("{} exited with {}", bin.display(), status)));
+    }
+    Ok(outfile)
+}
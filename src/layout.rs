@@ -0,0 +1,72 @@
+//! How lines sit on the page, from the word coordinates of the OCR: indented first
+//! lines, for books that mark paragraphs by indent alone, and the short last lines
+//! of paragraphs in justified text
+use crate::document::LineLayout;
+use crate::ocr::OcrLine;
+
+/// A block needs this many lines before its left edge is trusted
+const MIN_BLOCK_LINES: usize = 3;
+
+/// Lines starting at least this many line heights right of their block's left edge
+/// are indented...
+const INDENT_MIN: f32 = 0.8;
+
+/// ...and at most this many; further in they are centred or set apart (headings, verse)
+const INDENT_MAX: f32 = 5.0;
+
+/// Lines ending at least this many line heights left of their block's right edge end short
+const SHORT_MIN: f32 = 2.0;
+
+/// The layout of `lines`, in order; lines are measured against the others of their
+/// Tesseract block, so each column has its own left edge
+pub fn line_layout(lines: &[OcrLine]) -> Vec<LineLayout> {
+    lines
+        .iter()
+        .map(|line| {
+            let block: Vec<&OcrLine> = lines.iter().filter(|l| l.paragraph.0 == line.paragraph.0).collect();
+            let (indented, ends_short) = match Edges::of(&block) {
+                Some(edges) => (edges.is_indented(line), edges.ends_short(line)),
+                None => (false, false),
+            };
+            LineLayout { text: line.text(), indented, ends_short }
+        })
+        .collect()
+}
+
+/// The edges of the text of a block and its line height
+struct Edges {
+    left: u32,
+    right: u32,
+    line_height: f32,
+}
+
+impl Edges {
+    fn of(block: &[&OcrLine]) -> Option<Edges> {
+        if block.len() < MIN_BLOCK_LINES {
+            return None;
+        }
+        let sorted = |f: fn(&OcrLine) -> u32| {
+            let mut v: Vec<u32> = block.iter().map(|l| f(l)).collect();
+            v.sort_unstable();
+            v
+        };
+        // quantiles: most lines are flush on both sides, but some are indented or short
+        let lefts = sorted(|l| l.bbox.x);
+        let rights = sorted(|l| l.bbox.right());
+        let heights = sorted(|l| l.bbox.h);
+        Some(Edges {
+            left: lefts[lefts.len() / 4],
+            right: rights[rights.len() * 3 / 4],
+            line_height: heights[heights.len() / 2].max(1) as f32,
+        })
+    }
+
+    fn is_indented(&self, line: &OcrLine) -> bool {
+        let indent = line.bbox.x.saturating_sub(self.left) as f32 / self.line_height;
+        (INDENT_MIN..=INDENT_MAX).contains(&indent)
+    }
+
+    fn ends_short(&self, line: &OcrLine) -> bool {
+        self.right.saturating_sub(line.bbox.right()) as f32 / self.line_height >= SHORT_MIN
+    }
+}
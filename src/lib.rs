@@ -0,0 +1,706 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error;
+use pdfium_render::prelude::*;
+use image::{DynamicImage, RgbImage};
+use paragraphs::ParagraphRules;
+use document::{Block, Figure, Inline, LineLayout, PageText, ParagraphStyle, RubyWord, Script, ScriptedWord, UncertainWord};
+
+pub mod accuracy;
+pub mod annotations;
+pub mod anthology;
+pub mod backmatter;
+pub mod balance;
+pub mod bench;
+pub mod cache;
+#[cfg(feature = "captioning")]
+pub mod captioning;
+pub mod config;
+pub mod debug;
+pub mod document;
+pub mod duplicates;
+pub mod epigraphs;
+pub mod epub2;
+pub mod figures;
+pub mod frontmatter;
+pub mod glossary;
+#[cfg(test)]
+mod golden_tests;
+pub mod hooks;
+pub mod html;
+pub mod indic;
+pub mod index;
+pub mod junk;
+pub mod kindle;
+pub mod layout;
+pub mod linenum;
+pub mod links;
+pub mod manifest;
+pub mod mask;
+#[cfg(feature = "math")]
+pub mod math;
+pub mod notes;
+pub mod metadata;
+pub mod metrics;
+pub mod ocr;
+pub mod order;
+pub mod package;
+pub mod paragraphs;
+pub mod pagelabels;
+pub mod pagelayout;
+pub mod preprocess;
+pub mod quality;
+pub mod references;
+pub mod report;
+pub mod retry;
+pub mod rules;
+pub mod ruby;
+pub mod scripts;
+pub mod sentences;
+pub mod ssml;
+pub mod stats;
+pub mod style;
+pub mod thumbs;
+pub mod timings;
+pub mod titles;
+pub mod toc;
+pub mod validation;
+pub mod vector;
+pub mod wizard;
+pub mod xref;
+
+/// Default resolution pages are rendered at for OCR and figure extraction
+pub const RENDER_DPI: u16 = 300;
+
+/// Body text size as a fraction of the page width, as books are set: a line holds some
+/// 66 characters, half an em wide on average, across three quarters of the page
+const TEXT_SIZE_PER_WIDTH: f32 = 1.0 / 44.0;
+
+/// Bounds of the resolution `page_dpi` picks
+const MIN_DPI: f32 = 150.0;
+const MAX_DPI: f32 = 600.0;
+
+#[derive(Debug, Error)]
+pub enum Pdf2EPubErr {
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("PdfiumError error: {0}")]
+    PdfiumError(#[from] PdfiumError),
+
+    #[error("AnyHowError error: {0}")]
+    AnyHowError(#[from] anyhow::Error),
+
+    #[error("ZipLibrary error")]
+    ZipLibraryError(#[from] epub_builder::Error),
+
+    #[error("Kindle export error: {0}")]
+    KindleExportError(String),
+
+    #[error("Anthology manifest error: {0}")]
+    ManifestError(String),
+
+    #[error("Layout file error: {0}")]
+    LayoutError(String),
+
+    #[error("Metadata lookup error: {0}")]
+    MetadataLookupError(String),
+
+    #[error("Font error: {0}")]
+    FontError(String),
+
+    #[error("Input file not found: {}", .0.display())]
+    InputNotFound(PathBuf),
+
+    #[error("OCR engine error: {0}")]
+    OcrEngineError(String),
+
+    #[error("Settings error: {0}")]
+    ConfigError(String),
+
+    #[error("Post-processing command failed: {0}")]
+    HookError(String),
+
+    #[error("Rules file error: {0}")]
+    RulesError(String),
+
+    #[error("EPUB validation failed: {0}")]
+    ValidationError(String),
+}
+
+/// Exit codes of the command line tool, so scripts wrapping it can tell failures apart.
+/// Invalid command lines exit with clap's code 2.
+pub mod exit_code {
+    pub const SUCCESS: u8 = 0;
+
+    /// Any error without a code of its own
+    pub const FAILURE: u8 = 1;
+
+    /// The book was written, but warnings were printed
+    pub const SUCCESS_WITH_WARNINGS: u8 = 3;
+
+    /// The book was written, but some pages could not be recognised
+    pub const PARTIAL_CONVERSION: u8 = 4;
+
+    pub const INPUT_NOT_FOUND: u8 = 10;
+
+    /// The PDF needs a password
+    pub const ENCRYPTED_PDF: u8 = 11;
+
+    /// The pdfium library could not be loaded
+    pub const PDFIUM_MISSING: u8 = 12;
+
+    /// Tesseract or its language data could not be loaded
+    pub const OCR_ENGINE_MISSING: u8 = 13;
+
+    /// The epub failed its structure check under `--strict`
+    pub const INVALID_EPUB: u8 = 14;
+}
+
+impl Pdf2EPubErr {
+    /// The process exit code for this error, see `exit_code`
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Pdf2EPubErr::InputNotFound(_) => exit_code::INPUT_NOT_FOUND,
+            Pdf2EPubErr::PdfiumError(PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError)) => {
+                exit_code::ENCRYPTED_PDF
+            }
+            Pdf2EPubErr::PdfiumError(PdfiumError::LoadLibraryError(_)) => exit_code::PDFIUM_MISSING,
+            Pdf2EPubErr::OcrEngineError(_) => exit_code::OCR_ENGINE_MISSING,
+            Pdf2EPubErr::ValidationError(_) => exit_code::INVALID_EPUB,
+            _ => exit_code::FAILURE,
+        }
+    }
+}
+
+/// Warnings printed so far, see `warn`
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Print a warning; the command line tool exits with `SUCCESS_WITH_WARNINGS` after any
+pub fn warn(msg: impl std::fmt::Display) {
+    eprintln!("warning: {}", msg);
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Resolution at which the body text of `page` renders about `text_height` pixels high
+/// (its em), the text size being estimated from the page width. A page that is a scan
+/// of a double-page `spread` holds two pages side by side.
+pub fn page_dpi(page: &PdfPage, text_height: u32, spread: bool) -> u16 {
+    let mut width = page.paper_size().width().to_inches();
+    if spread {
+        width /= 2.0;
+    }
+    let text_size = width * TEXT_SIZE_PER_WIDTH;
+    (text_height as f32 / text_size).clamp(MIN_DPI, MAX_DPI).round() as u16
+}
+
+/// Convert a single `PdfPage` into the RGB byte buffer
+/// - `target_dpi` controls the rasterisation resolution
+/// - `grayscale` renders without color, which is all OCR needs
+pub fn img_source_from_page(
+    page: &PdfPage,
+    target_dpi: u16,
+    grayscale: bool,
+) -> Result<RgbImage, Pdf2EPubErr> {
+    let w_inch = page.paper_size().width().to_inches();
+    let w_pixels = (w_inch * (target_dpi as f32)) as i32;
+
+    let h_inch = page.paper_size().height().to_inches();
+    let h_pixels = (h_inch * (target_dpi as f32)) as i32;
+
+    let render_config = PdfRenderConfig::new()
+        .set_target_width(w_pixels)
+        .set_target_height(h_pixels)
+        .use_grayscale_rendering(grayscale);
+
+    let bitmap = page.render_with_config(&render_config)?;
+    let dyn_image: DynamicImage = bitmap.as_image();
+    let rgb8: RgbImage = dyn_image.into_rgb8();
+
+    Ok(rgb8)
+}
+
+/// Remove a trailing page number like "...some text\n\n11" and return it.
+/// On failure the original text is left intact and page_num is None.
+pub fn peel_trailing_page_num(s: &str) -> (&str, Option<u32>) {
+    let trimmed = s.trim_end();
+    match trimmed.rsplit_once(char::is_whitespace) {
+        Some((head, tail)) if !tail.is_empty() && tail.chars().all(|c| c.is_ascii_digit()) => {
+            (head.trim_end(), Some(tail.parse::<u32>().expect("number")))
+        }
+        _ => (trimmed, None),
+    }
+}
+
+/// What a marked byte range of a line is, see `LineInfo::marks`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mark {
+    /// A word recognised with low confidence and its alternative readings
+    Uncertain(Vec<String>),
+    /// A superscript or subscript
+    Script(Script),
+    /// Text with its reading
+    Ruby(String),
+}
+
+/// What is known of a line besides its text, see `LineUnwrapper::push_line_with`
+#[derive(Debug, Clone, Default)]
+pub struct LineInfo {
+    /// Marked byte ranges of the trimmed line, in order: uncertain words
+    /// (`Inline::Uncertain`), scripts (`Inline::Script`) and ruby (`Inline::Ruby`)
+    pub marks: Vec<(Range<usize>, Mark)>,
+
+    /// Indented on the page, by the left x of its words
+    pub indented: bool,
+
+    /// Ends well short of the right margin
+    pub ends_short: bool,
+}
+
+/// How a line is joined onto the paragraph before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Join {
+    /// Drop the hyphen of a word broken across the lines
+    Dehyphenate,
+    /// Keep the trailing dash or hyphen, or the letter an Indic vowel sign belongs to,
+    /// without a space
+    Glue,
+    Space,
+}
+
+/// How to join `next` onto `prev`. Only a hyphen between two letters, the second
+/// lowercase, is a word break; em and en dashes, a standing " - ", a hyphen after a
+/// digit ("1914-18") or before a capital ("Anglo-Saxon") are kept. A line starting
+/// with an Indic vowel sign continues the word before.
+fn line_join(prev: &str, next: &str) -> Join {
+    let mut tail = prev.chars().rev();
+    let first = next.chars().next();
+    if first.is_some_and(indic::is_sign) {
+        return Join::Glue;
+    }
+    match (tail.next(), tail.next()) {
+        (Some('—' | '–'), _) => Join::Glue,
+        (Some('-'), Some('-')) => Join::Glue,
+        (Some('-'), Some(c)) if c.is_whitespace() => Join::Space,
+        (Some('-'), Some(c)) if c.is_alphabetic() && first.is_some_and(char::is_lowercase) => Join::Dehyphenate,
+        (Some('-'), Some(_)) => Join::Glue,
+        _ => Join::Space,
+    }
+}
+
+/// Incrementally unwraps hard-wrapped lines *and* removes fake page-break
+/// blank lines.  Call `push_line()` for every raw line (in reading order),
+/// `page_break()` after finishing a page, and `finish()` at the very end.
+pub struct LineUnwrapper {
+    /// current paragraph being built
+    buf: String,
+
+    // fully emitted blocks
+    out: Vec<Block>,
+
+    /// blank lines since the last non-blank one
+    blank_run: usize,
+
+    /// whether the last non-blank line ended well short of the right margin
+    prev_short: bool,
+
+    /// anchors inside the current paragraph, as byte offsets into `buf`
+    anchors: Vec<(usize, String)>,
+
+    /// marked ranges of the current paragraph, as byte ranges of `buf`
+    marks: Vec<(Range<usize>, Mark)>,
+
+    /// figures waiting for the current paragraph to end
+    pending_figures: Vec<Figure>,
+
+    /// how to tell paragraph breaks from page and column gaps
+    rules: ParagraphRules,
+
+    /// style of the lines set apart from the body text being pushed, see `set_style`
+    style: Option<ParagraphStyle>,
+}
+
+impl Default for LineUnwrapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineUnwrapper {
+    pub fn new() -> Self {
+        Self::with_rules(ParagraphRules::default())
+    }
+
+    /// An unwrapper breaking paragraphs by `rules`
+    pub fn with_rules(rules: ParagraphRules) -> Self {
+        Self {
+            buf: String::new(),
+            out: Vec::new(),
+            blank_run: 0,
+            prev_short: false,
+            anchors: Vec::new(),
+            marks: Vec::new(),
+            pending_figures: Vec::new(),
+            rules,
+            style: None,
+        }
+    }
+
+    /// Emit the paragraph being built, followed by any figures that were
+    /// encountered while it was still open.
+    fn flush_paragraph(&mut self) {
+        if !self.buf.is_empty() {
+            let text = self.buf.trim_end();
+            let mut anchors = std::mem::take(&mut self.anchors)
+                .into_iter()
+                .map(|(offset, id)| (offset.min(text.len()), id))
+                .peekable();
+            let mut inlines = Vec::new();
+            let mut start = 0;
+            // plain text up to `end`, split at the anchors inside it
+            let mut plain = |inlines: &mut Vec<Inline>, start: &mut usize, end: usize| {
+                while let Some((offset, id)) = anchors.next_if(|(o, _)| *o <= end) {
+                    if offset > *start {
+                        inlines.push(Inline::Text(text[*start..offset].to_string()));
+                        *start = offset;
+                    }
+                    inlines.push(Inline::Anchor(id));
+                }
+                if *start < end {
+                    inlines.push(Inline::Text(text[*start..end].to_string()));
+                    *start = end;
+                }
+            };
+            for (range, mark) in self.marks.drain(..) {
+                let range = range.start..range.end.min(text.len());
+                if range.start < start || range.is_empty() {
+                    continue;
+                }
+                plain(&mut inlines, &mut start, range.start);
+                let marked = text[range.clone()].to_string();
+                inlines.push(match mark {
+                    Mark::Uncertain(alternatives) => Inline::Uncertain { text: marked, alternatives },
+                    Mark::Script(script) => Inline::Script { script, text: marked },
+                    Mark::Ruby(reading) => Inline::Ruby { text: marked, reading },
+                });
+                start = range.end;
+            }
+            plain(&mut inlines, &mut start, text.len());
+            self.out.push(match self.style {
+                Some(style) => Block::Styled(style, inlines),
+                None => Block::Paragraph(inlines),
+            });
+            self.buf.clear();
+        }
+        self.out.extend(self.pending_figures.drain(..).map(Block::Figure));
+    }
+
+    /// Push a figure found at the current reading position.
+    /// Figures never split a paragraph: they are placed after the paragraph that is
+    /// still open (which may continue on the next page).
+    pub fn push_figure(&mut self, fig: Figure) {
+        if self.buf.is_empty() {
+            self.out.push(Block::Figure(fig));
+        } else {
+            self.pending_figures.push(fig);
+        }
+    }
+
+    /// Set the lines pushed from here on apart as one paragraph of `style`, or go back
+    /// to body text with `None`. Either way the open paragraph ends.
+    pub fn set_style(&mut self, style: Option<ParagraphStyle>) {
+        self.flush_paragraph();
+        self.blank_run = 0;
+        self.prev_short = false;
+        self.style = style;
+    }
+
+    /// Mark the current reading position with an anchor (e.g. a page start).
+    /// Between paragraphs the anchor goes to the start of the next one.
+    pub fn push_anchor(&mut self, id: String) {
+        self.anchors.push((self.buf.len(), id));
+    }
+
+    /// Push one **raw** line (possibly blank, with trailing `\n` removed).
+    pub fn push_line(&mut self, raw: &str) {
+        self.push_line_with(raw, LineInfo::default());
+    }
+
+    /// Push a raw line with what else is known of it
+    pub fn push_line_with(&mut self, raw: &str, info: LineInfo) {
+        let line = raw.trim();
+
+        if line.is_empty() {
+            // postpone decision until we see the next non-blank line
+            self.blank_run += 1;
+            return;
+        }
+
+        // Decide what that previous blank (or the indent, or short line) really meant
+        let indented = self.rules.indent && info.indented;
+        let after_short = self.rules.short_lines && std::mem::replace(&mut self.prev_short, info.ends_short);
+        let blanks = std::mem::take(&mut self.blank_run);
+        if self.style.is_some() {
+            // a passage set apart stays one paragraph
+        } else if self.rules.section_break.is_some_and(|n| blanks >= n) {
+            // a deliberate gap: a section break, whatever the sentence did
+            self.flush_paragraph();
+            if !matches!(self.out.last(), None | Some(Block::Break)) {
+                self.out.push(Block::Break);
+            }
+        } else if (blanks > 0 || indented || after_short) && self.rules.is_break(&self.buf, line) {
+            // Real paragraph break → flush current paragraph; otherwise a fake blank (from
+            // a page break), and the same ¶ goes on
+            self.flush_paragraph();
+        }
+
+        // Join the current line onto the paragraph buffer
+        if !self.buf.is_empty() {
+            match line_join(&self.buf, line) {
+                Join::Dehyphenate => {
+                    self.buf.pop();
+                    let len = self.buf.len();
+                    for (offset, _) in &mut self.anchors {
+                        *offset = (*offset).min(len);
+                    }
+                    for (range, _) in &mut self.marks {
+                        range.end = range.end.min(len);
+                    }
+                }
+                Join::Glue => {}
+                Join::Space => self.buf.push(' '),
+            }
+        }
+        let base = self.buf.len();
+        self.marks.extend(info.marks.into_iter().map(|(r, alts)| (base + r.start..base + r.end, alts)));
+        self.buf.push_str(line);
+    }
+
+    /// Consume the unwrapper and return the cleaned blocks
+    pub fn finish(mut self) -> Vec<Block> {
+        self.flush_paragraph();
+        if !self.anchors.is_empty() {
+            // nothing followed the anchors, keep them as targets anyway
+            let anchors = self.anchors.drain(..).map(|(_, id)| Inline::Anchor(id)).collect();
+            self.out.push(Block::Paragraph(anchors));
+        }
+        self.out
+    }
+}
+
+/// How many of the next marked words (or laid out lines) of a page are looked for in
+/// every word (line), so that a few that changed or disappeared on the way do not stop
+/// the matching
+const MATCH_LOOKAHEAD: usize = 3;
+
+/// Take the first of the next few `items` that is `wanted`, dropping those before it
+fn take_next<T>(items: &mut Vec<T>, wanted: impl Fn(&T) -> bool) -> Option<T> {
+    let window = items.len().min(MATCH_LOOKAHEAD);
+    let i = items[..window].iter().position(wanted)?;
+    items.drain(..=i).next_back()
+}
+
+/// Find the page's next `words` in `line`, consuming the ones found.
+/// Returns them with the byte offsets in `line` they were found at.
+fn find_words<T>(line: &str, words: &mut Vec<T>, text: impl Fn(&T) -> &str) -> Vec<(usize, T)> {
+    let mut found = Vec::new();
+    for word in line.split_whitespace() {
+        if let Some(matched) = take_next(words, |w| text(w) == word) {
+            // `word` is a subslice of `line`
+            found.push((word.as_ptr() as usize - line.as_ptr() as usize, matched));
+        }
+    }
+    found
+}
+
+/// The layout of `line`, when it is among the page's next `layout` lines
+fn find_layout(line: &str, layout: &mut Vec<LineLayout>) -> Option<LineLayout> {
+    take_next(layout, |l| l.text == line)
+}
+
+/// Mark the page's next `uncertain` words in `line`
+fn mark_uncertain(line: &str, uncertain: &mut Vec<UncertainWord>) -> Vec<(Range<usize>, Mark)> {
+    find_words(line, uncertain, |u| &u.text)
+        .into_iter()
+        .map(|(start, u)| (start..start + u.text.len(), Mark::Uncertain(u.alternatives)))
+        .collect()
+}
+
+/// Mark the scripts of the page's next `scripted` words in `line`
+fn mark_scripts(line: &str, scripted: &mut Vec<ScriptedWord>) -> Vec<(Range<usize>, Mark)> {
+    find_words(line, scripted, |w| &w.text)
+        .into_iter()
+        .flat_map(|(start, w)| w.scripts.into_iter().map(move |(r, s)| (start + r.start..start + r.end, Mark::Script(s))))
+        .collect()
+}
+
+/// Mark the readings of the page's next `ruby` words in `line`
+fn mark_ruby(line: &str, ruby: &mut Vec<RubyWord>) -> Vec<(Range<usize>, Mark)> {
+    find_words(line, ruby, |w| &w.text)
+        .into_iter()
+        .flat_map(|(start, w)| w.readings.into_iter().map(move |(r, t)| (start + r.start..start + r.end, Mark::Ruby(t))))
+        .collect()
+}
+
+/// Unwrap the text of consecutive pages into blocks, placing each page's figures
+/// after the page's text. With `page_anchors`, the start of every page with a
+/// printed number or page label is marked by a `page-N` anchor. Pages that links
+/// of the PDF point to always get a `pdf-page-N` anchor. Paragraphs break by `rules`.
+pub fn unwrap_pages(pages: impl IntoIterator<Item = PageText>, page_anchors: bool, rules: &ParagraphRules) -> Vec<Block> {
+    let mut cleaner = LineUnwrapper::with_rules(rules.clone());
+    for mut page in pages {
+        if page.linked {
+            cleaner.push_anchor(links::pdf_page_anchor(page.page));
+        }
+        if let Some(name) = page.page_name().filter(|_| page_anchors) {
+            cleaner.push_anchor(document::label_anchor(&name));
+        }
+        let line_count = page.text.lines().count();
+        for (i, line) in page.text.lines().enumerate() {
+            if let Some(set_apart) = page.set_apart.iter().find(|s| s.lines.start == i) {
+                cleaner.set_style(Some(set_apart.style));
+            } else if page.set_apart.iter().any(|s| s.lines.end == i) {
+                cleaner.set_style(None);
+            }
+            let mut marks = mark_uncertain(line.trim(), &mut page.uncertain);
+            marks.extend(mark_scripts(line.trim(), &mut page.scripted));
+            marks.extend(mark_ruby(line.trim(), &mut page.ruby));
+            marks.sort_by_key(|(r, _)| r.start);
+            let layout = find_layout(line.trim(), &mut page.layout);
+            let (indented, ends_short) = layout.map_or((false, false), |l| (l.indented, l.ends_short));
+            cleaner.push_line_with(line, LineInfo { marks, indented, ends_short });
+        }
+        // text never runs on across a page set as its scan
+        if page.set_apart.iter().any(|s| s.lines.end >= line_count) || page.figures.iter().any(|f| f.full.is_some()) {
+            cleaner.set_style(None);
+        }
+        for fig in page.figures {
+            cleaner.push_figure(fig);
+        }
+    }
+    cleaner.finish()
+}
+
+/// `blocks` in runs whose markup stays within `max_size` bytes, split between blocks
+/// (never inside a paragraph); a block larger than that is a run of its own. There is
+/// always at least one run.
+pub fn split_blocks(blocks: &[Block], max_size: usize) -> Vec<&[Block]> {
+    let mut runs = Vec::new();
+    let (mut start, mut size) = (0, 0);
+    for (i, block) in blocks.iter().enumerate() {
+        let block_size = blocks_to_markup(std::slice::from_ref(block), |f| f.name.clone(), |f| f.name.clone(), str::to_string).len();
+        if i > start && size + block_size > max_size {
+            runs.push(&blocks[start..i]);
+            (start, size) = (i, 0);
+        }
+        size += block_size;
+    }
+    runs.push(&blocks[start..]);
+    runs
+}
+
+/// Render the content blocks as `<p>` and `<figure>` elements.
+/// Shared by the EPUB and standalone HTML outputs; `image_src` decides how a
+/// figure's `src` attribute refers to its image data, `scan_href` where the thumbnail
+/// of a page scan points to given its full image (a page showing it in the epub), and
+/// `link_href` where a link points to (anchors may live in another file of the epub).
+pub fn blocks_to_markup(
+    blocks: &[Block],
+    image_src: impl Fn(&Figure) -> String,
+    scan_href: impl Fn(&Figure) -> String,
+    link_href: impl Fn(&str) -> String,
+) -> String {
+    use html_escape::{encode_text, encode_double_quoted_attribute};
+
+    let inline = |i: &Inline| match i {
+        Inline::Text(t) => encode_text(t).into_owned(),
+        Inline::Anchor(id) => format!(r#"<span id="{}"></span>"#, encode_double_quoted_attribute(id)),
+        Inline::Link { href, text } => format!(
+            r#"<a href="{}">{}</a>"#,
+            encode_double_quoted_attribute(&link_href(href)),
+            encode_text(text)
+        ),
+        Inline::NoteRef { href, text } => format!(
+            r#"<a epub:type="noteref" href="{}">{}</a>"#,
+            encode_double_quoted_attribute(&link_href(href)),
+            encode_text(text)
+        ),
+        Inline::Uncertain { text, alternatives } if alternatives.is_empty() => {
+            format!(r#"<span class="ocr-uncertain">{}</span>"#, encode_text(text))
+        }
+        Inline::Uncertain { text, alternatives } => format!(
+            r#"<span class="ocr-uncertain" title="{}">{}</span>"#,
+            encode_double_quoted_attribute(&alternatives.join(" | ")),
+            encode_text(text)
+        ),
+        Inline::Script { script: Script::Super, text } => format!("<sup>{}</sup>", encode_text(text)),
+        Inline::Script { script: Script::Sub, text } => format!("<sub>{}</sub>", encode_text(text)),
+        Inline::Foreign { lang, text } => format!(
+            r#"<span lang="{lang}" xml:lang="{lang}">{}</span>"#,
+            encode_text(text),
+            lang = encode_double_quoted_attribute(lang)
+        ),
+        Inline::Ruby { text, reading } => format!(
+            "<ruby>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
+            encode_text(text),
+            encode_text(reading)
+        ),
+        Inline::Highlight { text, note: None } => format!("<mark>{}</mark>", encode_text(text)),
+        Inline::Highlight { text, note: Some(note) } => format!(
+            r#"<mark title="{}">{}</mark>"#,
+            encode_double_quoted_attribute(note),
+            encode_text(text)
+        ),
+    };
+
+    blocks
+        .iter()
+        .map(|b| match b {
+            Block::Paragraph(p) => format!("<p>{}</p>", p.iter().map(inline).collect::<String>()),
+            Block::Break => r#"<hr class="section-break"/>"#.to_string(),
+            // the note's anchor names the aside, which popup readers show by itself
+            Block::Styled(ParagraphStyle::Footnote, p) => match p.split_first() {
+                Some((Inline::Anchor(id), rest)) => format!(
+                    r#"<aside epub:type="footnote" id="{}"><p class="note">{}</p></aside>"#,
+                    encode_double_quoted_attribute(id),
+                    rest.iter().map(inline).collect::<String>()
+                ),
+                _ => format!(r#"<aside epub:type="footnote"><p class="note">{}</p></aside>"#, p.iter().map(inline).collect::<String>()),
+            },
+            Block::Styled(style, p) => {
+                format!(r#"<p class="{}">{}</p>"#, style.class(), p.iter().map(inline).collect::<String>())
+            }
+            // MathML from the math OCR command, checked to be well-formed
+            Block::Figure(f @ Figure { mathml: Some(math), .. }) => {
+                format!(r#"<div id="{}" class="equation">{}</div>"#, encode_double_quoted_attribute(f.id()), math)
+            }
+            Block::Figure(f) => {
+                let caption = f.caption
+                    .as_deref()
+                    .map(|c| format!("<figcaption>{}</figcaption>", encode_text(c)))
+                    .unwrap_or_default();
+                let img = format!(
+                    r#"<img src="{}" alt="{}"/>"#,
+                    encode_double_quoted_attribute(&image_src(f)),
+                    encode_double_quoted_attribute(&f.alt_text())
+                );
+                match &f.full {
+                    // a thumbnail opens the full image
+                    Some(full) => format!(
+                        r#"<figure id="{}" class="page-scan"><a href="{}">{}</a>{}</figure>"#,
+                        encode_double_quoted_attribute(f.id()),
+                        encode_double_quoted_attribute(&scan_href(full)),
+                        img,
+                        caption
+                    ),
+                    None => format!(r#"<figure id="{}">{}{}</figure>"#, encode_double_quoted_attribute(f.id()), img, caption),
+                }
+            }
+        })
+        .collect::<String>()
+}
@@ -0,0 +1,98 @@
+use crate::ocr::{OcrLine, OcrWord};
+
+/// A page needs at least this many marginal numbers before they are treated as line numbers
+const MIN_LINE_NUMBERS: usize = 3;
+
+/// Line numbers are short: "5", "10", "1250"
+fn is_line_number(word: &OcrWord) -> bool {
+    (1..=4).contains(&word.text.len()) && word.text.chars().all(|c| c.is_ascii_digit())
+}
+
+fn median(mut v: Vec<u32>) -> Option<u32> {
+    if v.is_empty() {
+        return None;
+    }
+    let mid = v.len() / 2;
+    Some(*v.select_nth_unstable(mid).1)
+}
+
+/// Indices of the lines whose edge word (picked by `edge`) is a number standing
+/// in a column of its own, separated from the text block by at least a word height.
+///
+/// `margin_side` tells how far a word sits outside the text block, positive when it
+/// is in the margin. The numbers must also line up with each other (within
+/// `tolerance`), by either of their edges.
+fn marginal_numbers(
+    lines: &[OcrLine],
+    edge: impl Fn(&OcrLine) -> Option<(&OcrWord, Option<&OcrWord>)>,
+    margin_side: impl Fn(&OcrWord, u32) -> i64,
+    body_edge: impl Fn(&OcrWord) -> u32,
+) -> Vec<usize> {
+    let body = median(
+        lines
+            .iter()
+            .filter_map(|l| match edge(l)? {
+                (w, _) if !is_line_number(w) => Some(body_edge(w)),
+                (_, Some(next)) => Some(body_edge(next)),
+                _ => None,
+            })
+            .collect(),
+    );
+    let Some(body) = body else {
+        return Vec::new();
+    };
+
+    let found: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| match edge(l) {
+            Some((w, _)) => is_line_number(w) && margin_side(w, body) > w.bbox.h as i64,
+            None => false,
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if found.len() < MIN_LINE_NUMBERS {
+        return Vec::new();
+    }
+
+    let words: Vec<&OcrWord> = found.iter().filter_map(|&i| edge(&lines[i]).map(|(w, _)| w)).collect();
+    let tolerance = median(words.iter().map(|w| w.bbox.h).collect()).unwrap_or(0) as i64;
+    let aligned = |pos: &dyn Fn(&OcrWord) -> u32| {
+        let (lo, hi) = words.iter().fold((u32::MAX, 0), |(lo, hi), w| (lo.min(pos(w)), hi.max(pos(w))));
+        (hi as i64 - lo as i64) <= tolerance
+    };
+    if aligned(&|w| w.bbox.x) || aligned(&|w| w.bbox.right()) {
+        found
+    } else {
+        Vec::new()
+    }
+}
+
+/// Remove line numbers printed in the left or right margin (court filings,
+/// poetry and critical editions), which OCR otherwise interleaves into the text.
+///
+/// A number is only removed when several of them on the page form a column
+/// clearly outside the text block; lines left empty are dropped.
+pub fn strip_line_numbers(lines: &mut Vec<OcrLine>) {
+    let left = marginal_numbers(
+        lines,
+        |l| l.words.first().map(|w| (w, l.words.get(1))),
+        |w, body| body as i64 - w.bbox.right() as i64,
+        |w| w.bbox.x,
+    );
+    for &i in &left {
+        lines[i].words.remove(0);
+    }
+
+    let right = marginal_numbers(
+        lines,
+        |l| l.words.last().map(|w| (w, l.words.len().checked_sub(2).map(|i| &l.words[i]))),
+        |w, body| w.bbox.x as i64 - body as i64,
+        |w| w.bbox.right(),
+    );
+    for &i in &right {
+        lines[i].words.pop();
+    }
+
+    lines.retain(|l| !l.words.is_empty());
+}
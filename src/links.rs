@@ -0,0 +1,124 @@
+//! Hyperlinks of the PDF's link annotations, carried over into the reflowed text
+use std::collections::HashSet;
+use pdfium_render::prelude::*;
+use crate::document::{self, Block, Chapter, Inline, PageText};
+use crate::Pdf2EPubErr;
+
+/// Where a link of the PDF points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    Url(String),
+
+    /// 1-based page of the same PDF
+    Page(usize),
+}
+
+/// A link annotation of a page, with the text it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLink {
+    pub text: String,
+    pub target: LinkTarget,
+}
+
+impl PageLink {
+    /// `href` of the link; pages are pointed at by their `pdf_page_anchor`
+    pub fn href(&self) -> String {
+        match &self.target {
+            LinkTarget::Url(url) => url.clone(),
+            LinkTarget::Page(page) => format!("#{}", pdf_page_anchor(*page)),
+        }
+    }
+}
+
+/// Anchor id of the start of a PDF page that links point to
+pub fn pdf_page_anchor(page: usize) -> String {
+    format!("pdf-page-{}", page)
+}
+
+fn link_target(link: &PdfLink) -> Option<LinkTarget> {
+    let page = |dest: PdfDestination| dest.page_index().ok().map(|i| LinkTarget::Page(i as usize + 1));
+    if let Some(dest) = link.destination() {
+        return page(dest);
+    }
+    let action = link.action()?;
+    if let Some(uri) = action.as_uri_action() {
+        uri.uri().ok().map(LinkTarget::Url)
+    } else if let Some(local) = action.as_local_destination_action() {
+        local.destination().ok().and_then(page)
+    } else {
+        None
+    }
+}
+
+/// The links of `page` to a URL or to a page of the same PDF, in annotation order.
+/// A link's text is what the PDF's text layer has under it; scans without one only
+/// keep URL links, on the chance the URL itself is printed.
+pub fn page_links(page: &PdfPage) -> Result<Vec<PageLink>, Pdf2EPubErr> {
+    let text = page.text()?;
+    let mut links = Vec::new();
+    for link in page.links().iter() {
+        let Some(target) = link_target(&link) else { continue };
+        let covered = link.rect().map(|r| text.inside_rect(r)).unwrap_or_default();
+        let covered = covered.split_whitespace().collect::<Vec<_>>().join(" ");
+        let text = match (&target, covered.is_empty()) {
+            (_, false) => covered,
+            (LinkTarget::Url(url), true) => url.clone(),
+            (LinkTarget::Page(_), true) => continue,
+        };
+        links.push(PageLink { text, target });
+    }
+    Ok(links)
+}
+
+/// Mark the pages links point to, so `unwrap_pages` gives them an anchor.
+/// Of the halves of a split spread only the first is marked.
+pub fn mark_targets(pages: &mut [PageText]) {
+    let mut targets: HashSet<usize> = pages
+        .iter()
+        .flat_map(|p| &p.links)
+        .filter_map(|l| match l.target {
+            LinkTarget::Page(page) => Some(page),
+            LinkTarget::Url(_) => None,
+        })
+        .collect();
+    for page in pages {
+        page.linked = targets.remove(&page.page);
+    }
+}
+
+/// Turn the first occurrence of each link's text, in order, into a link
+pub fn link_blocks(blocks: Vec<Block>, links: &[PageLink]) -> Vec<Block> {
+    let marks = links
+        .iter()
+        .map(|l| (l.text.clone(), Inline::Link { href: l.href(), text: l.text.clone() }))
+        .collect();
+    document::mark_text(blocks, marks)
+}
+
+/// Turn the links to PDF pages without an anchor in `chapters`, pages left out of the book
+/// (the contents, the index, another work), back into their text
+pub fn unlink_missing_pages<'a>(chapters: impl IntoIterator<Item = &'a mut Chapter>) {
+    let mut chapters: Vec<&mut Chapter> = chapters.into_iter().collect();
+    let anchors: HashSet<String> = chapters
+        .iter()
+        .flat_map(|c| &c.blocks)
+        .filter_map(Block::inlines)
+        .flatten()
+        .filter_map(|i| match i {
+            Inline::Anchor(id) => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    let missing = |href: &str| {
+        href.strip_prefix('#').is_some_and(|id| id.starts_with("pdf-page-") && !anchors.contains(id))
+    };
+    for inlines in chapters.iter_mut().flat_map(|c| &mut c.blocks).filter_map(Block::inlines_mut) {
+        for inline in inlines {
+            let text = match inline {
+                Inline::Link { href, text } if missing(href) => std::mem::take(text),
+                _ => continue,
+            };
+            *inline = Inline::Text(text);
+        }
+    }
+}
@@ -1,20 +1,50 @@
-use std::io::Cursor;
-use std::path::PathBuf;
-use indicatif;
-use clap::Parser;
-use thiserror::Error;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use image::{DynamicImage, GrayImage, RgbImage};
+use anyhow::Result;
+use epub_builder::{EpubBuilder, EpubContent, EpubVersion, MetadataOpf, ZipLibrary, ReferenceType};
+use std::collections::{HashMap, HashSet};
 use pdfium_render::prelude::*;
-use leptess::LepTess;
-use image::{DynamicImage, RgbImage, ImageFormat};
-use anyhow::{Context, Result};
-use epub_builder::{EpubBuilder, EpubContent, ZipLibrary, ReferenceType};
+use pdf2epub::document::{self, Block, Chapter, Figure, Inline, PageText, ParagraphStyle, Work};
+use pdf2epub::cache::PageOcr;
+use pdf2epub::ocr;
+use pdf2epub::{
+    accuracy, annotations, anthology, balance, bench, cache, config, debug, duplicates, epigraphs, epub2, figures, frontmatter, glossary, hooks, html, index, junk, kindle, layout, linenum, links, manifest, mask, metadata, metrics, notes, order, package, pagelabels, pagelayout, paragraphs, preprocess, quality,
+    references, report, retry, rules, ruby, scripts, sentences, ssml, stats, style, thumbs, timings, titles, toc, validation, vector, wizard, xref,
+};
+#[cfg(feature = "captioning")]
+use pdf2epub::captioning;
+#[cfg(feature = "math")]
+use pdf2epub::math;
+use pdf2epub::{blocks_to_markup, img_source_from_page, page_dpi, peel_trailing_page_num, split_blocks, unwrap_pages, warn, Pdf2EPubErr, RENDER_DPI};
+use pdf2epub::exit_code;
+
+/// Language of the book when none is given
+const DEFAULT_LANG: &str = "en";
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true, args_conflicts_with_subcommands = true,
+    args_override_self = true, group(ArgGroup::new("stats_pages").args(["title_page", "colophon"]).multiple(true)))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input file path
-    #[arg(short, long)]
-    input: PathBuf,
+    #[arg(short, long, required = true)]
+    input: Option<PathBuf>,
+
+    /// Settings file [default: ~/.config/pdf2epub/config.toml, if present]
+    #[arg(long, env = "PDF2EPUB_CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Apply a named set of settings: `novel`, `academic`, `bad-scan`,
+    /// or a `[profile.NAME]` of the settings file
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
 
     /// Title of the book
     #[arg(long)]
@@ -24,222 +54,2035 @@ struct Args {
     #[arg(long)]
     author: Option<String>,
 
+    /// Look up title, author, publisher, date, subjects and cover on OpenLibrary by ISBN.
+    /// Without a value the ISBN printed on the copyright page is used.
+    #[arg(long, num_args = 0..=1, value_name = "ISBN")]
+    isbn: Option<Option<String>>,
+
+    /// Take title, authors, publisher, date, subjects, description, series and cover from
+    /// a calibre `metadata.opf`; flags given win over it, and it over `--isbn`
+    #[arg(long, value_name = "FILE")]
+    metadata_opf: Option<PathBuf>,
+
+    /// Book metadata as one JSON object, e.g. `{"title": "…", "authors": ["…"], "subjects": ["…"]}`;
+    /// flags given win over it, and it over `--metadata-opf`
+    #[arg(long, value_name = "JSON", value_parser = metadata::parse_meta_json)]
+    meta_json: Option<metadata::BookMetadata>,
+
+    /// Description of the book; without it one is made from the first paragraphs
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Length (in characters) of the generated description
+    #[arg(long, default_value_t = 500)]
+    description_length: usize,
+
+    /// Subject or keyword of the book (dc:subject). May be repeated.
+    #[arg(long = "subject", value_name = "SUBJECT")]
+    subjects: Vec<String>,
+
+    /// UUID identifying the book (dc:identifier), so re-conversions replace the same book in readers
+    #[arg(long)]
+    uuid: Option<uuid::Uuid>,
+
+    /// Identifier of the book (dc:identifier) other than a UUID, e.g. `urn:isbn:9780306406157` or a DOI
+    #[arg(long, conflicts_with = "uuid")]
+    identifier: Option<String>,
+
+    /// Stylesheet of the book: typography, margins, paragraph indents or spacing, headings
+    #[arg(long, value_enum)]
+    theme: Option<style::Theme>,
+
+    /// Embed this font (.ttf, .otf, .woff) and use it for the body text
+    #[arg(long, value_name = "FONT")]
+    embed_font: Option<PathBuf>,
+
+    /// Paragraph alignment, overriding the theme's
+    #[arg(long, value_enum)]
+    text_align: Option<style::TextAlign>,
+
+    /// Let e-readers hyphenate words (`hyphens: auto`), for evenly justified lines
+    #[arg(long)]
+    hyphenate: bool,
+
+    /// Language of the book as a BCP 47 tag (e.g. en, de, fr-CA), set on every
+    /// document so readers hyphenate and pronounce it correctly; its abbreviations
+    /// ("Mr.", "etc.") are not taken for sentence ends when unwrapping lines.
+    /// Left out, it is the language of `--ocr-lang`
+    #[arg(long, env = "PDF2EPUB_LANG", default_value = DEFAULT_LANG)]
+    lang: String,
+
+    /// Resolution to render pages at for OCR and figure extraction
+    #[arg(long, env = "PDF2EPUB_DPI", default_value_t = RENDER_DPI, global = true)]
+    dpi: u16,
+
+    /// Render each page at the resolution that makes its body text about PX pixels high,
+    /// rather than all at `--dpi`; the text size is estimated from the page width
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(8..=200))]
+    text_height: Option<u32>,
+
+    /// Hand the pages to Tesseract as files in a temporary directory rather than in
+    /// memory, for large pages (art books, maps) on machines short of memory
+    #[arg(long)]
+    low_memory: bool,
+
+    /// Keep the OCR of every page in FILE and reuse it on the next run for the pages
+    /// that did not change, recognising only new and changed pages again
+    #[arg(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    /// Tesseract language(s) to recognise, e.g. eng, deu or fra+eng; left out, the
+    /// language of `--lang` (e.g. hin for hi, tam for ta)
+    #[arg(long, env = "PDF2EPUB_OCR_LANG", default_value = ocr::DEFAULT_OCR_LANGUAGE, global = true)]
+    ocr_lang: String,
+
+    /// Recognise the PDF pages FIRST-LAST with other Tesseract languages than `--ocr-lang`,
+    /// e.g. `120-180=eng+ell` for pages quoting Greek. May be repeated; later ranges win.
+    #[arg(long, value_name = "PAGES=LANGS", value_parser = scripts::parse_page_languages)]
+    page_lang: Vec<scripts::PageLanguages>,
+
+    /// Tesseract page segmentation mode, e.g. 4 for a single column of text or 6 for a uniform block
+    #[arg(long, value_name = "MODE", value_parser = clap::value_parser!(u8).range(0..=13), global = true)]
+    psm: Option<u8>,
+
+    /// Tesseract engine mode. The legacy engine sometimes beats LSTM on unusual typefaces,
+    /// but needs language data that includes it (tessdata, not tessdata_fast or tessdata_best)
+    #[arg(long, value_enum, default_value_t = ocr::EngineMode::Default, global = true)]
+    oem: ocr::EngineMode,
+
+    /// Words (one per line) Tesseract should recognise as they are instead of "correcting"
+    /// them into dictionary words: character names, technical terms
+    #[arg(long, value_name = "FILE", global = true)]
+    user_words: Option<PathBuf>,
+
+    /// Patterns (one per line, e.g. `\d\d\d\d-\d\d`) of words Tesseract should accept
+    #[arg(long, value_name = "FILE", global = true)]
+    user_patterns: Option<PathBuf>,
+
+    /// Only recognise these characters, e.g. `0123456789.,-` for numeric tables
+    #[arg(long, value_name = "CHARS", global = true)]
+    char_whitelist: Option<String>,
+
+    /// Never recognise these characters, e.g. Cyrillic lookalikes in a Latin text
+    #[arg(long, value_name = "CHARS", global = true)]
+    char_blacklist: Option<String>,
+
+    /// Directory of Tesseract's .traineddata files, instead of Tesseract's default
+    #[arg(long, env = "PDF2EPUB_TESSDATA", value_name = "DIR", global = true)]
+    tessdata: Option<PathBuf>,
+
+    /// Directory holding the pdfium library
+    #[arg(long, env = "PDF2EPUB_PDFIUM_PATH", value_name = "DIR", default_value = "./pdfium/lib", global = true)]
+    pdfium_path: PathBuf,
+
+    /// Start the epub with a generated title page (title, author, publisher and date if known)
+    #[arg(long)]
+    title_page: bool,
+
+    /// Largest XHTML file of the epub, in KB: longer chapters are split between
+    /// paragraphs into several files, as some e-ink readers stall on large ones
+    #[arg(long, value_name = "KB", default_value_t = 250, value_parser = clap::value_parser!(u64).range(1..))]
+    max_xhtml_size: u64,
+
+    /// EPUB version to write: 3, or 2 for older readers, with an NCX table of contents
+    /// only and XHTML 1.1 content documents
+    #[arg(long, value_name = "VERSION", default_value_t = 3, value_parser = clap::value_parser!(u8).range(2..=3))]
+    epub_version: u8,
+
+    /// End the book with a colophon recording how it was converted
+    /// (source file, date, DPI, OCR language and confidence, pdf2epub version)
+    #[arg(long)]
+    colophon: bool,
+
+    /// Show the word count and estimated reading time on the title page,
+    /// and the length of every chapter in the colophon; needs --title-page or --colophon
+    #[arg(long, requires = "stats_pages")]
+    reading_stats: bool,
+
+    /// Series the book belongs to (written as calibre series metadata)
+    #[arg(long)]
+    series: Option<String>,
+
+    /// Position of the book in its series, e.g. 2 or 2.5
+    #[arg(long, requires = "series")]
+    series_index: Option<f32>,
+
     /// If set to true, remove pagenum from the bottom of the page
     #[arg(long)]
     extract_pagenum: bool,
+
+    /// Add a page-list to the epub's navigation, so readers can show and go to the print
+    /// edition's pages. Pages are named by the PDF's page labels, or else by the numbers
+    /// found with `--extract-pagenum`.
+    #[arg(long)]
+    page_list: bool,
+
+    /// Keep an invisible `page-N` anchor where each page of the print edition starts, for
+    /// deep links into the book; implied by `--page-list`
+    #[arg(long)]
+    page_anchors: bool,
+
+    /// Fail, without writing the epub, when its structure check finds problems; without
+    /// it they are warnings
+    #[arg(long)]
+    strict: bool,
+
+    /// Leave out the half-title, copyright, CIP and printer's pages at the start of the book,
+    /// or move them to an appendix (`--skip-front-matter appendix`)
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "drop", value_name = "MODE")]
+    skip_front_matter: Option<frontmatter::FrontMatter>,
+
+    /// Move the copyright page from the front of the book to after the main text
+    #[arg(long)]
+    move_copyright_to_back: bool,
+
+    /// Manifest of the works in an omnibus scan, one `FIRST-LAST | Title | Author` line per work.
+    /// Each work becomes a top-level section of the epub; pages outside all works are left out.
+    #[arg(long, value_name = "MANIFEST")]
+    anthology: Option<PathBuf>,
+
+    /// Write every work of the anthology to its own epub instead
+    #[arg(long, requires = "anthology")]
+    split_works: bool,
+
+    /// TOML file of `[[rule]]` regex `pattern`s and their `replace`ments, applied in order
+    /// to the OCR text of every page to fix recurring misreadings
+    #[arg(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// Split chapters at the entries of the book's printed table of contents and use its titles
+    #[arg(long)]
+    parse_toc: bool,
+
+    /// Set chapter titles printed in ALL CAPS in title case, in the headings and the
+    /// table of contents
+    #[arg(long)]
+    title_case: bool,
+
+    /// Title the chapters whose headings are only a number ("XIV", "14") by TEMPLATE,
+    /// `{n}` standing for the number, e.g. "Chapter {n}"
+    #[arg(long, value_name = "TEMPLATE")]
+    chapter_numbers: Option<String>,
+
+    /// How `--chapter-numbers` writes the numbers
+    #[arg(long, value_enum, default_value_t = titles::Numerals::Arabic, requires = "chapter_numbers")]
+    chapter_numerals: titles::Numerals,
+
+    /// Also number the chapters whose titles have no number, counting from the chapters
+    /// before them: "Chapter 3: The Sea"
+    #[arg(long, requires = "chapter_numbers")]
+    number_all_chapters: bool,
+
+    /// Set epigraphs (a short passage above a "— Author" line at the start of a chapter)
+    /// and the dedication apart from the body text, in a style of their own
+    #[arg(long)]
+    epigraphs: bool,
+
+    /// Drop the index at the back of the book, keep it as its own chapter,
+    /// or keep it with its page numbers linked to the pages
+    #[arg(long, value_enum, value_name = "MODE", requires_if("link", "extract_pagenum"))]
+    index: Option<index::IndexMode>,
+
+    /// Link the note markers in the text to the book's notes section, and each note back to its marker
+    #[arg(long)]
+    endnotes: bool,
+
+    /// Split the footnotes off the bottom of the pages and put them after the paragraph
+    /// referring to them, in a "Footnotes" chapter, or in EPUB 3 popups
+    #[arg(long, value_enum, value_name = "MODE")]
+    footnotes: Option<notes::Footnotes>,
+
+    /// Move the bibliography or reference list at the back of the book to a chapter of its own,
+    /// with one hanging-indent paragraph per entry
+    #[arg(long)]
+    references: bool,
+
+    /// Link the URLs and DOIs of the reference list
+    #[arg(long, requires = "references")]
+    link_references: bool,
+
+    /// Render pages in color so cropped figures keep their colors (OCR still runs on grayscale)
+    #[arg(long)]
+    color: bool,
+
+    /// Straighten curved text lines (photographed or spine-bound scans) before OCR
+    #[arg(long, global = true)]
+    dewarp: bool,
+
+    /// Remove the dark gutter shadow of flatbed scans of bound books before OCR
+    #[arg(long, global = true)]
+    remove_gutter: bool,
+
+    /// Remove speckle noise (noisy photocopies, microfilm) before OCR
+    #[arg(long, value_enum, global = true)]
+    despeckle: Option<preprocess::Despeckle>,
+
+    /// Largest dark blob, in pixels, removed by `--despeckle components`
+    #[arg(long, default_value_t = 8, global = true)]
+    speckle_size: u32,
+
+    /// Suppress text showing through from the other side of thin paper before OCR
+    #[arg(long, global = true)]
+    suppress_bleed_through: bool,
+
+    /// Remove line numbers printed in the page margin (legal documents, critical editions)
+    #[arg(long)]
+    strip_line_numbers: bool,
+
+    /// Drop the lines of punctuation noise OCR reads into pictures and decorations
+    /// ("~,.;’` |"), told by their share of symbols, dictionary words and confidence
+    #[arg(long)]
+    drop_junk_lines: bool,
+
+    /// Drop the lines whose mean word confidence (0-100) is below THRESHOLD, for what
+    /// the junk classifier lets through: smudges and show-through read as words
+    #[arg(long, value_name = "THRESHOLD")]
+    min_line_confidence: Option<f32>,
+
+    /// Write the lines dropped by `--drop-junk-lines` and `--min-line-confidence` to
+    /// FILE, one per line with their page and confidence, to check nothing was lost
+    #[arg(long, value_name = "FILE")]
+    dropped_lines: Option<PathBuf>,
+
+    /// How readily a blank line is taken for a paragraph break rather than a page or
+    /// column gap: only after a finished sentence (low), also before a capitalised line
+    /// (medium), or always (high)
+    #[arg(long, value_enum, value_name = "LEVEL", default_value_t = paragraphs::Aggressiveness::Medium)]
+    paragraph_breaks: paragraphs::Aggressiveness,
+
+    /// Also start a paragraph at an indented line, for books that mark paragraphs only
+    /// by a first-line indent; indents are measured from the word coordinates of the OCR
+    #[arg(long)]
+    indented_paragraphs: bool,
+
+    /// Also start a paragraph after a line ending well short of the right margin, for
+    /// justified text; line ends are measured from the word coordinates of the OCR
+    #[arg(long)]
+    short_line_breaks: bool,
+
+    /// Characters that end a sentence, replacing the default set (. ? ! … and those of
+    /// other scripts), e.g. ".?!:" for text whose paragraphs end in a colon
+    #[arg(long, value_name = "CHARS")]
+    sentence_end: Option<String>,
+
+    /// Take BLANKS or more blank lines in a row for a section break (a deliberate gap,
+    /// set as a rule) rather than a paragraph break
+    #[arg(long, value_name = "BLANKS", num_args = 0..=1, default_missing_value = "2")]
+    section_breaks: Option<usize>,
+
+    /// Detect overlays repeated on every page (watermarks, library stamps) and remove them before OCR
+    #[arg(long)]
+    remove_watermarks: bool,
+
+    /// Blank out a region of every page before OCR, as `x,y,w,h` fractions of the page
+    /// (e.g. `0.7,0.85,0.3,0.15`), or of some pages as `FIRST-LAST:x,y,w,h`. May be repeated.
+    #[arg(long = "exclude-region", value_name = "[PAGES:]X,Y,W,H")]
+    exclude_regions: Vec<mask::PageRegion>,
+
+    /// Read every page as N columns of equal width, for layouts Tesseract segments wrong
+    #[arg(long, value_name = "N", value_parser = pagelayout::parse_columns)]
+    columns: Option<usize>,
+
+    /// Turn some pages clockwise by DEGREES (90, 180 or 270) before OCR, e.g. `45-60=90` for
+    /// pages printed in landscape. May be repeated.
+    #[arg(long, value_name = "PAGES=DEGREES", value_parser = pagelayout::parse_page_rotation)]
+    rotate: Vec<pagelayout::PageRotation>,
+
+    /// Layout overrides for some pages, one `FIRST-LAST key=value ...` line per range,
+    /// e.g. `12-40 columns=2` or `41 boundaries=0.3,0.65`
+    #[arg(long, value_name = "FILE")]
+    layout: Option<PathBuf>,
+
+    /// Keep the highlights and comments of the PDF: marked where they occur in the text
+    /// (comments not attached to text still go to an "Annotations" chapter), or all
+    /// listed by page in that chapter
+    #[arg(long, value_enum, value_name = "MODE")]
+    annotations: Option<annotations::AnnotationMode>,
+
+    /// Look for pages scanned twice in a row and warn about them or drop the repeats
+    #[arg(long, value_enum, value_name = "ACTION")]
+    duplicate_pages: Option<duplicates::Duplicates>,
+
+    /// Split scans of double-page spreads into their left and right pages
+    #[arg(long)]
+    split_spreads: bool,
+
+    /// Extract embedded images (with their captions) as figures instead of OCRing over them
+    #[arg(long)]
+    extract_images: bool,
+
+    /// Extract drawings made of vector paths (charts, diagrams) as SVG figures
+    #[arg(long)]
+    extract_vector_graphics: bool,
+
+    /// Number the captioned figures in order and link the references to them in the text
+    #[arg(long)]
+    number_figures: bool,
+
+    /// Add a "List of Figures" chapter linking to every figure by its caption
+    #[arg(long)]
+    list_of_figures: bool,
+
+    /// Downscale embedded figures wider than this many pixels
+    #[arg(long)]
+    max_image_width: Option<u32>,
+
+    /// Re-encode embedded figures in this format
+    #[arg(long, value_enum)]
+    image_format: Option<figures::FigureFormat>,
+
+    /// JPEG quality (1-100) used when re-encoding figures
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    image_quality: Option<u8>,
+
+    /// Command that describes an uncaptioned figure for its alt text.
+    /// The image is written to its stdin, the description is read from its stdout.
+    #[cfg(feature = "captioning")]
+    #[arg(long)]
+    alt_text_cmd: Option<String>,
+
+    /// Command that reads a display equation as MathML, for the text in place of its image.
+    /// The image is written to its stdin, the MathML is read from its stdout.
+    #[cfg(feature = "math")]
+    #[arg(long)]
+    math_ocr_cmd: Option<String>,
+
+    /// Command run over the cleaned text of every chapter before the epub is written,
+    /// reading the text on stdin and writing the fixed text to stdout. May be repeated.
+    #[arg(long = "post-process", value_name = "CMD")]
+    post_process: Vec<String>,
+
+    /// Keep `--post-process` commands (spellcheckers, LLM cleanup) from changing names and
+    /// invented words: capitalised words found mid-sentence throughout the book, and the
+    /// `--user-words`
+    #[arg(long, requires = "post_process")]
+    protect_names: bool,
+
+    /// Also write a standalone .html file next to the epub
+    #[arg(long)]
+    html: bool,
+
+    /// Also write the text as SSML (sentence-segmented, with pauses) for TTS engines
+    #[arg(long)]
+    ssml: bool,
+
+    /// Also write a JSON report (`.report.json`) with word counts, reading times
+    /// and an OCR quality score per chapter, and warnings about a likely wrong reading order
+    #[arg(long)]
+    report: bool,
+
+    /// Word list (one word per line) for the report's dictionary hit rate and for telling
+    /// words from noise with `--drop-junk-lines` [default: /usr/share/dict/words, if present]
+    #[arg(long, value_name = "FILE")]
+    dictionary: Option<PathBuf>,
+
+    /// Also convert the epub for Kindle using Calibre's ebook-convert (AZW3) or kindlegen (MOBI)
+    #[arg(long)]
+    kindle: bool,
+
+    /// Write the epub's files unzipped into DIR (`mimetype`, `META-INF/`, `OEBPS/`) instead
+    /// of the epub, to edit the XHTML by hand and package it again
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["kindle", "split_works"])]
+    output_dir: Option<PathBuf>,
+
+    /// Path to the ebook-convert/kindlegen binary, instead of searching PATH
+    #[arg(long, requires = "kindle")]
+    kindle_tool: Option<PathBuf>,
+
+    /// Print how long each stage of the conversion took, in total and per page
+    #[arg(long)]
+    timings: bool,
+
+    /// Highlight words recognised with a confidence (0-100) below THRESHOLD, for proofreading
+    #[arg(long, value_name = "THRESHOLD", num_args = 0..=1, default_missing_value = "60")]
+    mark_uncertain: Option<f32>,
+
+    /// Show the other readings Tesseract considered for a highlighted word as its tooltip
+    #[arg(long, requires = "mark_uncertain")]
+    uncertain_alternatives: bool,
+
+    /// Keep superscripts and subscripts (note markers, ordinal suffixes, chemical formulas)
+    /// as such, found from the size and baseline of the recognised characters
+    #[arg(long)]
+    superscripts: bool,
+
+    /// Set furigana, the small kana printed over kanji in Japanese books, as ruby over
+    /// the kanji instead of letting the readings run into the text
+    #[arg(long)]
+    ruby: bool,
+
+    /// Tag runs of words in another script than the book's (Greek or Cyrillic in a Latin
+    /// text) with their language, and keep `--post-process` commands from changing them
+    #[arg(long)]
+    script_runs: bool,
+
+    /// Set the pages OCR fails on at their place in the text as a thumbnail of the page
+    /// linking to its full image, rather than leaving them out
+    #[arg(long)]
+    page_images: bool,
+
+    /// Also set the pages whose mean OCR confidence (0-100) is below THRESHOLD as page
+    /// images, in place of their text
+    #[arg(long, value_name = "THRESHOLD", requires = "page_images")]
+    page_images_below: Option<f32>,
+
+    /// Write the pages whose mean OCR confidence is below `--review-threshold`, and those
+    /// OCR failed on, to FILE for checking by hand: as JSON if it ends in .json, as text otherwise
+    #[arg(long, value_name = "FILE")]
+    review_list: Option<PathBuf>,
+
+    /// Mean OCR confidence (0-100) below which a page goes on the review list
+    #[arg(long, value_name = "THRESHOLD", default_value_t = 70.0, requires = "review_list")]
+    review_threshold: f32,
+
+    /// Try a page again up to N times when rendering or OCR fails on it, or when its mean
+    /// OCR confidence is below `--retry-below`; the best try is kept
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: u32,
+
+    /// Resolution of the further tries at recognising a page, in order; the last one stays.
+    /// May be repeated, e.g. `--retry-dpi 400 --retry-dpi 600`
+    #[arg(long, value_name = "DPI", requires = "retries")]
+    retry_dpi: Vec<u16>,
+
+    /// Page segmentation modes the further tries at recognising a page cycle through.
+    /// May be repeated, e.g. `--retry-psm 6 --retry-psm 4`
+    #[arg(long, value_name = "MODE", value_parser = clap::value_parser!(u8).range(0..=13), requires = "retries")]
+    retry_psm: Vec<u8>,
+
+    /// Milliseconds to wait before the first retry, doubled before each one after it
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    retry_backoff: u64,
+
+    /// Mean OCR confidence (0-100) below which a recognised page is tried again
+    #[arg(long, value_name = "THRESHOLD", requires = "retries")]
+    retry_below: Option<f32>,
+
+    /// Save debug images of every page here: as rendered, and as handed to OCR with
+    /// the recognised words boxed from red (low confidence) to green (high)
+    #[arg(long, value_name = "DIR")]
+    debug_dir: Option<PathBuf>,
+
+    /// Serve Prometheus metrics (conversion counters, per-stage histograms) on
+    /// http://ADDR/metrics while converting
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Seconds to keep serving metrics after the conversion, for a last scrape
+    #[arg(long, value_name = "SECONDS", default_value_t = 30, requires = "metrics_addr")]
+    metrics_linger: u64,
 }
 
-#[derive(Debug, Error)]
-pub enum Pdf2EPubErr {
-    #[error("IO error: {0}")]
-    IOError(#[from] std::io::Error),
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Time each stage of the pipeline on a real PDF, without writing a book
+    Bench {
+        /// PDF to convert
+        pdf: PathBuf,
 
-    #[error("PdfiumError error: {0}")]
-    PdfiumError(#[from] PdfiumError),
+        /// Only process the first N pages
+        #[arg(long, value_name = "N")]
+        pages: Option<usize>,
+    },
 
-    #[error("AnyHowError error: {0}")]
-    AnyHowError(#[from] anyhow::Error),
+    /// Recognise a PDF and compare its text with a known-good transcription, printing the
+    /// character and word error rate of each page
+    Diff {
+        /// PDF to recognise
+        pdf: PathBuf,
 
-    #[error("ZipLibrary error")]
-    ZipLibraryError(#[from] epub_builder::Error),
+        /// Known-good text of the book; with its pages separated by form feeds, page N is
+        /// compared with page N of the PDF
+        #[arg(long, value_name = "FILE")]
+        reference: PathBuf,
+
+        /// Only process the first N pages
+        #[arg(long, value_name = "N")]
+        pages: Option<usize>,
+    },
+
+    /// Write a settings file by answering a few questions about a sample of the book
+    #[command(alias = "wizard")]
+    Init {
+        /// PDF to take the sample page from
+        sample: PathBuf,
+
+        /// Page (1-based) to recognise as the sample [default: the middle page]
+        #[arg(long, value_name = "N")]
+        page: Option<usize>,
+
+        /// Where to write the settings [default: ~/.config/pdf2epub/config.toml]
+        #[arg(long, env = "PDF2EPUB_CONFIG", value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Replace an existing settings file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Render a small image of every page into a directory, e.g. to pick the cover page
+    Thumbs {
+        /// PDF to render
+        pdf: PathBuf,
+
+        /// Directory to write the thumbnails to, as `page-0001.png` and on
+        #[arg(long, value_name = "DIR", default_value = "thumbs")]
+        output: PathBuf,
+
+        /// Width of the thumbnails in pixels
+        #[arg(long, value_name = "PX", default_value_t = thumbs::DEFAULT_WIDTH, value_parser = clap::value_parser!(u32).range(16..=2000))]
+        width: u32,
+
+        /// Only render the first N pages
+        #[arg(long, value_name = "N")]
+        pages: Option<usize>,
+    },
+
+    /// Print a shell completion script, e.g. `pdf2epub completions bash > /etc/bash_completion.d/pdf2epub`
+    Completions {
+        shell: clap_complete::Shell,
+    },
 }
 
-/// Convert a single `PdfPage` into the RGB byte buffer
-/// - `target_dpi` controls the rasterisation resolution
-pub fn img_source_from_page(
-    page: &PdfPage,
-    target_dpi: u16,
-) -> Result<RgbImage, Pdf2EPubErr> {
-    let w_inch = page.paper_size().width().to_inches();
-    let w_pixels = (w_inch * (target_dpi as f32)) as i32;
+/// Path of a figure's image inside the epub
+fn epub_image_path(fig: &Figure) -> String {
+    format!("images/{}", fig.name)
+}
 
-    let h_inch = page.paper_size().height().to_inches();
-    let h_pixels = (h_inch * (target_dpi as f32)) as i32;
+/// File of the epub showing the full image `full` of a page scan on a page of its own
+fn scan_page_file(full: &Figure) -> String {
+    Path::new(&full.name).with_extension("xhtml").display().to_string()
+}
 
-    let render_config = PdfRenderConfig::new()
-        .set_target_width(w_pixels)
-        .set_target_height(h_pixels)
-        .use_grayscale_rendering(true);
+/// The page of its own of the full image `full` of a page scan, which the thumbnail in the
+/// text links to; it stays out of the reading order
+fn scan_page_xhtml(full: &Figure, ctx: &XhtmlContext) -> String {
+    use html_escape::encode_double_quoted_attribute;
 
-    let bitmap = page.render_with_config(&render_config)?;
-    let dyn_image: DynamicImage = bitmap.as_image();
-    let rgb8: RgbImage = dyn_image.into_rgb8();
+    let body = format!(
+        r#"<figure class="page-scan"><img src="{}" alt="{}"/></figure>"#,
+        encode_double_quoted_attribute(&epub_image_path(full)),
+        encode_double_quoted_attribute(&full.alt_text())
+    );
+    xhtml_document(&full.alt_text(), &body, ctx)
+}
 
-    Ok(rgb8)
+/// File of the epub each anchor id lives in, for chapters split into `parts`
+fn anchor_files(parts: &[Vec<&[Block]>]) -> HashMap<String, String> {
+    let mut files = HashMap::new();
+    for (n, chapter) in parts.iter().enumerate() {
+        for (part, blocks) in chapter.iter().enumerate() {
+            for block in blocks.iter() {
+                // an id is the first element that has it
+                if let Block::Figure(f) = block {
+                    files.entry(f.id().to_string()).or_insert_with(|| chapter_file(n, part));
+                }
+                if let Some(p) = block.inlines() {
+                    for i in p {
+                        if let Inline::Anchor(id) = i {
+                            files.entry(id.clone()).or_insert_with(|| chapter_file(n, part));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    files
 }
 
-/// Perform ocr on `RbgImage` using Tesseract
-pub fn ocr_rgb_png(img: &RgbImage) -> Result<String, Pdf2EPubErr> {
-    let mut png_bytes: Vec<u8> = Vec::new();
-    DynamicImage::ImageRgb8(img.clone())
-        .write_to(&mut Cursor::new(&mut png_bytes), ImageFormat::Png)
-        .context("failed to encode PNG")?;
+/// File name of part `part` of the n-th chapter (both 0-based) inside the epub:
+/// `chapter-3.xhtml`, then `chapter-3-2.xhtml` and on
+fn chapter_file(n: usize, part: usize) -> String {
+    match part {
+        0 => format!("chapter-{}.xhtml", n + 1),
+        _ => format!("chapter-{}-{}.xhtml", n + 1, part + 1),
+    }
+}
 
-    let mut lt = LepTess::new(None, "eng")
-        .context("could not create Tesseract engine")?;
+/// What all XHTML files of one epub share
+struct XhtmlContext {
+    /// File each anchor id lives in, see `anchor_files`
+    anchors: HashMap<String, String>,
 
-    lt.set_image_from_mem(&png_bytes)
-        .context("Tesseract failed to load image from memory")?;
+    /// Whether the epub has a `stylesheet.css` to link
+    stylesheet: bool,
 
-    let text = lt.get_utf8_text()
-        .context("Tesseract failed to recognise text")?;
+    /// Language tag for the `lang` attributes
+    lang: String,
 
-    Ok(text)
+    /// Whether the epub is an EPUB 2 one, whose documents are XHTML 1.1
+    epub2: bool,
 }
 
-/// Remove a trailing page number like "...some text\n\n11" and return it.
-/// On failure the original text is left intact and page_num is None.
-pub fn peel_trailing_page_num(s: &str) -> (&str, Option<u32>) {
-    let trimmed = s.trim_end();
-    match trimmed.rsplit_once(char::is_whitespace) {
-        Some((head, tail)) if !tail.is_empty() && tail.chars().all(|c| c.is_ascii_digit()) => {
-            (head.trim_end(), Some(tail.parse::<u32>().expect("number")))
+/// Wrap `body` into an XHTML document
+fn xhtml_document(title: &str, body: &str, ctx: &XhtmlContext) -> String {
+    use html_escape::encode_text;
+
+    let link = if ctx.stylesheet {
+        r#"<link rel="stylesheet" type="text/css" href="stylesheet.css"/>"#
+    } else {
+        ""
+    };
+    let lang = html_escape::encode_double_quoted_attribute(&ctx.lang);
+    if ctx.epub2 {
+        return format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+               {}
+               <html xmlns="http://www.w3.org/1999/xhtml" xml:lang="{lang}">
+                 <head><title>{}</title>{}</head>
+                 <body>{}</body>
+               </html>"#,
+            epub2::XHTML11_DOCTYPE,
+            encode_text(title),
+            link,
+            epub2::downgrade_markup(body),
+            lang = lang,
+        );
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+           <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}">
+             <head><title>{}</title>{}</head>
+             <body>{}</body>
+           </html>"#,
+        encode_text(title),
+        link,
+        body,
+        lang = lang,
+    )
+}
+
+/// A simple title page: title, author and further `lines` such as the publication info
+fn title_page_xhtml(title: &str, author: &str, lines: &[String], ctx: &XhtmlContext) -> String {
+    use html_escape::encode_text;
+
+    let lines = lines
+        .iter()
+        .map(|l| format!("<p>{}</p>", encode_text(l)))
+        .collect::<String>();
+    let body = format!("<h1>{}</h1><p>{}</p>{}", encode_text(title), encode_text(author), lines);
+    xhtml_document(title, &body, ctx)
+}
+
+/// Render a chapter as XHTML; `#id` links are pointed at the file holding the anchor
+fn text_to_xhtml(title: &str, blocks: &[Block], ctx: &XhtmlContext) -> String {
+    let paras = blocks_to_markup(blocks, epub_image_path, scan_page_file, |href| {
+        match href.strip_prefix('#').and_then(|id| ctx.anchors.get(id)) {
+            Some(file) => format!("{}{}", file, href),
+            None => href.to_string(),
         }
-        _ => (trimmed, None),
+    });
+    xhtml_document(title, &paras, ctx)
+}
+
+/// Add the parts of the n-th chapter after its first, which are left out of the table
+/// of contents
+fn add_continued_parts(
+    epub: &mut EpubBuilder<ZipLibrary>,
+    title: &str,
+    n: usize,
+    parts: &[&[Block]],
+    ctx: &XhtmlContext,
+) -> Result<(), Pdf2EPubErr> {
+    for (part, blocks) in parts.iter().enumerate().skip(1) {
+        let xhtml = text_to_xhtml(title, blocks, ctx);
+        epub.add_content(EpubContent::new(chapter_file(n, part), xhtml.as_bytes()))?;
+    }
+    Ok(())
+}
+
+/// Unwrap `pages` into blocks, keeping the PDF's links found on them and,
+/// with `--annotations inline`, marking its highlights
+fn page_blocks(pages: Vec<PageText>, page_anchors: bool, args: &Args) -> Vec<Block> {
+    let page_links: Vec<links::PageLink> = pages.iter().flat_map(|p| p.links.iter().cloned()).collect();
+    let highlights: Vec<annotations::PageAnnotation> = match args.annotations {
+        Some(annotations::AnnotationMode::Inline) => pages.iter().flat_map(|p| p.annotations.iter().cloned()).collect(),
+        _ => Vec::new(),
+    };
+    let mut sentences = sentences::SentenceRules::for_language(&args.lang);
+    if let Some(terminators) = &args.sentence_end {
+        sentences = sentences.with_terminators(terminators);
     }
+    let rules = paragraphs::ParagraphRules {
+        sentences,
+        aggressiveness: args.paragraph_breaks,
+        indent: args.indented_paragraphs,
+        short_lines: args.short_line_breaks,
+        section_break: args.section_breaks,
+    };
+    let blocks = links::link_blocks(unwrap_pages(pages, page_anchors, &rules), &page_links);
+    annotations::mark_blocks(blocks, &highlights)
 }
 
-/// Incrementally unwraps hard-wrapped lines *and* removes fake page-break
-/// blank lines.  Call `push_line()` for every raw line (in reading order),
-/// `page_break()` after finishing a page, and `finish()` at the very end.
-pub struct LineUnwrapper {
-    /// current paragraph being built
-    buf: String,
+/// Turn the pages of one work into chapters, applying the page-level options
+/// (index, copyright page, printed table of contents, front matter)
+fn build_chapters(mut pages: Vec<PageText>, title: &str, args: &Args) -> Vec<Chapter> {
+    if args.epigraphs {
+        epigraphs::mark_dedication(&mut pages);
+    }
+    let annotation_blocks = match args.annotations {
+        Some(mode) => annotations::annotations_blocks(&pages, mode == annotations::AnnotationMode::Inline),
+        None => Vec::new(),
+    };
+    let index_pages: Vec<PageText> = match args.index {
+        Some(_) => index::INDEX.find(&pages).map(|r| pages.drain(r).collect()).unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let notes = match args.endnotes.then(|| notes::find_notes(&pages)).flatten() {
+        Some(range) => notes::parse_notes(&pages.drain(range).collect::<Vec<_>>()),
+        None => Vec::new(),
+    };
+    let references = match args.references.then(|| references::find_references(&pages)).flatten() {
+        Some(range) => references::parse_entries(&pages.drain(range).collect::<Vec<_>>()),
+        None => Vec::new(),
+    };
+    let copyright_page = if args.move_copyright_to_back {
+        frontmatter::find_copyright_page(&pages).map(|i| pages.remove(i))
+    } else {
+        None
+    };
+    let toc_entries = match args.parse_toc.then(|| toc::find_toc(&pages)).flatten() {
+        // the printed contents page is replaced by the epub's own table of contents
+        Some(range) => pages.drain(range).flat_map(|p| toc::parse_entries(&p.text)).collect(),
+        None => Vec::new(),
+    };
+    let front_matter: Vec<PageText> = match args.skip_front_matter {
+        Some(_) => pages.drain(..frontmatter::front_matter_len(&pages)).collect(),
+        None => Vec::new(),
+    };
+    // cross-references are linked to the pages of the page-list that remain in the text
+    let known_pages: HashSet<String> = if args.page_list {
+        pages.iter().filter_map(PageText::page_name).collect()
+    } else {
+        HashSet::new()
+    };
+    let link_index = args.index == Some(index::IndexMode::Link);
+    let page_anchors = link_index || args.page_list || args.page_anchors;
+    let index_blocks = match args.index {
+        Some(index::IndexMode::Drop) | None => Vec::new(),
+        Some(_) => index::index_blocks(&index_pages, &pages, link_index),
+    };
+    let footnotes = |pages: &[PageText]| -> Vec<notes::Note> {
+        pages.iter().flat_map(|p| p.footnotes.iter().cloned()).collect()
+    };
+    let gathered_footnotes = match args.footnotes {
+        Some(notes::Footnotes::Endnotes) => footnotes(&pages),
+        _ => Vec::new(),
+    };
 
-    // fully emitted text
-    out: String,
+    let mut numbering = args
+        .chapter_numbers
+        .as_ref()
+        .map(|t| titles::ChapterNumbering::new(t, args.chapter_numerals, args.number_all_chapters));
+    let starts = toc::locate_entries(&toc_entries, &pages);
+    let mut chapters: Vec<Chapter> = toc::split_pages(pages, &starts)
+        .into_iter()
+        .map(|(chapter_title, mut pages)| {
+            if let Some(first) = pages.first_mut().filter(|_| args.epigraphs) {
+                epigraphs::mark_epigraph(first);
+            }
+            let confidence = document::mean_confidence(&pages);
+            let chapter_footnotes = footnotes(&pages);
+            let blocks = xref::link_page_refs(page_blocks(pages, page_anchors, args), &known_pages);
+            let mut blocks = match args.footnotes {
+                Some(notes::Footnotes::Inline) => notes::place_notes(blocks, &chapter_footnotes, ParagraphStyle::Note),
+                Some(notes::Footnotes::Popup) => notes::place_notes(blocks, &chapter_footnotes, ParagraphStyle::Footnote),
+                Some(notes::Footnotes::Endnotes) | None => blocks,
+            };
+            let chapter_title = match chapter_title {
+                Some(printed) => {
+                    let t = if args.title_case { titles::title_case(&printed) } else { printed.clone() };
+                    let t = match &mut numbering {
+                        Some(numbering) => numbering.number(&t),
+                        None => t,
+                    };
+                    // the heading in the text changes with the title
+                    if t != printed {
+                        titles::retitle(&mut blocks, &printed, &t);
+                    }
+                    t
+                }
+                None => title.to_string(),
+            };
+            Chapter { title: chapter_title, confidence, blocks }
+        })
+        .collect();
+    if !notes.is_empty() {
+        let linked = notes::link_markers(chapters.iter_mut().flat_map(|c| &mut c.blocks), &notes);
+        chapters.push(Chapter {
+            title: "Notes".to_string(),
+            blocks: notes::notes_blocks(&notes, &linked),
+            confidence: None,
+        });
+    }
+    if !gathered_footnotes.is_empty() {
+        let linked = notes::link_markers(chapters.iter_mut().flat_map(|c| &mut c.blocks), &gathered_footnotes);
+        chapters.push(Chapter {
+            title: "Footnotes".to_string(),
+            blocks: notes::notes_blocks(&gathered_footnotes, &linked),
+            confidence: None,
+        });
+    }
+    if !references.is_empty() {
+        chapters.push(Chapter {
+            title: "References".to_string(),
+            blocks: references::reference_blocks(&references, args.link_references),
+            confidence: None,
+        });
+    }
+    if let Some(page) = copyright_page {
+        chapters.push(Chapter {
+            title: "Copyright".to_string(),
+            confidence: page.confidence,
+            blocks: page_blocks(vec![page], false, args),
+        });
+    }
+    if args.skip_front_matter == Some(frontmatter::FrontMatter::Appendix) && !front_matter.is_empty() {
+        chapters.push(Chapter {
+            title: "Front Matter".to_string(),
+            confidence: document::mean_confidence(&front_matter),
+            blocks: page_blocks(front_matter, false, args),
+        });
+    }
+    if !index_blocks.is_empty() {
+        chapters.push(Chapter {
+            title: "Index".to_string(),
+            confidence: document::mean_confidence(&index_pages),
+            blocks: index_blocks,
+        });
+    }
+    if !annotation_blocks.is_empty() {
+        chapters.push(Chapter {
+            title: "Annotations".to_string(),
+            blocks: annotation_blocks,
+            confidence: None,
+        });
+    }
+    if args.number_figures {
+        figures::number_figures(chapters.iter_mut().flat_map(|c| &mut c.blocks));
+    }
+    if args.list_of_figures {
+        let blocks = figures::list_of_figures(&chapters);
+        if !blocks.is_empty() {
+            chapters.push(Chapter { title: "List of Figures".to_string(), blocks, confidence: None });
+        }
+    }
+    document::dedup_anchors(chapters.iter_mut().flat_map(|c| &mut c.blocks));
+    chapters
+}
 
-    pending_blank: bool,
+/// Chapter recording how the book was produced, for archives
+fn colophon_chapter(args: &Args, pages: &[PageText]) -> Chapter {
+    let confidence = match document::mean_confidence(pages) {
+        Some(c) => format!("{:.1}%", c),
+        None => "n/a".to_string(),
+    };
+    let source = args.input
+        .as_deref()
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let lines = [
+        format!("Converted from {} ({} pages)", source, pages.len()),
+        format!("Converted on {}", chrono::Local::now().format("%Y-%m-%d %H:%M")),
+        match args.text_height {
+            Some(px) => format!("Rendered with body text {} px high", px),
+            None => format!("Rendered at {} DPI", args.dpi),
+        },
+        format!("OCR language: {}", args.ocr_lang),
+        format!("Mean OCR confidence: {}", confidence),
+        format!("Produced by pdf2epub {}", env!("CARGO_PKG_VERSION")),
+    ];
+    Chapter {
+        title: "Colophon".to_string(),
+        blocks: lines.into_iter().map(|l| Block::Paragraph(vec![Inline::Text(l)])).collect(),
+        confidence: None,
+    }
 }
 
-impl LineUnwrapper {
-    pub fn new() -> Self {
-        Self { buf: String::new(), out: String::new(), pending_blank: false }
+/// Look up the book on OpenLibrary, by the given ISBN or the one printed in the book.
+/// A failed lookup is reported but does not abort the conversion.
+fn lookup_metadata(isbn: Option<&str>, pages: &[PageText]) -> metadata::BookMetadata {
+    let isbn = match isbn {
+        Some(isbn) => metadata::normalize_isbn(isbn),
+        None => metadata::find_isbn(pages),
+    };
+    let Some(isbn) = isbn else {
+        warn("no valid ISBN to look up");
+        return metadata::BookMetadata::default();
+    };
+    match metadata::lookup_isbn(&isbn) {
+        Ok(meta) => {
+            eprintln!(
+                "ISBN {}: {} by {} ({}, {})",
+                isbn,
+                meta.title.as_deref().unwrap_or("untitled"),
+                meta.authors.join(", "),
+                meta.publisher.as_deref().unwrap_or("unknown publisher"),
+                meta.published.as_deref().unwrap_or("undated"),
+            );
+            meta
+        }
+        Err(e) => {
+            warn(e);
+            metadata::BookMetadata::default()
+        }
     }
+}
 
-    /// Push one **raw** line (possibly blank, with trailing `\n` removed).
-    pub fn push_line(&mut self, raw: &str) {
-        let line = raw.trim();
+/// Files loaded before the conversion and shared by every book written
+struct Assets {
+    font: Option<style::EmbeddedFont>,
+    dictionary: Option<quality::Dictionary>,
+}
 
-        if line.is_empty() {
-            // postpone decision until we see the next non-blank line
-            self.pending_blank = true;
-            return;
+/// Write the epub and the other requested outputs for `works`, named after `title` and `author`.
+/// The colophon, if any, closes the book.
+fn write_outputs(
+    args: &Args,
+    meta: &metadata::BookMetadata,
+    assets: &Assets,
+    title: &str,
+    author: &str,
+    mut works: Vec<Work>,
+    colophon: Option<&Chapter>,
+) -> Result<(), Pdf2EPubErr> {
+    links::unlink_missing_pages(works.iter_mut().flat_map(|w| &mut w.chapters));
+    let font = assets.font.as_ref();
+    let mut chapters: Vec<&Chapter> = works.iter().flat_map(|w| &w.chapters).collect();
+    let stats = stats::ReadingStats::of(chapters.iter().copied());
+    let quality: Vec<quality::ChapterQuality> = chapters
+        .iter()
+        .map(|c| quality::ChapterQuality::of(c, assets.dictionary.as_ref()))
+        .collect();
+    let colophon = colophon.map(|c| {
+        let mut c = c.clone();
+        if args.reading_stats {
+            c.blocks.extend(stats.lines().into_iter().map(|l| Block::Paragraph(vec![Inline::Text(l)])));
         }
+        c
+    });
+    chapters.extend(&colophon);
 
-        // Decide what that previous blank really meant
-        if self.pending_blank {
-            self.pending_blank = false;
+    let epub2 = args.epub_version == 2;
+    let mut epub = EpubBuilder::new(ZipLibrary::new()?)?;
+    epub.epub_version(if epub2 { EpubVersion::V20 } else { EpubVersion::V30 });
+    epub.metadata("title",  title)?;
+    epub.metadata("author", author)?;
+    epub.set_lang(&args.lang);
+    if let Some(uuid) = args.uuid {
+        epub.set_uuid(uuid);
+    }
+    let description = args.description.clone()
+        .or(meta.description.clone())
+        .or_else(|| metadata::describe(chapters.iter().copied(), args.description_length));
+    if let Some(description) = description {
+        epub.metadata("description", description)?;
+    }
+    let mut subjects: Vec<&String> = Vec::new();
+    for subject in args.subjects.iter().chain(&meta.subjects) {
+        if !subjects.iter().any(|s| s.eq_ignore_ascii_case(subject)) {
+            subjects.push(subject);
+        }
+    }
+    for subject in subjects {
+        epub.metadata("subject", subject)?;
+    }
+    if let Some(date) = meta.publication_date() {
+        epub.set_publication_date(date);
+    }
+    if let Some((cover, mime)) = &meta.cover {
+        epub.add_cover_image("cover.jpg", cover.as_slice(), mime)?;
+    }
+    let (series, series_index) = match &args.series {
+        Some(series) => (Some(series), args.series_index),
+        None => (meta.series.as_ref(), meta.series_index),
+    };
+    if let Some(series) = series {
+        epub.add_metadata_opf(MetadataOpf { name: "calibre:series".to_string(), content: series.clone() });
+        if let Some(index) = series_index {
+            epub.add_metadata_opf(MetadataOpf { name: "calibre:series_index".to_string(), content: index.to_string() });
+        }
+    }
 
-            let prev_ended_sentence = self
-                .buf
-                .chars()
-                .rev()
-                .find(|c| !c.is_whitespace())
-                .map(|c| ".?!".contains(c))
-                .unwrap_or(false);
+    // equations read as MathML need no image
+    for fig in document::figures(chapters.iter().flat_map(|c| &c.blocks)).filter(|f| f.mathml.is_none()) {
+        epub.add_resource(epub_image_path(fig), fig.data.as_slice(), fig.mime)?;
+        if let Some(full) = &fig.full {
+            epub.add_resource(epub_image_path(full), full.data.as_slice(), full.mime)?;
+        }
+    }
 
-            let this_starts_lower = line
-                .chars()
-                .next()
-                .map(|c| c.is_lowercase())
-                .unwrap_or(false);
+    let style_opts = style::StyleOptions {
+        theme: args.theme,
+        font,
+        text_align: args.text_align,
+        hyphenate: args.hyphenate,
+        mark_uncertain: args.mark_uncertain.is_some(),
+        references: args.references,
+        notes: args.endnotes || args.footnotes.is_some(),
+        epigraphs: args.epigraphs,
+    };
+    let css = style::stylesheet(&style_opts, style::EmbeddedFont::epub_path);
+    let css = if epub2 { css.map(|css| epub2::downgrade_css(&css)) } else { css };
+    if let Some(font) = font {
+        epub.add_resource(font.epub_path(), font.data.as_slice(), font.mime)?;
+    }
+    if let Some(css) = &css {
+        epub.stylesheet(css.as_bytes())?;
+    }
+    // several readers stall on large documents, so long chapters go into several files
+    let parts: Vec<Vec<&[Block]>> = chapters.iter().map(|c| split_blocks(&c.blocks, args.max_xhtml_size as usize * 1024)).collect();
+    let ctx = XhtmlContext { anchors: anchor_files(&parts), stylesheet: css.is_some(), lang: args.lang.clone(), epub2 };
+    // the full page scans, each on a page of its own that is left out of the reading order
+    let mut scan_pages = Vec::new();
+    for full in document::figures(chapters.iter().flat_map(|c| &c.blocks)).filter_map(|f| f.full.as_deref()) {
+        let file = scan_page_file(full);
+        epub.add_resource(&file, scan_page_xhtml(full, &ctx).as_bytes(), "application/xhtml+xml")?;
+        scan_pages.push(file);
+    }
 
-            if prev_ended_sentence || !this_starts_lower {
-                // Real paragraph break → flush current paragraph.
-                if !self.buf.is_empty() {
-                    self.out.push_str(self.buf.trim_end());
-                    self.out.push_str("\n\n");
-                    self.buf.clear();
-                }
-            }
-            // else: fake blank (from a page break); keep building same ¶
+    if args.title_page {
+        let publication = [meta.publisher.as_deref(), meta.published.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut lines: Vec<String> = Some(publication).filter(|p| !p.is_empty()).into_iter().collect();
+        if args.reading_stats {
+            lines.push(stats.summary());
         }
+        let xhtml = title_page_xhtml(title, author, &lines, &ctx);
+        epub.add_content(
+            EpubContent::new("title-page.xhtml", xhtml.as_bytes())
+            .title("Title Page")
+            .reftype(ReferenceType::TitlePage),
+        )?;
+    }
 
-        // Join the current line onto the paragraph buffer
-        if !self.buf.is_empty() {
-            if self.buf.ends_with('-') {
-                self.buf.pop();
-            } else {
-                self.buf.push(' ');
+    // several works: each gets a title page, with its chapters nested below it
+    let nested = works.len() > 1;
+    let mut n = 0;
+    for (w, work) in works.iter().enumerate() {
+        if nested {
+            let xhtml = title_page_xhtml(&work.title, &work.author, &[], &ctx);
+            epub.add_content(
+                EpubContent::new(format!("work-{}.xhtml", w + 1), xhtml.as_bytes())
+                .title(&work.title)
+                .level(1),
+            )?;
+        }
+        for chapter in &work.chapters {
+            let xhtml = text_to_xhtml(&chapter.title, parts[n][0], &ctx);
+            let mut content = EpubContent::new(chapter_file(n, 0), xhtml.as_bytes())
+                .title(&chapter.title)
+                .level(if nested { 2 } else { 1 });   // depth in the TOC
+            if n == 0 {
+                content = content.reftype(ReferenceType::Text);
             }
+            epub.add_content(content)?;
+            add_continued_parts(&mut epub, &chapter.title, n, &parts[n], &ctx)?;
+            n += 1;
         }
-        self.buf.push_str(line);
     }
+    if let Some(colophon) = &colophon {
+        let xhtml = text_to_xhtml(&colophon.title, parts[n][0], &ctx);
+        epub.add_content(EpubContent::new(chapter_file(n, 0), xhtml.as_bytes()).title(&colophon.title))?;
+        add_continued_parts(&mut epub, &colophon.title, n, &parts[n], &ctx)?;
+    }
+    // pages dropped from the text (contents, index) have no anchor to point to
+    let page_list: Vec<(String, String)> = works
+        .iter()
+        .flat_map(|w| w.page_names.iter().map(move |name| (name, &w.id_prefix)))
+        .filter_map(|(name, prefix)| {
+            let id = format!("{}{}", prefix, document::label_anchor(name));
+            ctx.anchors.get(&id).map(|file| (name.clone(), format!("{}#{}", file, id)))
+        })
+        .collect();
 
-    /// Consume the unwrapper and return the cleaned text
-    pub fn finish(mut self) -> String {
-        if !self.buf.is_empty() {
-            self.out.push_str(self.buf.trim_end());
+    let outstem = format!("{}-by-{}", title, author);
+    let epub_path = PathBuf::from(format!("{}.epub", outstem));
+    let mut bytes = Vec::new();
+    epub.generate(&mut bytes)?;
+    if let Some(id) = &args.identifier {
+        bytes = package::rewrite_identifier(&bytes, id)?;
+    }
+    if !scan_pages.is_empty() {
+        bytes = package::rewrite_opf(&bytes, |opf| package::add_nonlinear_items(opf, &scan_pages))?;
+    }
+    if args.page_list && epub2 {
+        warn("--page-list needs EPUB 3, the EPUB 2 book has no page list");
+    } else if args.page_list && !page_list.is_empty() {
+        bytes = package::rewrite_nav(&bytes, |nav| package::add_page_list(nav, &page_list))?;
+    }
+    #[cfg(feature = "math")]
+    if !epub2 {
+        let math = package::documents_containing(&bytes, "<math")?;
+        if !math.is_empty() {
+            bytes = package::rewrite_opf(&bytes, |opf| package::add_item_property(opf, &math, "mathml"))?;
+        }
+    }
+    let problems = validation::validate(&bytes)?;
+    if args.strict && !problems.is_empty() {
+        let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        return Err(Pdf2EPubErr::ValidationError(format!("{}: {}", epub_path.display(), problems.join("; "))));
+    }
+    for problem in &problems {
+        warn(format!("{}: {}", epub_path.display(), problem));
+    }
+    match &args.output_dir {
+        Some(dir) => {
+            package::unpack(&bytes, dir)?;
+            println!("wrote {}", dir.display());
         }
-        self.out
+        None => std::fs::write(&epub_path, bytes)?,
+    }
+
+    if args.html {
+        let css = style::stylesheet(&style_opts, |f| html::data_uri(f.mime, &f.data));
+        let page = html::text_to_html(title, author, chapters.iter().copied(), css.as_deref(), &args.lang);
+        std::fs::write(format!("{}.html", outstem), page)?;
+    }
+
+    if args.ssml {
+        let speech = ssml::text_to_ssml(chapters.iter().flat_map(|c| &c.blocks), &args.lang);
+        std::fs::write(format!("{}.ssml", outstem), speech)?;
+    }
+
+    if args.report {
+        let mut issues: Vec<order::ReadingIssue> = works.iter().flat_map(|w| w.reading_issues.iter().cloned()).collect();
+        issues.extend(order::check_chapters(works.iter().flat_map(|w| &w.chapters)));
+        let unbalanced: Vec<balance::Unbalanced> = works.iter().flat_map(|w| w.unbalanced.iter().cloned()).collect();
+        let report = report::book_report(title, author, &stats, &quality, &issues, &unbalanced);
+        report::write_report(Path::new(&format!("{}.report.json", outstem)), &report)?;
     }
+
+    if args.kindle {
+        let kindle_path = kindle::epub_to_kindle(&epub_path, args.kindle_tool.as_deref())?;
+        println!("wrote {}", kindle_path.display());
+    }
+
+    Ok(())
 }
 
-fn text_to_xhtml(title: &str, body: &str) -> String {
-    use html_escape::encode_text;
+/// The pages of the render `img` as OCR reads them: grayscale, with the watermark
+/// blanked, turned upright by `rotate` degrees, with the `excluded` regions blanked,
+/// cropped to `crop`, and split in two when a spread
+fn ocr_pages(
+    img: &RgbImage,
+    watermark: Option<&mask::WatermarkMask>,
+    rotate: u16,
+    excluded: &[mask::Region],
+    crop: Option<&mask::Region>,
+    args: &Args,
+) -> Vec<GrayImage> {
+    // OCR always works on grayscale, whatever the figures were rendered in
+    let mut gray = image::imageops::grayscale(img);
+    if let Some(watermark) = watermark {
+        watermark.apply(&mut gray);
+    }
+    // regions are given on the upright page
+    let mut gray = mask::rotate(gray, rotate);
+    for region in excluded {
+        mask::blank_region(&mut gray, region);
+    }
+    if let Some(crop) = crop {
+        gray = mask::crop_to(&gray, crop);
+    }
+    let split = if args.split_spreads { preprocess::find_spread_split(&gray) } else { None };
+    match split {
+        Some(x) => preprocess::split_at(&gray, x).to_vec(),
+        None => vec![gray],
+    }
+}
 
-    let paras = body
-        .split("\n\n")                 // our “real” paragraph breaks
-        .map(|p| format!("<p>{}</p>", encode_text(p)))
-        .collect::<String>();
+/// Load the pdfium library from `--pdfium-path` (see `just install_pdfium`)
+fn bind_pdfium(args: &Args) -> Result<Pdfium, Pdf2EPubErr> {
+    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&args.pdfium_path))?;
+    Ok(Pdfium::new(bindings))
+}
 
-    format!(
-        r#"<?xml version="1.0" encoding="utf-8"?>
-           <html xmlns="http://www.w3.org/1999/xhtml">
-             <head><title>{}</title></head>
-             <body>{}</body>
-           </html>"#,
-        encode_text(title),
-        paras
-    )
+fn preprocess_options(args: &Args) -> preprocess::PreprocessOptions {
+    preprocess::PreprocessOptions {
+        dewarp: args.dewarp,
+        remove_gutter: args.remove_gutter,
+        despeckle: args.despeckle,
+        speckle_size: args.speckle_size,
+        suppress_bleed_through: args.suppress_bleed_through,
+    }
+}
+
+fn ocr_options(args: &Args) -> ocr::OcrOptions {
+    ocr::OcrOptions {
+        language: args.ocr_lang.clone(),
+        tessdata: args.tessdata.clone(),
+        psm: args.psm,
+        engine_mode: args.oem,
+        user_words: args.user_words.clone(),
+        user_patterns: args.user_patterns.clone(),
+        char_whitelist: args.char_whitelist.clone(),
+        char_blacklist: args.char_blacklist.clone(),
+    }
 }
 
-fn main() -> Result<(), Pdf2EPubErr> {
-    let args = Args::parse();
+/// Fail early, and with its own exit code, when the input does not exist
+fn check_input(path: &Path) -> Result<(), Pdf2EPubErr> {
+    if path.exists() {
+        Ok(())
+    } else {
+        Err(Pdf2EPubErr::InputNotFound(path.to_path_buf()))
+    }
+}
 
-    let pdfium = Pdfium::new(Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./pdfium/lib")).unwrap());
-    let pdf = pdfium.load_pdf_from_file(args.input.to_str().expect("Invalid input path"), None)?;
+/// `pdf2epub bench`: print how long each stage takes on `path`
+fn run_bench(args: &Args, path: &Path, max_pages: Option<usize>) -> Result<(), Pdf2EPubErr> {
+    check_input(path)?;
+    let pdfium = bind_pdfium(args)?;
+    let pdf = pdfium.load_pdf_from_file(path.to_str().expect("Invalid input path"), None)?;
+    let (timings, pages) = bench::bench_pdf(&pdf, max_pages, args.dpi, &ocr_options(args))?;
+    println!("{}: {} pages at {} DPI", path.display(), pages, args.dpi);
+    print!("{}", timings.summary(pages));
+    Ok(())
+}
+
+/// Recognise the first `max_pages` pages of the PDF at `path` with the preprocessing and
+/// OCR flags given, and print their error rates against the text at `reference`
+fn run_diff(args: &Args, path: &Path, reference: &Path, max_pages: Option<usize>) -> Result<(), Pdf2EPubErr> {
+    check_input(path)?;
+    check_input(reference)?;
+    let reference = std::fs::read_to_string(reference)?;
+    let pdfium = bind_pdfium(args)?;
+    let pdf = pdfium.load_pdf_from_file(path.to_str().expect("Invalid input path"), None)?;
+    let (ocr_opts, preprocess_opts) = (ocr_options(args), preprocess_options(args));
+    let progress_bar = indicatif::ProgressBar::new(max_pages.unwrap_or(usize::MAX).min(pdf.pages().len() as usize) as u64);
+    let mut pages: Vec<(usize, String)> = Vec::new();
+    for (index, page) in pdf.pages().iter().take(max_pages.unwrap_or(usize::MAX)).enumerate() {
+        progress_bar.inc(1);
+        let img = img_source_from_page(&page, args.dpi, true)?;
+        let gray = preprocess::preprocess(image::imageops::grayscale(&img), &preprocess_opts);
+        let (text, _) = ocr::OcrInput::Image(DynamicImage::ImageLuma8(gray).into_rgb8()).text(&ocr_opts)?;
+        // transcriptions leave out the page numbers
+        pages.push((index + 1, peel_trailing_page_num(&text).0.to_string()));
+    }
+    progress_bar.finish_and_clear();
+    print!("{}", accuracy::summary(&accuracy::compare(&pages, &reference)));
+    Ok(())
+}
+
+/// The command line interface. Boolean flags also take a value, `--dewarp=false`, to turn
+/// off what the settings file turns on.
+fn cli() -> clap::Command {
+    Args::command().mut_args(|arg| match arg.get_action() {
+        ArgAction::SetTrue => arg
+            .action(ArgAction::Set)
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("true")
+            .default_value("false"),
+        _ => arg,
+    })
+}
+
+fn args_from(matches: &ArgMatches) -> Args {
+    Args::from_arg_matches(matches).unwrap_or_else(|e| e.exit())
+}
+
+/// Whether the flag `--long` is given in `matches` on the command line itself
+fn given_on_command_line(matches: &ArgMatches, long: &str) -> bool {
+    let cli = cli();
+    let id = cli.get_arguments().find(|a| a.get_long() == Some(long)).map(|a| a.get_id().clone());
+    id.is_some_and(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+}
+
+/// Parse the command line, with the settings file and profile in front of it
+/// less the flags given on the command line, which replace them
+fn parse_args() -> Result<(Args, ArgMatches), Pdf2EPubErr> {
+    let matches = cli().get_matches();
+    let args = args_from(&matches);
+    if args.command.is_some() {
+        return Ok((args, matches));
+    }
+    let config = match &args.config {
+        Some(path) => Some(config::load(path)?),
+        None => config::default_path().filter(|p| p.exists()).map(|p| config::load(&p)).transpose()?,
+    };
+    let settings = config::settings_args(config.as_ref(), args.profile.as_deref(), |key| {
+        given_on_command_line(&matches, key)
+    })?;
+    if settings.is_empty() {
+        return Ok((args, matches));
+    }
+    let mut argv: Vec<std::ffi::OsString> = std::env::args_os().take(1).collect();
+    argv.extend(settings.into_iter().map(Into::into));
+    argv.extend(std::env::args_os().skip(1));
+    let matches = cli().get_matches_from(argv);
+    Ok((args_from(&matches), matches))
+}
+
+/// Fill in the language not given (on the command line, in the settings file or the
+/// environment) from the other one: `--lang hi` recognises with the Hindi traineddata,
+/// `--ocr-lang ben` tags the book as Bengali
+fn match_languages(mut args: Args, matches: &ArgMatches) -> Args {
+    let given = |id: &str| matches.value_source(id) != Some(ValueSource::DefaultValue);
+    if !given("ocr_lang") {
+        if let Some(code) = ocr::tesseract_language(&args.lang) {
+            args.ocr_lang = code.to_string();
+        }
+    } else if !given("lang") {
+        let primary = args.ocr_lang.split('+').next().unwrap_or_default();
+        if let Some(tag) = ocr::language_tag(primary) {
+            args.lang = tag.to_string();
+        }
+    }
+    args
+}
+
+/// `pdf2epub init`: preview the OCR of a sample page and write the settings chosen
+fn run_init(args: &Args, sample: &Path, page: Option<usize>, output: Option<&Path>, force: bool) -> Result<(), Pdf2EPubErr> {
+    let path = output
+        .map(Path::to_path_buf)
+        .or_else(config::default_path)
+        .ok_or_else(|| Pdf2EPubErr::ConfigError("no home directory; use --output".to_string()))?;
+    if path.exists() && !force {
+        return Err(Pdf2EPubErr::ConfigError(format!("{} exists; use --force to replace it", path.display())));
+    }
+    check_input(sample)?;
+    let pdfium = bind_pdfium(args)?;
+    let pdf = pdfium.load_pdf_from_file(sample.to_str().expect("Invalid input path"), None)?;
+    let count = pdf.pages().len() as usize;
+    let index = match page {
+        Some(n) if (1..=count).contains(&n) => n - 1,
+        Some(n) => return Err(Pdf2EPubErr::ConfigError(format!("no page {} in a {} page PDF", n, count))),
+        None => count / 2,
+    };
+    let preview = wizard::preview(&pdf.pages().get(index as u16)?, args.dpi, &ocr_options(args))?;
+    let settings = wizard::interview(std::io::stdin().lock(), std::io::stdout(), &preview, &args.ocr_lang)?;
+    wizard::write_settings(&path, &settings)?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = parse_args().map(|(args, matches)| match_languages(args, &matches)).and_then(|args| run(&args));
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Run the subcommand or conversion; returns the exit code of a finished run
+fn run(args: &Args) -> Result<u8, Pdf2EPubErr> {
+    match &args.command {
+        Some(Command::Bench { pdf, pages }) => {
+            run_bench(args, pdf, *pages)?;
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Diff { pdf, reference, pages }) => {
+            run_diff(args, pdf, reference, *pages)?;
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Init { sample, page, output, force }) => {
+            run_init(args, sample, *page, output.as_deref(), *force)?;
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Thumbs { pdf, output, width, pages }) => {
+            check_input(pdf)?;
+            let pdfium = bind_pdfium(args)?;
+            let document = pdfium.load_pdf_from_file(pdf.to_str().expect("Invalid input path"), None)?;
+            let count = thumbs::render_thumbnails(&document, output, *width, *pages)?;
+            println!("wrote {} thumbnails to {}", count, output.display());
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut cli(), env!("CARGO_PKG_NAME"), &mut std::io::stdout());
+            return Ok(exit_code::SUCCESS);
+        }
+        None => {}
+    }
+    let input = args.input.as_deref().expect("--input is required");
+
+    let metrics = match args.metrics_addr {
+        Some(addr) => {
+            let metrics = Arc::new(metrics::Metrics::default());
+            metrics::serve(metrics.clone(), addr)?;
+            Some(metrics)
+        }
+        None => None,
+    };
+    let mut timings = match &metrics {
+        Some(metrics) => timings::Timings::with_metrics(metrics.clone()),
+        None => timings::Timings::default(),
+    };
+
+    let result = convert(args, input, &mut timings, metrics.as_deref());
+    if let Some(metrics) = &metrics {
+        metrics.conversion_done(result.is_ok());
+        std::thread::sleep(std::time::Duration::from_secs(args.metrics_linger));
+    }
+    let outcome = result?;
+    if args.timings {
+        eprint!("{}", timings.summary(outcome.pages));
+    }
+    if !outcome.failed_pages.is_empty() {
+        let failed: Vec<String> = outcome.failed_pages.iter().map(usize::to_string).collect();
+        eprintln!("{} of {} pages could not be recognised: {}", failed.len(), outcome.pages, failed.join(", "));
+        Ok(exit_code::PARTIAL_CONVERSION)
+    } else if pdf2epub::warning_count() > 0 {
+        Ok(exit_code::SUCCESS_WITH_WARNINGS)
+    } else {
+        Ok(exit_code::SUCCESS)
+    }
+}
+
+/// What a finished conversion got through
+struct Outcome {
+    /// Pages of the PDF
+    pages: usize,
+
+    /// 1-based PDF pages left out because OCR failed on them
+    failed_pages: Vec<usize>,
+}
+
+/// Convert `input` as the options say
+fn convert(
+    args: &Args,
+    input: &Path,
+    timings: &mut timings::Timings,
+    metrics: Option<&metrics::Metrics>,
+) -> Result<Outcome, Pdf2EPubErr> {
+    check_input(input)?;
+    // read the manifest first, so mistakes in it show up before the long OCR run
+    let specs = args.anthology.as_deref().map(anthology::read_manifest).transpose()?;
+    let opf_meta = args.metadata_opf.as_deref().map(metadata::read_opf).transpose()?;
+    let layouts = args.layout.as_deref().map(pagelayout::read_layout).transpose()?.unwrap_or_default();
+    let rules = args.rules.as_deref().map(rules::Rules::load).transpose()?.unwrap_or_default();
+    let dictionary = match &args.dictionary {
+        Some(path) => Some(quality::Dictionary::load(path)?),
+        None if args.report || args.drop_junk_lines => {
+            quality::Dictionary::load(Path::new(quality::SYSTEM_DICTIONARY)).ok()
+        }
+        None => None,
+    };
+    let assets = Assets {
+        font: args.embed_font.as_deref().map(style::EmbeddedFont::load).transpose()?,
+        dictionary,
+    };
+
+    if let Some(dir) = &args.debug_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let pdfium = bind_pdfium(args)?;
+    let pdf = pdfium.load_pdf_from_file(input.to_str().expect("Invalid input path"), None)?;
     let progress_bar = indicatif::ProgressBar::new(pdf.pages().len() as u64);
-    let mut cleaner = LineUnwrapper::new();
+    let mut pages: Vec<PageText> = Vec::new();
+    let mut failed_pages: Vec<usize> = Vec::new();
+    let mut review: Vec<report::ReviewPage> = Vec::new();
+    let mut dropped: Vec<junk::DroppedLine> = Vec::new();
+    let mut records: Vec<manifest::PageRecord> = Vec::new();
+    let mut previous_hash: Option<duplicates::PageHash> = None;
+    let ocr_opts = ocr_options(args);
+    let junk_filter = args.drop_junk_lines.then(|| junk::JunkFilter::new(assets.dictionary.as_ref()));
+    let preprocess_opts = preprocess_options(args);
+    let retry = retry::RetryPolicy {
+        retries: args.retries,
+        dpi_steps: args.retry_dpi.clone(),
+        psm_modes: args.retry_psm.clone(),
+        backoff: std::time::Duration::from_millis(args.retry_backoff),
+        below: args.retry_below,
+    };
+    let image_opts = figures::ImageOptions {
+        max_width: args.max_image_width,
+        format: args.image_format,
+        quality: args.image_quality,
+    };
+    // MathML needs EPUB 3; an EPUB 2 book keeps its equations as they are
+    #[cfg(feature = "math")]
+    let math_cmd = args.math_ocr_cmd.as_deref().filter(|_| args.epub_version == 3);
+    #[cfg(feature = "math")]
+    if args.math_ocr_cmd.is_some() && math_cmd.is_none() {
+        warn("--math-ocr-cmd needs EPUB 3, the equations are left as they are");
+    }
+    // with --low-memory the pages go to Tesseract through files rather than in memory
+    let spool = args.low_memory.then(ocr::SpoolDir::create).transpose()?;
+    let mut page_cache = args.cache.as_deref().map(cache::PageCache::load);
+    // the options hold the paths of the word lists, not what is in them
+    let user_files = match page_cache {
+        Some(_) => cache::files_hash(args.user_words.iter().chain(&args.user_patterns).map(PathBuf::as_path))?,
+        None => String::new(),
+    };
+    let watermark = if args.remove_watermarks {
+        mask::detect_watermark(&pdf)?
+    } else {
+        None
+    };
 
-    for (_index, page) in pdf.pages().iter().enumerate() {
+    for (index, page) in pdf.pages().iter().enumerate() {
         progress_bar.inc(1);
-        let img = img_source_from_page(&page, 300)?;
-        let raw_text = ocr_rgb_png(&img)?;
+        let boundaries = pagelayout::column_boundaries(&layouts, index + 1, args.columns);
+        // the regions of `--exclude-region` and the layout file for the page
+        let excluded: Vec<mask::Region> = args
+            .exclude_regions
+            .iter()
+            .filter(|r| r.applies_to(index + 1))
+            .map(|r| r.region)
+            .chain(pagelayout::excluded_regions(&layouts, index + 1).copied())
+            .collect();
+        let crop = pagelayout::crop_box(&layouts, index + 1);
+        let rotate = pagelayout::rotation(&layouts, &args.rotate, index + 1);
+        let ocr_opts = match scripts::page_languages(&args.page_lang, index + 1) {
+            Some(language) => ocr::OcrOptions { language: language.to_string(), ..ocr_opts.clone() },
+            None => ocr_opts.clone(),
+        };
+        let dpi = match args.text_height {
+            Some(px) => page_dpi(&page, px, args.split_spreads && page.width().value > page.height().value),
+            None => args.dpi,
+        };
+        let mut img = timings.time("render", || retry.run(|_| img_source_from_page(&page, dpi, !args.color)))?;
+        if let Some(dir) = &args.debug_dir {
+            timings.time("debug", || debug::save(&img, &debug::render_path(dir, index + 1)))?;
+        }
+        if let Some(action) = args.duplicate_pages {
+            let hash = timings.time("duplicates", || duplicates::PageHash::of(&img));
+            let repeated = matches!((&hash, &previous_hash), (Some(h), Some(p)) if h.is_duplicate_of(p));
+            previous_hash = hash;
+            if repeated {
+                warn(format!("page {} repeats page {}", index + 1, index));
+                if action == duplicates::Duplicates::Drop {
+                    continue;
+                }
+            }
+        }
+        let figures = timings.time("figures", || -> Result<Vec<Figure>, Pdf2EPubErr> {
+            let mut figures = if args.extract_images {
+                figures::extract_figures(&page, index, &mut img, dpi, &ocr_opts)?
+            } else {
+                Vec::new()
+            };
+            if args.extract_vector_graphics {
+                figures.extend(vector::extract_vector_figures(&page, index, &mut img, dpi, &ocr_opts)?);
+            }
+            #[cfg(feature = "math")]
+            if let Some(cmd) = math_cmd {
+                figures.extend(math::extract_equations(&page, index, &mut img, dpi, cmd)?);
+            }
+            let figures = figures
+                .into_iter()
+                .map(|f| figures::recompress(f, &image_opts))
+                .collect::<Result<Vec<_>, _>>()?;
+            #[cfg(feature = "captioning")]
+            let figures = captioning::describe_uncaptioned(args.alt_text_cmd.as_deref(), figures)?;
+            Ok(figures)
+        })?;
+        let logical_pages = timings.time("preprocess", || ocr_pages(&img, watermark.as_ref(), rotate, &excluded, crop.as_ref(), args));
+        // a page tried again at another resolution is rendered again, its figures masked
+        // as in the first render
+        let rerender = |dpi: u16, part: Option<usize>| -> Result<GrayImage, Pdf2EPubErr> {
+            let mut img = img_source_from_page(&page, dpi, !args.color)?;
+            if args.extract_images {
+                figures::extract_figures(&page, index, &mut img, dpi, &ocr_opts)?;
+            }
+            if args.extract_vector_graphics {
+                vector::extract_vector_figures(&page, index, &mut img, dpi, &ocr_opts)?;
+            }
+            #[cfg(feature = "math")]
+            if math_cmd.is_some() {
+                for (region, _) in math::equation_regions(&page, dpi) {
+                    figures::mask_region(&mut img, &region);
+                }
+            }
+            let mut halves = ocr_pages(&img, watermark.as_ref(), rotate, &excluded, crop.as_ref(), args);
+            let part = part.unwrap_or(0).min(halves.len() - 1);
+            Ok(halves.swap_remove(part))
+        };
 
-        let (text, _pagenum_opt) = if args.extract_pagenum {
-            peel_trailing_page_num(&raw_text)
-        } else {
-            (raw_text.as_str(), None)
+        // only the logical pages are needed from here on
+        drop(img);
+        let halves = logical_pages.len() > 1;
+        // the links and annotations go with the first half of a spread
+        let mut page_links = Some(links::page_links(&page)?);
+        let mut page_annotations = match args.annotations {
+            Some(_) => Some(annotations::page_annotations(&page)?),
+            None => None,
         };
+        for (part, logical_page) in logical_pages.into_iter().enumerate() {
+            let part = halves.then_some(part);
+            let review_page = |number, confidence, images| report::ReviewPage {
+                page: index + 1,
+                part,
+                number,
+                confidence,
+                images,
+            };
+            let stem = match part {
+                Some(part) => format!("page-{}-{}-scan", index + 1, part + 1),
+                None => format!("page-{}-scan", index + 1),
+            };
+            // the page as a reader would see it, should its text be unusable; rendered again
+            // rather than kept for every page, as few pages end up replaced
+            let page_image = |caption: String| -> Result<Figure, Pdf2EPubErr> {
+                let fig = figures::page_fallback(rerender(dpi, part)?, &stem, index + 1, caption)?;
+                let full = fig.full.map(|full| figures::recompress(*full, &image_opts)).transpose()?;
+                Ok(Figure { full: full.map(Box::new), ..fig })
+            };
+            let prepare = |gray: GrayImage, attempt: u32| -> Result<ocr::OcrInput, Pdf2EPubErr> {
+                let gray = preprocess::stack_columns(preprocess::preprocess(gray, &preprocess_opts), &boundaries);
+                match &spool {
+                    Some(dir) => ocr::OcrInput::spool(&gray, dir.page_path(index + 1, part, attempt)),
+                    None => Ok(ocr::OcrInput::Image(DynamicImage::ImageLuma8(gray).into_rgb8())),
+                }
+            };
+            let first = timings.time("preprocess", || prepare(logical_page, 0))?;
+            // words are only needed to strip line numbers, mark uncertain ones or scripts,
+            // find furigana, or measure indents and line confidences
+            let words = args.strip_line_numbers
+                || args.mark_uncertain.is_some()
+                || args.superscripts
+                || args.ruby
+                || args.indented_paragraphs
+                || args.short_line_breaks
+                || args.min_line_confidence.is_some();
+            let details = ocr::WordDetails { alternatives: args.uncertain_alternatives, scripts: args.superscripts };
+            let read = |input: &ocr::OcrInput, ocr_opts: &ocr::OcrOptions| -> Result<PageOcr, Pdf2EPubErr> {
+                if words {
+                    Ok(PageOcr::Lines(input.lines(ocr_opts, details)?))
+                } else {
+                    let (text, confidence) = input.text(ocr_opts)?;
+                    Ok(PageOcr::Text(text, confidence))
+                }
+            };
+            let clean = |ocr: PageOcr| match ocr {
+                PageOcr::Lines(mut lines) => {
+                    if args.strip_line_numbers {
+                        linenum::strip_line_numbers(&mut lines);
+                    }
+                    let mut dropped = Vec::new();
+                    if let Some(filter) = &junk_filter {
+                        dropped = filter.filter_lines(&mut lines);
+                    }
+                    if let Some(min) = args.min_line_confidence {
+                        dropped.extend(junk::drop_unsure_lines(&mut lines, min));
+                    }
+                    let ruby = if args.ruby { ruby::split_readings(&mut lines) } else { Vec::new() };
+                    (ocr::lines_to_text(&lines), ocr::mean_confidence(&lines), lines, ruby, dropped)
+                }
+                PageOcr::Text(text, confidence) => {
+                    let (text, dropped) = match &junk_filter {
+                        Some(filter) => filter.filter_text(&text),
+                        None => (text, Vec::new()),
+                    };
+                    (text, confidence, Vec::new(), Vec::new(), dropped)
+                }
+            };
+            // the page as the last run read it, when neither the page nor the settings changed
+            let hash = cache::page_hash(&first)?;
+            let key = page_cache
+                .is_some()
+                .then(|| cache::page_key(&hash, &format!("{:?} {} {:?} {} {:?}", ocr_opts, user_files, details, words, retry)));
+            let cached = page_cache.as_mut().zip(key.as_deref()).and_then(|(c, key)| c.get(key));
+            let from_cache = cached.is_some();
+            // the input of the try kept, when it rendered the page at another resolution
+            let mut retried: Option<ocr::OcrInput> = None;
+            let mut kept_dpi = dpi;
+            let mut recognised = match cached {
+                Some(cached) => {
+                    // a page last kept from a retry at another resolution is rendered at
+                    // that one again, for its word boxes to fit the image
+                    if cached.dpi != dpi {
+                        retried = Some(prepare(timings.time("render", || rerender(cached.dpi, part))?, 1)?);
+                        kept_dpi = cached.dpi;
+                    }
+                    Ok(cached.ocr)
+                }
+                None => timings.time("ocr", || read(&first, &ocr_opts)),
+            };
+            let mut attempt = 0;
+            while !from_cache
+                && retry.can_retry(attempt)
+                && match &recognised {
+                    Ok(r) => retry.unsure(r.confidence()),
+                    Err(Pdf2EPubErr::OcrEngineError(_)) => false,
+                    Err(_) => true,
+                }
+            {
+                attempt += 1;
+                retry.wait(attempt);
+                let opts = ocr::OcrOptions { psm: retry.psm(attempt, ocr_opts.psm), ..ocr_opts.clone() };
+                let retry_dpi = retry.dpi(attempt, dpi);
+                let again = (|| -> Result<_, Pdf2EPubErr> {
+                    let input = if retry_dpi == dpi {
+                        None
+                    } else {
+                        Some(prepare(timings.time("render", || rerender(retry_dpi, part))?, attempt)?)
+                    };
+                    let r = timings.time("ocr", || read(input.as_ref().unwrap_or(&first), &opts))?;
+                    Ok((r, input))
+                })();
+                match again {
+                    Ok((r, input)) if recognised.as_ref().map_or(true, |best| r.confidence() > best.confidence()) => {
+                        recognised = Ok(r);
+                        retried = input;
+                        kept_dpi = retry_dpi;
+                    }
+                    Ok(_) => {}
+                    Err(e @ Pdf2EPubErr::OcrEngineError(_)) => return Err(e),
+                    Err(e) => {
+                        if recognised.is_err() {
+                            recognised = Err(e);
+                        }
+                    }
+                }
+            }
+            if let (Some(c), Some(key), Ok(ocr)) = (page_cache.as_mut(), key, &recognised) {
+                c.insert(key, ocr.clone(), kept_dpi)?;
+            }
+            records.push(manifest::PageRecord {
+                page: index + 1,
+                part,
+                hash,
+                dpi: kept_dpi,
+                language: ocr_opts.language.clone(),
+                confidence: recognised.as_ref().ok().and_then(PageOcr::confidence),
+                tries: attempt + 1,
+                cached: from_cache,
+                failed: recognised.is_err(),
+            });
+            let input = retried.as_ref().unwrap_or(&first);
+            let (raw_text, confidence, lines, ruby, page_dropped) = match recognised {
+                Ok(ocr) => clean(ocr),
+                // without an engine no page can be recognised
+                Err(e @ Pdf2EPubErr::OcrEngineError(_)) => return Err(e),
+                Err(e) => {
+                    warn(format!("page {}: {}", index + 1, e));
+                    if failed_pages.last() != Some(&(index + 1)) {
+                        failed_pages.push(index + 1);
+                    }
+                    let render = args.debug_dir.iter().map(|d| debug::render_path(d, index + 1)).collect();
+                    review.push(review_page(None, None, render));
+                    if args.page_images {
+                        let label = if halves { None } else { pagelabels::page_label(&page) };
+                        pages.push(PageText {
+                            page: index + 1,
+                            number: pagelabels::reconcile(index + 1, None, label.as_deref()),
+                            label,
+                            figures: vec![page_image(format!("Page {} could not be recognised", index + 1))?],
+                            links: page_links.take().unwrap_or_default(),
+                            annotations: page_annotations.take().unwrap_or_default(),
+                            ..Default::default()
+                        });
+                    }
+                    continue;
+                }
+            };
+            dropped.extend(page_dropped.into_iter().map(|(confidence, text)| junk::DroppedLine {
+                page: index + 1,
+                confidence,
+                text,
+            }));
+            let mut images = Vec::new();
+            if let Some(dir) = &args.debug_dir {
+                let path = debug::confidence_path(dir, index + 1, part);
+                timings.time("debug", || -> Result<(), Pdf2EPubErr> {
+                    // a page read as plain text has its words boxed by a separate pass, so
+                    // that debugging does not change how pages are recognised
+                    let boxed;
+                    let boxes = if words {
+                        &lines
+                    } else {
+                        boxed = input.lines(&ocr_opts, ocr::WordDetails::default())?;
+                        &boxed
+                    };
+                    let page = input.to_rgb()?;
+                    debug::save(&debug::confidence_overlay(&page, boxes), &path)
+                })?;
+                images = vec![debug::render_path(dir, index + 1), path];
+            }
 
-        for line in text.lines() {
-            cleaner.push_line(line);
+            let (text, number) = if args.extract_pagenum {
+                peel_trailing_page_num(&raw_text)
+            } else {
+                (raw_text.as_str(), None)
+            };
+            // the halves of a spread share the PDF page and with it its label
+            let label = if halves { None } else { pagelabels::page_label(&page) };
+            let number = pagelabels::reconcile(index + 1, number, label.as_deref());
+
+            if confidence.is_some_and(|c| c < args.review_threshold) {
+                review.push(review_page(number, confidence, images));
+            }
+            let scan = match (args.page_images_below, confidence) {
+                (Some(threshold), Some(c)) if c < threshold => {
+                    Some(page_image(format!("Page {} could not be recognised reliably ({:.0}% confidence)", index + 1, c))?)
+                }
+                _ => None,
+            };
+            let text = if scan.is_some() { "" } else { text };
+            let text = rules.apply(text);
+            let (text, footnotes) = match args.footnotes {
+                Some(_) => notes::split_footnotes(&text, index + 1, part),
+                None => (text, Vec::new()),
+            };
+            pages.push(PageText {
+                page: index + 1,
+                number,
+                label,
+                text,
+                confidence,
+                figures: scan.into_iter().collect(),
+                footnotes,
+                uncertain: args.mark_uncertain.map(|t| ocr::uncertain_words(&lines, t)).unwrap_or_default(),
+                scripted: if args.superscripts { ocr::scripted_words(&lines) } else { Vec::new() },
+                ruby,
+                layout: if args.indented_paragraphs || args.short_line_breaks {
+                    layout::line_layout(&lines)
+                } else {
+                    Vec::new()
+                },
+                links: page_links.take().unwrap_or_default(),
+                annotations: page_annotations.take().unwrap_or_default(),
+                ..Default::default()
+            });
+        }
+        // figures follow the page's text (the right half of a spread)
+        if let Some(last) = pages.last_mut() {
+            last.figures.extend(figures);
+        }
+        if let Some(metrics) = metrics {
+            metrics.page_done();
         }
     }
     progress_bar.finish();
-    let final_text = cleaner.finish();
+    links::mark_targets(&mut pages);
+    if let Some(path) = &args.review_list {
+        report::write_review(path, &mut review, args.review_threshold)?;
+    }
+    if let Some(c) = &page_cache {
+        c.save()?;
+        eprintln!("reused the OCR of {} pages from {}", c.hits, args.cache.as_ref().expect("cache given").display());
+    }
+    if let Some(path) = &args.dropped_lines {
+        std::fs::write(path, junk::dropped_text(&dropped))?;
+    } else if !dropped.is_empty() {
+        warn(format!("dropped {} lines as noise; list them with --dropped-lines", dropped.len()));
+    }
 
-    let title = args.title.unwrap_or("ebook-output".to_string());
-    let author = args.author.unwrap_or("unknown author".to_string());
+    let meta = match &args.isbn {
+        Some(isbn) => timings.time("metadata", || lookup_metadata(isbn.as_deref(), &pages)),
+        None => metadata::BookMetadata::default(),
+    };
+    let meta = match opf_meta {
+        Some(opf) => opf.or(meta),
+        None => meta,
+    };
+    let meta = match &args.meta_json {
+        Some(json) => json.clone().or(meta),
+        None => meta,
+    };
+    let title = args.title.clone().or(meta.title.clone()).unwrap_or("ebook-output".to_string());
+    let author = args.author.clone()
+        .or((!meta.authors.is_empty()).then(|| meta.authors.join(", ")))
+        .unwrap_or("unknown author".to_string());
 
-    let mut epub = EpubBuilder::new(ZipLibrary::new()?)?;
-    epub.metadata("title",  &title)?;
-    epub.metadata("author", &author)?;
-    epub.set_lang("en");
-
-    let xhtml = text_to_xhtml(&title, &final_text);
-    epub.add_content(
-        EpubContent::new("FILENAME".to_string(), xhtml.as_bytes())
-        .title(&title)
-        .level(1)              // depth in the TOC
-        .reftype(ReferenceType::Text),
-    )?;
-
-    let outfile = format!("{}-by-{}.epub", title, author);
-    let mut out = std::fs::File::create(outfile)?;
-    epub.generate(&mut out)?;
+    let colophon = args.colophon.then(|| colophon_chapter(args, &pages));
 
-    Ok(())
+    let page_count = pdf.pages().len() as usize;
+    let mut works: Vec<Work> = timings.time("clean", || match specs {
+        None => vec![Work {
+            reading_issues: order::check_pages(&pages),
+            unbalanced: balance::check_pages(&pages),
+            page_names: pages.iter().filter_map(PageText::page_name).collect(),
+            id_prefix: String::new(),
+            chapters: build_chapters(pages, &title, args),
+            title: title.clone(),
+            author: author.clone(),
+        }],
+        Some(specs) => {
+            let mut work_pages: Vec<Vec<PageText>> = vec![Vec::new(); specs.len()];
+            for page in pages {
+                if let Some(w) = specs.iter().position(|s| s.pages.contains(&page.page)) {
+                    work_pages[w].push(page);
+                }
+            }
+            specs
+                .into_iter()
+                .zip(work_pages)
+                .enumerate()
+                .map(|(w, (spec, pages))| {
+                    let reading_issues = order::check_pages(&pages);
+                    let unbalanced = balance::check_pages(&pages);
+                    let page_names = pages.iter().filter_map(PageText::page_name).collect();
+                    // the works share the epub, so each gets ids of its own
+                    let mut chapters = build_chapters(pages, &spec.title, args);
+                    let id_prefix = format!("work-{}-", w + 1);
+                    document::namespace_ids(&mut chapters, &id_prefix);
+                    Work {
+                        reading_issues,
+                        unbalanced,
+                        page_names,
+                        id_prefix,
+                        chapters,
+                        author: spec.author.unwrap_or_else(|| author.clone()),
+                        title: spec.title,
+                    }
+                })
+                .collect()
+        }
+    });
+
+    timings.time("post-process", || -> Result<(), Pdf2EPubErr> {
+        let mut glossary = if args.protect_names {
+            let user_words = match &args.user_words {
+                Some(path) => std::fs::read_to_string(path)?.lines().map(str::to_string).collect(),
+                None => Vec::new(),
+            };
+            Some(glossary::Glossary::build(works.iter().flat_map(|w| &w.chapters), user_words))
+        } else {
+            None
+        };
+        let script_runs = args.script_runs.then(|| {
+            let languages = std::iter::once(args.ocr_lang.as_str()).chain(args.page_lang.iter().map(|p| p.languages.as_str()));
+            scripts::ScriptRuns::new(&args.lang, languages)
+        });
+        if let Some(runs) = &script_runs {
+            glossary = Some(glossary.unwrap_or_default().keep_other_scripts(runs.main()));
+        }
+        hooks::post_process(&args.post_process, works.iter_mut().flat_map(|w| &mut w.chapters), glossary.as_ref())?;
+        // tagged after the commands ran, so the paragraphs they changed are tagged too
+        if let Some(runs) = &script_runs {
+            for chapter in works.iter_mut().flat_map(|w| &mut w.chapters) {
+                chapter.blocks = runs.mark_blocks(std::mem::take(&mut chapter.blocks));
+            }
+        }
+        Ok(())
+    })?;
+
+    let works_json = manifest::works_json(&works);
+    // the book's author defaults to the authors of its works
+    let book_author = args.author.clone().unwrap_or_else(|| {
+        let mut authors: Vec<&str> = Vec::new();
+        for w in &works {
+            if !authors.contains(&w.author.as_str()) {
+                authors.push(&w.author);
+            }
+        }
+        authors.join(", ")
+    });
+    timings.time("epub", || -> Result<(), Pdf2EPubErr> {
+        if args.split_works {
+            for work in works {
+                // every epub written gets the colophon at its end
+                let (title, author) = (work.title.clone(), work.author.clone());
+                write_outputs(args, &meta, &assets, &title, &author, vec![work], colophon.as_ref())?;
+            }
+            Ok(())
+        } else {
+            write_outputs(args, &meta, &assets, &title, &book_author, works, colophon.as_ref())
+        }
+    })?;
+    let conversion = manifest::manifest(input, &ocr_opts, &preprocess_opts, &records, works_json, timings);
+    manifest::write_manifest(Path::new(&format!("{}-by-{}.pdf2epub.json", title, book_author)), &conversion)?;
+    Ok(Outcome { pages: page_count, failed_pages })
 }
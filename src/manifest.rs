@@ -0,0 +1,119 @@
+//! The conversion manifest, `<book>.pdf2epub.json`, written next to the epub: what was
+//! converted and how, page by page (a hash of each page image, its resolution, language
+//! and confidence), the chapters found and the time each stage took. It is meant for
+//! audits of a conversion and for tools that compare or resume conversions.
+use std::path::Path;
+use anyhow::Context;
+use serde_json::{json, Value};
+use crate::document::Work;
+use crate::ocr::OcrOptions;
+use crate::preprocess::PreprocessOptions;
+use crate::timings::Timings;
+use crate::Pdf2EPubErr;
+
+/// How one logical page was converted
+#[derive(Debug, Clone)]
+pub struct PageRecord {
+    /// 1-based PDF page
+    pub page: usize,
+
+    /// Half of a split spread (0 for the left one)
+    pub part: Option<usize>,
+
+    /// Hash of the preprocessed image handed to Tesseract
+    pub hash: String,
+
+    /// Resolution the page was rendered at
+    pub dpi: u16,
+
+    /// Tesseract language(s) the page was recognised with
+    pub language: String,
+
+    /// Mean OCR confidence (0-100), `None` when no text was found or OCR failed
+    pub confidence: Option<f32>,
+
+    /// Tries it took to recognise the page
+    pub tries: u32,
+
+    /// Whether the page's OCR was taken from `--cache`
+    pub cached: bool,
+
+    /// Whether OCR failed on the page
+    pub failed: bool,
+}
+
+/// The works of a conversion and their chapters, for the manifest; taken before the
+/// epub is written, which consumes them
+pub fn works_json(works: &[Work]) -> Value {
+    works
+        .iter()
+        .map(|w| {
+            json!({
+                "title": w.title,
+                "author": w.author,
+                "chapters": w.chapters.iter().map(|c| json!({
+                    "title": c.title,
+                    "confidence": c.confidence,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// The manifest of the conversion of `input` into `works` (see `works_json`)
+pub fn manifest(
+    input: &Path,
+    ocr: &OcrOptions,
+    preprocess: &PreprocessOptions,
+    pages: &[PageRecord],
+    works: Value,
+    timings: &Timings,
+) -> Value {
+    let pages: Vec<Value> = pages
+        .iter()
+        .map(|p| {
+            json!({
+                "page": p.page,
+                "part": p.part,
+                "hash": p.hash,
+                "dpi": p.dpi,
+                "language": p.language,
+                "confidence": p.confidence,
+                "tries": p.tries,
+                "cached": p.cached,
+                "failed": p.failed,
+            })
+        })
+        .collect();
+    json!({
+        "generator": format!("pdf2epub {}", env!("CARGO_PKG_VERSION")),
+        "input": input.display().to_string(),
+        "ocr": {
+            "language": ocr.language,
+            "psm": ocr.psm,
+            "engine_mode": format!("{:?}", ocr.engine_mode).to_lowercase(),
+            "char_whitelist": ocr.char_whitelist,
+            "char_blacklist": ocr.char_blacklist,
+        },
+        "preprocess": {
+            "dewarp": preprocess.dewarp,
+            "remove_gutter": preprocess.remove_gutter,
+            "despeckle": preprocess.despeckle.map(|d| format!("{:?}", d).to_lowercase()),
+            "speckle_size": preprocess.speckle_size,
+            "suppress_bleed_through": preprocess.suppress_bleed_through,
+        },
+        "pages": pages,
+        "works": works,
+        "timings": timings.stages().iter().map(|(stage, d)| json!({
+            "stage": stage,
+            "seconds": d.as_secs_f64(),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Write `manifest` to `path`
+pub fn write_manifest(path: &Path, manifest: &Value) -> Result<(), Pdf2EPubErr> {
+    let text = serde_json::to_string_pretty(manifest).context("failed to serialize manifest")?;
+    std::fs::write(path, text)?;
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use image::{GrayImage, Luma};
+use image::imageops::{self, FilterType};
+use pdfium_render::prelude::*;
+use crate::pagelayout::parse_page_range;
+use crate::{img_source_from_page, Pdf2EPubErr};
+
+/// Rectangle in page fractions (0.0-1.0, origin top left), so it applies to
+/// pages of any size and rendering resolution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parse `x,y,w,h`, e.g. `0.7,0.85,0.3,0.15` for a stamp in the bottom right corner
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|p| p.trim().parse::<f32>().map_err(|e| format!("{}: {}", p.trim(), e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, w, h] = parts[..] else {
+            return Err("expected x,y,w,h".to_string());
+        };
+        if [x, y, w, h].iter().any(|v| !(0.0..=1.0).contains(v)) || x + w > 1.0 || y + h > 1.0 {
+            return Err("region must lie within the page (fractions between 0 and 1)".to_string());
+        }
+        Ok(Region { x, y, w, h })
+    }
+}
+
+/// A region left out of OCR on some pages, or on all of them, see `--exclude-region`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRegion {
+    /// PDF pages (1-based, inclusive), all of them when `None`
+    pub pages: Option<RangeInclusive<usize>>,
+
+    pub region: Region,
+}
+
+impl PageRegion {
+    pub fn applies_to(&self, page: usize) -> bool {
+        self.pages.as_ref().is_none_or(|pages| pages.contains(&page))
+    }
+}
+
+impl FromStr for PageRegion {
+    type Err = String;
+
+    /// Parse `x,y,w,h` for every page, or `FIRST-LAST:x,y,w,h` (or `PAGE:x,y,w,h`) for some
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((range, region)) = s.split_once(':') else {
+            return Ok(PageRegion { pages: None, region: s.parse()? });
+        };
+        Ok(PageRegion { pages: Some(parse_page_range(range)?), region: region.parse()? })
+    }
+}
+
+/// Paint `region` of the page white
+pub fn blank_region(img: &mut GrayImage, region: &Region) {
+    let (w, h) = img.dimensions();
+    let x0 = (region.x * w as f32) as u32;
+    let y0 = (region.y * h as f32) as u32;
+    let x1 = ((region.x + region.w) * w as f32).ceil().min(w as f32) as u32;
+    let y1 = ((region.y + region.h) * h as f32).ceil().min(h as f32) as u32;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            img.put_pixel(x, y, Luma([255]));
+        }
+    }
+}
+
+/// The page turned `degrees` (0, 90, 180 or 270) clockwise
+pub fn rotate(img: GrayImage, degrees: u16) -> GrayImage {
+    match degrees {
+        90 => imageops::rotate90(&img),
+        180 => imageops::rotate180(&img),
+        270 => imageops::rotate270(&img),
+        _ => img,
+    }
+}
+
+/// The part of the page inside `region`
+pub fn crop_to(img: &GrayImage, region: &Region) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let x0 = ((region.x * w as f32) as u32).min(w.saturating_sub(1));
+    let y0 = ((region.y * h as f32) as u32).min(h.saturating_sub(1));
+    let x1 = ((region.x + region.w) * w as f32).ceil().min(w as f32) as u32;
+    let y1 = ((region.y + region.h) * h as f32).ceil().min(h as f32) as u32;
+    imageops::crop_imm(img, x0, y0, x1.saturating_sub(x0).max(1), y1.saturating_sub(y0).max(1)).to_image()
+}
+
+/// Resolution of the quick pass that looks for watermarks
+const WATERMARK_DPI: u16 = 50;
+
+/// Side of the page-fraction grid watermark statistics are gathered on
+const WATERMARK_GRID: u32 = 256;
+
+/// Watermarks are only looked for in documents with at least this many pages
+const MIN_WATERMARK_PAGES: usize = 3;
+
+/// A watermark cell is at least this much darker than the paper...
+const WATERMARK_DELTA: f32 = 12.0;
+
+/// ...and varies less than this (standard deviation) from page to page,
+/// unlike text which differs on every page
+const WATERMARK_MAX_STDDEV: f32 = 10.0;
+
+/// Pixels darker than this are ink and survive watermark removal, so text
+/// printed over a light watermark is kept
+const INK_LEVEL: u8 = 96;
+
+/// Cells of the page (on a `WATERMARK_GRID` square grid) covered by an overlay
+/// repeated on every page, such as a "DRAFT" watermark or a library stamp
+pub struct WatermarkMask {
+    cells: Vec<bool>,
+}
+
+impl WatermarkMask {
+    fn covers(&self, gx: u32, gy: u32) -> bool {
+        self.cells[(gy * WATERMARK_GRID + gx) as usize]
+    }
+
+    /// Whiten the light (non-ink) pixels of `img` under the watermark
+    pub fn apply(&self, img: &mut GrayImage) {
+        let (w, h) = img.dimensions();
+        for (x, y, p) in img.enumerate_pixels_mut() {
+            let gx = x * WATERMARK_GRID / w;
+            let gy = y * WATERMARK_GRID / h;
+            if p[0] > INK_LEVEL && self.covers(gx, gy) {
+                p[0] = 255;
+            }
+        }
+    }
+}
+
+/// Look for an overlay repeated across the pages of `pdf`.
+///
+/// Every page is rendered at low resolution and scaled to a common grid. Cells whose
+/// gray level is consistently below the paper level on all pages are the watermark;
+/// text moves from page to page and has a large variance. Returns `None` when the
+/// document is too short to tell or nothing is found.
+pub fn detect_watermark(pdf: &PdfDocument) -> Result<Option<WatermarkMask>, Pdf2EPubErr> {
+    let pages = pdf.pages().len() as usize;
+    if pages < MIN_WATERMARK_PAGES {
+        return Ok(None);
+    }
+
+    // running mean and variance per cell (Welford)
+    let cells = (WATERMARK_GRID * WATERMARK_GRID) as usize;
+    let mut mean = vec![0f32; cells];
+    let mut m2 = vec![0f32; cells];
+    for (n, page) in pdf.pages().iter().enumerate() {
+        let img = img_source_from_page(&page, WATERMARK_DPI, true)?;
+        let gray = imageops::grayscale(&img);
+        let grid = imageops::resize(&gray, WATERMARK_GRID, WATERMARK_GRID, FilterType::Triangle);
+        let count = (n + 1) as f32;
+        for (i, p) in grid.pixels().enumerate() {
+            let v = p[0] as f32;
+            let delta = v - mean[i];
+            mean[i] += delta / count;
+            m2[i] += delta * (v - mean[i]);
+        }
+    }
+
+    let mut sorted = mean.clone();
+    sorted.sort_unstable_by(f32::total_cmp);
+    let paper = sorted[sorted.len() * 9 / 10];
+
+    let marked: Vec<bool> = mean
+        .iter()
+        .zip(&m2)
+        .map(|(&m, &m2)| {
+            let stddev = (m2 / pages as f32).sqrt();
+            m < paper - WATERMARK_DELTA && m > INK_LEVEL as f32 && stddev < WATERMARK_MAX_STDDEV
+        })
+        .collect();
+    if !marked.contains(&true) {
+        return Ok(None);
+    }
+
+    // grow by one cell so the antialiased edges of the overlay are covered too
+    let g = WATERMARK_GRID as i64;
+    let cells = (0..g * g)
+        .map(|i| {
+            let (x, y) = (i % g, i / g);
+            (-1..=1).any(|dy| {
+                (-1..=1).any(|dx| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    (0..g).contains(&nx) && (0..g).contains(&ny) && marked[(ny * g + nx) as usize]
+                })
+            })
+        })
+        .collect();
+    Ok(Some(WatermarkMask { cells }))
+}
@@ -0,0 +1,206 @@
+//! Display equations as MathML (`--math-ocr-cmd`), for technical books: equations set in
+//! TeX's math fonts are found in the text layer of the PDF, cropped from the rendered page
+//! and handed to an external math OCR command (a pix2tex-style model, or a service) that
+//! answers with MathML. Equations the command cannot read stay images; scans without a
+//! text layer keep their equations in the OCR text.
+use std::io::Write;
+use std::process::{Command, Stdio};
+use anyhow::Context;
+use image::RgbImage;
+use pdfium_render::prelude::*;
+use crate::document::Figure;
+use crate::figures::{crop_figure, mask_region};
+use crate::ocr::Rect;
+use crate::validation::check_xml;
+use crate::{warn, Pdf2EPubErr};
+
+/// Fonts equations are set in (matched lowercase, anywhere in the font name): TeX's math
+/// italic, symbols and extensions, the AMS symbols and fraktur, and OpenType math fonts
+const MATH_FONTS: &[&str] = &["cmmi", "cmsy", "cmex", "msam", "msbm", "eufm", "rsfs", "math"];
+
+/// Share of the characters of a line set in math fonts for the line to be an equation;
+/// lines of text with some inline math fall short of it
+const MATH_LINE_SHARE: f32 = 0.5;
+
+/// Margin around an equation, in points
+const EQUATION_PADDING: f32 = 3.0;
+
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// A line of text objects, in PDF points (origin bottom left)
+struct TextLine {
+    left: f32,
+    bottom: f32,
+    right: f32,
+    top: f32,
+    chars: usize,
+    math_chars: usize,
+    text: String,
+}
+
+impl TextLine {
+    fn is_equation(&self) -> bool {
+        self.chars > 0 && self.math_chars as f32 >= MATH_LINE_SHARE * self.chars as f32
+    }
+
+    fn extend(&mut self, other: TextLine) {
+        self.left = self.left.min(other.left);
+        self.bottom = self.bottom.min(other.bottom);
+        self.right = self.right.max(other.right);
+        self.top = self.top.max(other.top);
+        self.chars += other.chars;
+        self.math_chars += other.math_chars;
+        if !other.text.is_empty() {
+            if !self.text.is_empty() {
+                self.text.push(' ');
+            }
+            self.text.push_str(&other.text);
+        }
+    }
+}
+
+/// The text object `obj` as a line of its own
+fn text_line(obj: &PdfPageObject) -> Option<TextLine> {
+    let text = obj.as_text_object()?;
+    let b = obj.bounds().ok()?;
+    let content = text.text().trim().to_string();
+    let chars = content.chars().filter(|c| !c.is_whitespace()).count();
+    let font = text.font().name().to_lowercase();
+    let math = MATH_FONTS.iter().any(|f| font.contains(f));
+    Some(TextLine {
+        left: b.left().value,
+        bottom: b.bottom().value,
+        right: b.right().value,
+        top: b.top().value,
+        chars,
+        math_chars: if math { chars } else { 0 },
+        text: content,
+    })
+}
+
+/// The display equations of `page`: where they are on the page rendered at `target_dpi`,
+/// and their text as the text layer has it
+pub fn equation_regions(page: &PdfPage, target_dpi: u16) -> Vec<(Rect, String)> {
+    let scale = target_dpi as f32 / 72.0;
+    let page_h = page.height().value;
+    let mut objects: Vec<TextLine> = page.objects().iter().filter_map(|o| text_line(&o)).collect();
+    let middle = |l: &TextLine| (l.bottom + l.top) / 2.0;
+    objects.sort_by(|a, b| middle(b).total_cmp(&middle(a)));
+
+    // objects whose middle falls within the line above, scripts included, are on that line
+    let mut lines: Vec<TextLine> = Vec::new();
+    for obj in objects {
+        match lines.last_mut() {
+            Some(line) if middle(&obj) >= line.bottom => line.extend(obj),
+            _ => lines.push(obj),
+        }
+    }
+
+    // the lines of a fraction or an aligned derivation make one equation
+    let mut equations: Vec<TextLine> = Vec::new();
+    let mut previous_equation = false;
+    for line in lines {
+        let equation = line.is_equation();
+        match equations.last_mut() {
+            Some(last) if equation && previous_equation && last.bottom - line.top < last.top - last.bottom => {
+                last.extend(line)
+            }
+            _ if equation => equations.push(line),
+            _ => {}
+        }
+        previous_equation = equation;
+    }
+
+    equations
+        .into_iter()
+        .map(|e| {
+            let (left, top) = ((e.left - EQUATION_PADDING).max(0.0), (page_h - e.top - EQUATION_PADDING).max(0.0));
+            let rect = Rect {
+                x: (left * scale) as u32,
+                y: (top * scale) as u32,
+                w: ((e.right - e.left + 2.0 * EQUATION_PADDING) * scale) as u32,
+                h: ((e.top - e.bottom + 2.0 * EQUATION_PADDING) * scale) as u32,
+            };
+            (rect, e.text)
+        })
+        .collect()
+}
+
+/// The `<math>` element of `output`, in the MathML namespace and displayed as a block,
+/// if it is well-formed
+pub fn extract_mathml(output: &str) -> Option<String> {
+    let start = output.find("<math")?;
+    let end = output.rfind("</math>")? + "</math>".len();
+    if end <= start {
+        return None;
+    }
+    let mut math = output[start..end].to_string();
+    let open = math[..math.find('>')?].to_string();
+    if !open.contains("display=") {
+        math.insert_str("<math".len(), r#" display="block""#);
+    }
+    if !open.contains("xmlns") {
+        math.insert_str("<math".len(), &format!(r#" xmlns="{}""#, MATHML_NS));
+    }
+    check_xml(&math).ok()?;
+    Some(math)
+}
+
+/// Read the equation `fig` with the math OCR command `cmd`.
+/// `cmd` is run through `sh -c` with the encoded image on stdin and its media type in
+/// `PDF2EPUB_IMAGE_MIME`; its output is expected to hold a `<math>` element.
+pub fn recognize(cmd: &str, fig: &Figure) -> Result<Option<String>, Pdf2EPubErr> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .env("PDF2EPUB_IMAGE_MIME", fig.mime)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run math OCR command `{}`", cmd))?;
+
+    child.stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(&fig.data)
+        .context("failed to send equation to math OCR command")?;
+
+    let output = child.wait_with_output().context("math OCR command failed")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("math OCR command exited with {}", output.status).into());
+    }
+    Ok(extract_mathml(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Pull the display equations out of `page` as figures, read as MathML by `cmd` when it
+/// can; a failing command is warned about. Like `figures::extract_figures`, the equations are masked in `img`.
+pub fn extract_equations(
+    page: &PdfPage,
+    page_index: usize,
+    img: &mut RgbImage,
+    target_dpi: u16,
+    cmd: &str,
+) -> Result<Vec<Figure>, Pdf2EPubErr> {
+    let mut equations = Vec::new();
+    for (n, (region, text)) in equation_regions(page, target_dpi).into_iter().enumerate() {
+        let region = Rect {
+            w: region.w.min(img.width().saturating_sub(region.x)),
+            h: region.h.min(img.height().saturating_sub(region.y)),
+            ..region
+        };
+        if region.w == 0 || region.h == 0 {
+            continue;
+        }
+        let name = format!("page-{}-equation-{}.png", page_index + 1, n + 1);
+        let mut fig = crop_figure(img, &region, name, page_index + 1)?;
+        fig.alt = (!text.is_empty()).then_some(text);
+        // an equation the command fails on stays an image, like one it cannot read
+        fig.mathml = recognize(cmd, &fig).unwrap_or_else(|e| {
+            warn(format!("page {}: equation {} left as an image: {}", page_index + 1, n + 1, e));
+            None
+        });
+        mask_region(img, &region);
+        equations.push(fig);
+    }
+    Ok(equations)
+}
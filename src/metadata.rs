@@ -0,0 +1,331 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+use regex::Regex;
+use serde_json::Value;
+use crate::document::{plain_text, Block, Chapter, PageText};
+use crate::frontmatter::find_copyright_page;
+use crate::{warn, Pdf2EPubErr};
+
+/// Bibliographic data about the book beyond what the command line gives
+#[derive(Debug, Clone, Default)]
+pub struct BookMetadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+
+    /// Publication date as given by the source, e.g. "March 1999"
+    pub published: Option<String>,
+
+    pub subjects: Vec<String>,
+
+    /// Cover image bytes and media type
+    pub cover: Option<(Vec<u8>, String)>,
+
+    pub description: Option<String>,
+
+    /// Series the book belongs to, and its position in it
+    pub series: Option<String>,
+    pub series_index: Option<f32>,
+}
+
+impl BookMetadata {
+    /// Publication date for the OPF; only the year is reliably known
+    pub fn publication_date(&self) -> Option<DateTime<Utc>> {
+        static YEAR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(1[5-9]\d\d|20\d\d)\b").unwrap());
+        let year = YEAR_RE.captures(self.published.as_deref()?)?[1].parse().ok()?;
+        Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).single()
+    }
+
+    /// This metadata, with what it lacks taken from `other`
+    pub fn or(self, other: BookMetadata) -> BookMetadata {
+        BookMetadata {
+            title: self.title.or(other.title),
+            authors: if self.authors.is_empty() { other.authors } else { self.authors },
+            publisher: self.publisher.or(other.publisher),
+            published: self.published.or(other.published),
+            subjects: if self.subjects.is_empty() { other.subjects } else { self.subjects },
+            cover: self.cover.or(other.cover),
+            description: self.description.or(other.description),
+            series_index: if self.series.is_some() { self.series_index } else { other.series_index },
+            series: self.series.or(other.series),
+        }
+    }
+}
+
+/// Paragraphs shorter than this are headings or captions, not body text
+const MIN_DESCRIPTION_PARAGRAPH: usize = 80;
+
+/// Synthesize a description from the first body paragraphs of `chapters`, cut at
+/// a word boundary to at most `max_len` characters.
+pub fn describe<'a>(chapters: impl IntoIterator<Item = &'a Chapter>, max_len: usize) -> Option<String> {
+    let mut text = String::new();
+    let paragraphs = chapters
+        .into_iter()
+        .flat_map(|c| &c.blocks)
+        .filter_map(|b| match b {
+            Block::Paragraph(p) => Some(plain_text(p)),
+            _ => None,
+        })
+        .filter(|p| p.chars().count() >= MIN_DESCRIPTION_PARAGRAPH);
+    for p in paragraphs {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&p);
+        if text.chars().count() >= max_len {
+            break;
+        }
+    }
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= max_len {
+        return Some(text);
+    }
+
+    let cut: String = text.chars().take(max_len).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(i) => &cut[..i],
+        None => &cut,
+    };
+    Some(format!("{}…", cut.trim_end_matches([',', ';', ':', ' '])))
+}
+
+/// How long a metadata lookup or cover download may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// "ISBN 0-306-40615-2", "ISBN-13: 978-0-306-40615-7"
+static ISBN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bISBN(?:-1[03])?[:\s]*((?:\d[\s-]?){9}[\dX](?:[\s-]?\d){0,3})").unwrap());
+
+fn valid_isbn(isbn: &str) -> bool {
+    let digit = |c: char| if c == 'X' { 10 } else { c.to_digit(10).unwrap_or(0) };
+    match isbn.len() {
+        10 => isbn.chars().enumerate().map(|(i, c)| (10 - i as u32) * digit(c)).sum::<u32>() % 11 == 0,
+        13 if !isbn.contains('X') => {
+            isbn.chars().enumerate().map(|(i, c)| if i % 2 == 0 { digit(c) } else { 3 * digit(c) }).sum::<u32>() % 10 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Strip separators and check the ISBN-10 or ISBN-13 check digit.
+/// Digits that follow an ISBN-10 on the same line are ignored.
+pub fn normalize_isbn(s: &str) -> Option<String> {
+    let isbn: String = s.chars().filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x').collect::<String>().to_uppercase();
+    if valid_isbn(&isbn) {
+        return Some(isbn);
+    }
+    let isbn10 = isbn.get(..10)?;
+    valid_isbn(isbn10).then(|| isbn10.to_string())
+}
+
+/// Find a valid ISBN printed in the book, looking at the copyright page first
+pub fn find_isbn(pages: &[PageText]) -> Option<String> {
+    let copyright = find_copyright_page(pages).map(|i| &pages[i]);
+    copyright
+        .into_iter()
+        .chain(pages)
+        .flat_map(|p| ISBN_RE.captures_iter(&p.text).map(|c| c[1].to_string()).collect::<Vec<_>>())
+        .find_map(|s| normalize_isbn(&s))
+}
+
+/// Download `url`
+fn fetch(url: &str) -> Result<Vec<u8>, Pdf2EPubErr> {
+    let lookup_err = |e: &dyn std::fmt::Display| Pdf2EPubErr::MetadataLookupError(format!("fetching {} failed: {}", url, e));
+    let response = ureq::get(url).timeout(FETCH_TIMEOUT).call().map_err(|e| lookup_err(&e))?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body).map_err(|e| lookup_err(&e))?;
+    Ok(body)
+}
+
+/// The `name` of every object in the JSON array `v`
+fn names(v: Option<&Value>) -> Vec<String> {
+    v.and_then(Value::as_array)
+        .map(|a| a.iter().filter_map(|x| x.get("name")?.as_str()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Look up `isbn` on OpenLibrary: title, authors, publisher, date, subjects and cover
+pub fn lookup_isbn(isbn: &str) -> Result<BookMetadata, Pdf2EPubErr> {
+    let key = format!("ISBN:{}", isbn);
+    let url = format!("https://openlibrary.org/api/books?bibkeys={}&format=json&jscmd=data", key);
+    let body = fetch(&url)?;
+    let json: Value = serde_json::from_slice(&body)
+        .map_err(|e| Pdf2EPubErr::MetadataLookupError(format!("invalid OpenLibrary response: {}", e)))?;
+    let book = json
+        .get(&key)
+        .ok_or_else(|| Pdf2EPubErr::MetadataLookupError(format!("OpenLibrary has no record for ISBN {}", isbn)))?;
+
+    let cover_url = book
+        .get("cover")
+        .and_then(|c| c.get("large").or_else(|| c.get("medium")))
+        .and_then(Value::as_str);
+    let cover = cover_url.and_then(|url| match fetch(url) {
+        Ok(data) => Some((data, "image/jpeg".to_string())),
+        Err(e) => {
+            warn(format!("no cover: {}", e));
+            None
+        }
+    });
+
+    Ok(BookMetadata {
+        title: book.get("title").and_then(Value::as_str).map(str::to_string),
+        authors: names(book.get("authors")),
+        publisher: names(book.get("publishers")).into_iter().next(),
+        published: book.get("publish_date").and_then(Value::as_str).map(str::to_string),
+        subjects: names(book.get("subjects")),
+        cover,
+        description: None,
+        series: None,
+        series_index: None,
+    })
+}
+
+/// Media type of the image file at `path`, by its extension
+fn image_mime(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+/// An opening or empty element tag: its name, attributes and the slash of an empty one
+static OPEN_TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<([\w:.-]+)([^>]*?)(/?)>").unwrap());
+
+/// `name="value"` or `name='value'`
+static ATTRIBUTE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// The attributes and text of the `tag` elements of `opf`, entities decoded
+fn opf_elements(opf: &str, tag: &str) -> Vec<(String, String)> {
+    let close = format!("</{}>", tag);
+    OPEN_TAG_RE
+        .captures_iter(opf)
+        .filter(|c| &c[1] == tag)
+        .map(|c| {
+            let start = c.get(0).map_or(0, |m| m.end());
+            // an empty element, `<meta ... />`, has no text
+            let text = if c[3].is_empty() {
+                opf[start..].find(&close).map_or("", |end| &opf[start..start + end])
+            } else {
+                ""
+            };
+            (c[2].to_string(), html_escape::decode_html_entities(text.trim()).into_owned())
+        })
+        .collect()
+}
+
+/// The value of attribute `name` among `attributes`
+fn opf_attribute(attributes: &str, name: &str) -> Option<String> {
+    ATTRIBUTE_RE
+        .captures_iter(attributes)
+        .find(|c| &c[1] == name)
+        .and_then(|c| c.get(2).or_else(|| c.get(3)))
+        .map(|value| html_escape::decode_html_entities(value.as_str()).into_owned())
+}
+
+/// Keys of the `--meta-json` object
+const META_JSON_KEYS: &[&str] =
+    &["title", "author", "authors", "publisher", "date", "subjects", "description", "series", "series_index", "cover"];
+
+/// Parse a `--meta-json` argument: an object with any of the keys `title`, `author` (or an
+/// array of `authors`), `publisher`, `date`, `subjects`, `description`, `series`,
+/// `series_index` and `cover`, the path of the cover image
+pub fn parse_meta_json(arg: &str) -> Result<BookMetadata, String> {
+    let json: Value = serde_json::from_str(arg).map_err(|e| format!("invalid JSON: {}", e))?;
+    let object = json.as_object().ok_or("expected a JSON object")?;
+    if let Some(key) = object.keys().find(|k| !META_JSON_KEYS.contains(&k.as_str())) {
+        return Err(format!("unknown key `{}`, expected one of {}", key, META_JSON_KEYS.join(", ")));
+    }
+    let string = |key: &str| match object.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(format!("`{}` must be a string", key)),
+    };
+    let strings = |key: &str| match object.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(a)) => {
+            a.iter().map(|v| v.as_str().map(str::to_string).ok_or_else(|| format!("`{}` must be strings", key))).collect()
+        }
+        Some(_) => Err(format!("`{}` must be an array of strings", key)),
+    };
+    let mut authors = strings("authors")?;
+    authors.extend(string("author")?);
+    let series_index = match object.get("series_index") {
+        None | Some(Value::Null) => None,
+        Some(v) => Some(v.as_f64().ok_or("`series_index` must be a number")? as f32),
+    };
+    let cover = match string("cover")? {
+        Some(path) => {
+            let path = Path::new(&path);
+            let data = std::fs::read(path).map_err(|e| format!("cannot read cover {}: {}", path.display(), e))?;
+            Some((data, image_mime(path).to_string()))
+        }
+        None => None,
+    };
+    Ok(BookMetadata {
+        title: string("title")?,
+        authors,
+        publisher: string("publisher")?,
+        published: string("date")?,
+        subjects: strings("subjects")?,
+        cover,
+        description: string("description")?,
+        series: string("series")?,
+        series_index,
+    })
+}
+
+/// Read the metadata of a calibre `metadata.opf`: title, authors, publisher, date,
+/// subjects, description, series and the cover image it names
+pub fn read_opf(path: &Path) -> Result<BookMetadata, Pdf2EPubErr> {
+    static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    let opf = std::fs::read_to_string(path)?;
+    let first = |tag: &str| opf_elements(&opf, tag).into_iter().map(|(_, text)| text).find(|t| !t.is_empty());
+    let meta = |name: &str| {
+        opf_elements(&opf, "meta")
+            .into_iter()
+            .find(|(attrs, _)| opf_attribute(attrs, "name").as_deref() == Some(name))
+            .and_then(|(attrs, _)| opf_attribute(&attrs, "content"))
+    };
+    // authors, not editors or illustrators
+    let authors = opf_elements(&opf, "dc:creator")
+        .into_iter()
+        .filter(|(attrs, _)| opf_attribute(attrs, "opf:role").is_none_or(|role| role == "aut"))
+        .map(|(_, name)| name)
+        .collect();
+    // calibre's stand-in for an unknown date is the year 101
+    let published = first("dc:date")
+        .filter(|d| !d.starts_with("0101"))
+        .map(|d| d.split('T').next().unwrap_or(&d).to_string());
+    // calibre keeps the description as HTML
+    let description = first("dc:description")
+        .map(|d| TAG_RE.replace_all(&d, " ").split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|d| !d.is_empty());
+    let cover = opf_elements(&opf, "reference")
+        .into_iter()
+        .find(|(attrs, _)| opf_attribute(attrs, "type").as_deref() == Some("cover"))
+        .and_then(|(attrs, _)| opf_attribute(&attrs, "href"))
+        .map(|href| path.parent().unwrap_or(Path::new("")).join(href));
+    let cover = match cover {
+        Some(file) => Some((std::fs::read(&file)?, image_mime(&file).to_string())),
+        None => None,
+    };
+    Ok(BookMetadata {
+        title: first("dc:title"),
+        authors,
+        publisher: first("dc:publisher"),
+        published,
+        subjects: opf_elements(&opf, "dc:subject").into_iter().map(|(_, s)| s).filter(|s| !s.is_empty()).collect(),
+        cover,
+        description,
+        series: meta("calibre:series"),
+        series_index: meta("calibre:series_index").and_then(|i| i.parse().ok()),
+    })
+}
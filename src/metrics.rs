@@ -0,0 +1,148 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long a scrape may take to send its request or read the answer
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bounds, in seconds, of the stage duration histogram buckets
+const BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket (not cumulative), the last one past every bound
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; BUCKETS.len() + 1];
+        }
+        let i = BUCKETS.iter().position(|&b| secs <= b).unwrap_or(BUCKETS.len());
+        self.buckets[i] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    conversions: u64,
+    failures: u64,
+    pages: u64,
+    stages: Vec<(&'static str, Histogram)>,
+}
+
+/// Conversion counters and per-stage duration histograms, shared between the
+/// converting threads and the `/metrics` endpoint
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    fn counters(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record one run of `stage`
+    pub fn observe_stage(&self, stage: &'static str, elapsed: Duration) {
+        let mut c = self.counters();
+        let i = match c.stages.iter().position(|(s, _)| *s == stage) {
+            Some(i) => i,
+            None => {
+                c.stages.push((stage, Histogram::default()));
+                c.stages.len() - 1
+            }
+        };
+        c.stages[i].1.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn page_done(&self) {
+        self.counters().pages += 1;
+    }
+
+    pub fn conversion_done(&self, ok: bool) {
+        let mut c = self.counters();
+        c.conversions += 1;
+        if !ok {
+            c.failures += 1;
+        }
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let c = self.counters();
+        let mut out = format!(
+            "# HELP pdf2epub_conversions_total Conversions finished, successful or not.\n\
+             # TYPE pdf2epub_conversions_total counter\n\
+             pdf2epub_conversions_total {}\n\
+             # HELP pdf2epub_conversion_failures_total Conversions that failed.\n\
+             # TYPE pdf2epub_conversion_failures_total counter\n\
+             pdf2epub_conversion_failures_total {}\n\
+             # HELP pdf2epub_pages_total PDF pages recognised.\n\
+             # TYPE pdf2epub_pages_total counter\n\
+             pdf2epub_pages_total {}\n\
+             # HELP pdf2epub_stage_duration_seconds Time spent per run of each pipeline stage.\n\
+             # TYPE pdf2epub_stage_duration_seconds histogram\n",
+            c.conversions, c.failures, c.pages
+        );
+        for (stage, h) in &c.stages {
+            let mut cumulative = 0;
+            for (i, bound) in BUCKETS.iter().enumerate() {
+                cumulative += h.buckets[i];
+                out.push_str(&format!(
+                    "pdf2epub_stage_duration_seconds_bucket{{stage=\"{}\",le=\"{}\"}} {}\n",
+                    stage, bound, cumulative
+                ));
+            }
+            out.push_str(&format!(
+                "pdf2epub_stage_duration_seconds_bucket{{stage=\"{stage}\",le=\"+Inf\"}} {count}\n\
+                 pdf2epub_stage_duration_seconds_sum{{stage=\"{stage}\"}} {sum}\n\
+                 pdf2epub_stage_duration_seconds_count{{stage=\"{stage}\"}} {count}\n",
+                stage = stage,
+                sum = h.sum,
+                count = h.count,
+            ));
+        }
+        out
+    }
+}
+
+/// Answer one HTTP request: the metrics on `GET /metrics`, 404 otherwise
+fn respond(stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if path == "/metrics" {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Serve `metrics` on `http://addr/metrics` from a background thread
+pub fn serve(metrics: Arc<Metrics>, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = respond(stream, &metrics) {
+                eprintln!("warning: metrics request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! Notes: the notes section at the back of the book and the footnotes at the bottom of
+//! pages, linked from the note markers in the text and back
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::backmatter::Section;
+use crate::document::{Block, Inline, PageText, ParagraphStyle};
+
+/// The notes section: pages headed "Notes" with lines starting a note on them
+static NOTES: Section = Section {
+    headings: &["notes", "endnotes", "end notes", "notes and references"],
+    entry_ratio: 0.2,
+    is_entry: |line| NOTE_START_RE.is_match(line),
+};
+
+/// "12. Text", "12) Text" or "12 Text"
+static NOTE_START_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{1,3})[.)]?\s+(\S.*)$").unwrap());
+
+/// A note number glued to the word or punctuation before it ("end.12", "word¹²"),
+/// as OCR reads superscript markers
+static MARKER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"[\p{L}.,;:!?"')\]”’](\d{1,3}\b|[⁰¹²³⁴⁵⁶⁷⁸⁹]{1,3})"#).unwrap()
+});
+
+/// How many notes from the one expected next a marker may be for, so that one misread
+/// or lost marker does not stop the markers after it from being linked
+const NOTE_LOOKAHEAD: usize = 3;
+
+/// Where footnotes go in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Footnotes {
+    /// After the paragraph referring to them
+    Inline,
+    /// Gathered in a "Footnotes" chapter at the end
+    Endnotes,
+    /// As EPUB 3 footnotes, which readers show in a popup when the marker is tapped
+    Popup,
+}
+
+/// One note of the notes section, or a footnote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// Anchor id of the note
+    pub id: String,
+
+    /// Number printed before the note; numbering may restart for every chapter or page
+    pub number: u32,
+    pub text: String,
+}
+
+impl Note {
+    /// Anchor id of the place in the text referring to the note
+    pub fn ref_id(&self) -> String {
+        format!("{}-ref", self.id)
+    }
+}
+
+/// Pages of the notes section: the last page in the second half of the book that starts
+/// with a notes heading, and the note-like pages following it
+pub fn find_notes(pages: &[PageText]) -> Option<Range<usize>> {
+    NOTES.find(pages)
+}
+
+/// The notes of the notes section, in order. Lines not starting a note continue the one
+/// before; lines before the first note (headings) are left out.
+pub fn parse_notes(pages: &[PageText]) -> Vec<Note> {
+    let mut notes: Vec<Note> = Vec::new();
+    for line in NOTES.lines(pages) {
+        push_line(&mut notes, line, |k| format!("note-{}", k + 1));
+    }
+    notes
+}
+
+/// Add a line of a notes section to `notes`: a new note when it starts with a number,
+/// else the continuation of the last note. `id` names the `k`-th (0-based) new note.
+fn push_line(notes: &mut Vec<Note>, line: &str, id: impl Fn(usize) -> String) {
+    if let Some(caps) = NOTE_START_RE.captures(line) {
+        let id = id(notes.len());
+        notes.push(Note { id, number: caps[1].parse().expect("number"), text: caps[2].to_string() });
+    } else if let Some(note) = notes.last_mut() {
+        if note.text.ends_with('-') {
+            note.text.pop();
+        } else {
+            note.text.push(' ');
+        }
+        note.text.push_str(line);
+    }
+}
+
+/// Split the footnotes off the bottom of the text of 1-based PDF `page` (half `part` of a
+/// split spread): trailing paragraphs that start with a note number which a marker above
+/// refers to
+pub fn split_footnotes(text: &str, page: usize, part: Option<usize>) -> (String, Vec<Note>) {
+    let page = match part {
+        Some(part) => format!("{}-{}", page, part + 1),
+        None => page.to_string(),
+    };
+    let mut paragraphs: Vec<&str> = text.trim_end().split("\n\n").collect();
+    let mut footnotes: Vec<&str> = Vec::new();
+    while paragraphs.len() > 1 {
+        let last = paragraphs[paragraphs.len() - 1];
+        let Some(caps) = NOTE_START_RE.captures(last.trim_start()) else { break };
+        let number = &caps[1];
+        let body = paragraphs[..paragraphs.len() - 1].join("\n\n");
+        let referenced = MARKER_RE
+            .captures_iter(&body)
+            .any(|m| marker_number(&m[1]).is_some_and(|n| n.to_string() == number));
+        if !referenced {
+            break;
+        }
+        footnotes.insert(0, last);
+        paragraphs.pop();
+    }
+    let mut notes = Vec::new();
+    for line in footnotes.iter().flat_map(|f| f.lines()).map(str::trim).filter(|l| !l.is_empty()) {
+        push_line(&mut notes, line, |k| format!("fn-{}-{}", page, k + 1));
+    }
+    (paragraphs.join("\n\n"), notes)
+}
+
+fn marker_number(marker: &str) -> Option<u32> {
+    let digits: String = marker
+        .chars()
+        .map(|c| match c {
+            '⁰' => '0',
+            '¹' => '1',
+            '²' => '2',
+            '³' => '3',
+            '⁴' => '4',
+            '⁵' => '5',
+            '⁶' => '6',
+            '⁷' => '7',
+            '⁸' => '8',
+            '⁹' => '9',
+            c => c,
+        })
+        .collect();
+    digits.parse().ok()
+}
+
+/// Link the note markers of `text`, starting with the `next` note; markers are only
+/// taken for the number of one of the next `NOTE_LOOKAHEAD` notes, so other numbers stay
+/// text, and the notes skipped over stay unlinked. The notes linked are marked in `linked`.
+/// With `popup`, markers are EPUB 3 note references.
+fn link_text(text: &str, notes: &[Note], next: &mut usize, linked: &mut [bool], popup: bool) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    for m in MARKER_RE.captures_iter(text) {
+        let marker = m.get(1).expect("marker");
+        if *next >= notes.len() {
+            break;
+        }
+        let number = marker_number(marker.as_str());
+        let Some(k) = (*next..notes.len().min(*next + NOTE_LOOKAHEAD)).find(|&k| number == Some(notes[k].number)) else {
+            continue;
+        };
+        let note = &notes[k];
+        if marker.start() > start {
+            inlines.push(Inline::Text(text[start..marker.start()].to_string()));
+        }
+        inlines.push(Inline::Anchor(note.ref_id()));
+        let (href, text) = (format!("#{}", note.id), marker.as_str().to_string());
+        inlines.push(if popup { Inline::NoteRef { href, text } } else { Inline::Link { href, text } });
+        start = marker.end();
+        linked[k] = true;
+        *next = k + 1;
+    }
+    if start < text.len() {
+        inlines.push(Inline::Text(text[start..].to_string()));
+    }
+    inlines
+}
+
+/// Link the note markers in `blocks` to `notes`, in reading order.
+/// Returns which notes were linked.
+pub fn link_markers<'a>(blocks: impl IntoIterator<Item = &'a mut Block>, notes: &[Note]) -> Vec<bool> {
+    let mut next = 0;
+    let mut linked = vec![false; notes.len()];
+    for block in blocks {
+        link_block(block, notes, &mut next, &mut linked, false);
+    }
+    linked
+}
+
+fn link_block(block: &mut Block, notes: &[Note], next: &mut usize, linked: &mut [bool], popup: bool) {
+    if let Some(inlines) = block.inlines_mut() {
+        *inlines = inlines
+            .drain(..)
+            .flat_map(|i| match i {
+                Inline::Text(t) => link_text(&t, notes, next, linked, popup),
+                other => vec![other],
+            })
+            .collect();
+    }
+}
+
+fn note_block(note: &Note, style: ParagraphStyle, linked: bool) -> Block {
+    let number = format!("{}.", note.number);
+    let number = if linked {
+        Inline::Link { href: format!("#{}", note.ref_id()), text: number }
+    } else {
+        Inline::Text(number)
+    };
+    Block::Styled(style, vec![Inline::Anchor(note.id.clone()), number, Inline::Text(format!(" {}", note.text))])
+}
+
+/// Link the markers of `notes` in `blocks` and put every note, set in `style`, after the
+/// paragraph referring to it; notes without a marker go at the end. Popup footnotes
+/// (`ParagraphStyle::Footnote`) get EPUB 3 note references.
+pub fn place_notes(blocks: Vec<Block>, notes: &[Note], style: ParagraphStyle) -> Vec<Block> {
+    let popup = style == ParagraphStyle::Footnote;
+    let mut next = 0;
+    let mut linked = vec![false; notes.len()];
+    let mut out = Vec::with_capacity(blocks.len() + notes.len());
+    for mut block in blocks {
+        let first = next;
+        link_block(&mut block, notes, &mut next, &mut linked, popup);
+        out.push(block);
+        // notes skipped over for a misread marker go here too, in order
+        out.extend((first..next).map(|k| note_block(&notes[k], style, linked[k])));
+    }
+    out.extend(notes[next..].iter().map(|n| note_block(n, style, false)));
+    out
+}
+
+/// The notes chapter, one paragraph per note; the notes `linked` (see `link_markers`)
+/// start with a link back to their marker
+pub fn notes_blocks(notes: &[Note], linked: &[bool]) -> Vec<Block> {
+    notes
+        .iter()
+        .zip(linked)
+        .map(|(note, &linked)| note_block(note, ParagraphStyle::Note, linked))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(number: u32) -> Note {
+        Note { id: format!("note-{}", number), number, text: String::new() }
+    }
+
+    #[test]
+    fn notes_section() {
+        let page = PageText {
+            text: "Notes\n\n1. A first note that is hyphen-\nated across lines.\n2. A second\nnote, continued.".to_string(),
+            ..Default::default()
+        };
+        let notes = parse_notes(&[page]);
+        let texts: Vec<(&str, u32, &str)> = notes.iter().map(|n| (n.id.as_str(), n.number, n.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("note-1", 1, "A first note that is hyphenated across lines."),
+                ("note-2", 2, "A second note, continued."),
+            ]
+        );
+    }
+
+    #[test]
+    fn footnotes_referred_to() {
+        let (text, notes) = split_footnotes("As was claimed.1 More text.\n\n1. The source.", 7, Some(1));
+        assert_eq!(text, "As was claimed.1 More text.");
+        assert_eq!(notes, vec![Note { id: "fn-7-2-1".to_string(), number: 1, text: "The source.".to_string() }]);
+    }
+
+    #[test]
+    fn unreferenced_number_is_no_footnote() {
+        let page = "They moved house that year.\n\n12 Main Street was their address.";
+        let (text, notes) = split_footnotes(page, 7, None);
+        assert_eq!(text, page);
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn markers_past_a_lost_one() {
+        let notes: Vec<Note> = (1..=4).map(note).collect();
+        let mut blocks = vec![Block::Paragraph(vec![Inline::Text(
+            "First.1 The second marker was lost. Pi is 3.14, and so.3 Then.4".to_string(),
+        )])];
+        let linked = link_markers(&mut blocks, &notes);
+        assert_eq!(linked, vec![true, false, true, true]);
+        let marker = |n: u32| {
+            [
+                Inline::Anchor(format!("note-{}-ref", n)),
+                Inline::Link { href: format!("#note-{}", n), text: n.to_string() },
+            ]
+        };
+        let mut expected = vec![Inline::Text("First.".to_string())];
+        expected.extend(marker(1));
+        expected.push(Inline::Text(" The second marker was lost. Pi is 3.14, and so.".to_string()));
+        expected.extend(marker(3));
+        expected.push(Inline::Text(" Then.".to_string()));
+        expected.extend(marker(4));
+        assert_eq!(blocks[0].inlines(), Some(expected.as_slice()));
+    }
+
+    #[test]
+    fn footnote_markers_are_note_references() {
+        let notes = vec![note(1)];
+        let mut next = 0;
+        let mut linked = vec![false];
+        let inlines = link_text("A word¹ here.", &notes, &mut next, &mut linked, true);
+        assert_eq!(
+            inlines,
+            vec![
+                Inline::Text("A word".to_string()),
+                Inline::Anchor("note-1-ref".to_string()),
+                Inline::NoteRef { href: "#note-1".to_string(), text: "¹".to_string() },
+                Inline::Text(" here.".to_string()),
+            ]
+        );
+        assert_eq!((next, linked), (1, vec![true]));
+    }
+}
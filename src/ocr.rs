@@ -0,0 +1,747 @@
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+use std::ops::Range;
+use std::io::Cursor;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use leptess::capi;
+use image::{DynamicImage, GrayImage, RgbImage, ImageFormat};
+use anyhow::Context;
+use crate::document::{Script, ScriptedWord, UncertainWord};
+use crate::indic;
+use crate::Pdf2EPubErr;
+
+/// Axis aligned rectangle in rendered-page pixel coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn bottom(&self) -> u32 {
+        self.y + self.h
+    }
+
+    pub fn right(&self) -> u32 {
+        self.x + self.w
+    }
+
+    /// Smallest rectangle containing both `self` and `other`
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect { x, y, w: self.right().max(other.right()) - x, h: self.bottom().max(other.bottom()) - y }
+    }
+}
+
+/// A single recognised word
+#[derive(Debug, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    pub bbox: Rect,
+
+    /// Tesseract's confidence in the word (0-100)
+    pub conf: f32,
+
+    /// Other readings Tesseract considered, best first; only filled in on request
+    pub alternatives: Vec<String>,
+
+    /// Byte ranges of `text` set as superscript or subscript; only filled in on request
+    pub scripts: Vec<(Range<usize>, Script)>,
+}
+
+/// A recognised text line, words in reading order
+#[derive(Debug, Clone)]
+pub struct OcrLine {
+    pub words: Vec<OcrWord>,
+    pub bbox: Rect,
+
+    /// Tesseract block and paragraph numbers of the line
+    pub paragraph: (u32, u32),
+}
+
+impl OcrLine {
+    pub fn text(&self) -> String {
+        self.words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// A recognised symbol (character)
+#[derive(Debug, Clone)]
+struct Symbol {
+    /// The best reading
+    text: String,
+
+    /// The other readings considered, with their confidences
+    choices: Vec<(f32, String)>,
+
+    /// Whether Tesseract found the symbol raised or lowered
+    script: Option<Script>,
+}
+
+/// Words smaller than this share of their line's median word height may be scripts
+const SCRIPT_HEIGHT_RATIO: f32 = 0.7;
+
+/// How far (as a share of the median word height) a script is raised or lowered
+const SCRIPT_SHIFT_RATIO: f32 = 0.2;
+
+/// What to find out about every word besides its text, box and confidence
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordDetails {
+    /// The other readings Tesseract considered, see `OcrWord::alternatives`
+    pub alternatives: bool,
+
+    /// Superscripts and subscripts, see `OcrWord::scripts`
+    pub scripts: bool,
+}
+
+/// Most alternative readings kept per word
+const MAX_ALTERNATIVES: usize = 3;
+
+/// How many symbol words ahead a TSV word is looked for, where the two lists disagree
+const SYMBOL_LOOKAHEAD: usize = 3;
+
+/// A word as read symbol by symbol: its text, the other readings considered and the byte
+/// ranges of it set raised or lowered
+type WordChoices = (String, Vec<String>, Vec<(Range<usize>, Script)>);
+
+/// Language of the Tesseract model used when none is given
+pub const DEFAULT_OCR_LANGUAGE: &str = "eng";
+
+/// Book language tags of some common Tesseract languages
+const LANGUAGE_TAGS: &[(&str, &str)] = &[
+    ("eng", "en"), ("deu", "de"), ("fra", "fr"), ("spa", "es"), ("ita", "it"), ("por", "pt"),
+    ("nld", "nl"), ("swe", "sv"), ("dan", "da"), ("nor", "no"), ("fin", "fi"), ("pol", "pl"),
+    ("ces", "cs"), ("rus", "ru"), ("ukr", "uk"), ("ell", "el"), ("tur", "tr"), ("heb", "he"),
+    ("ara", "ar"), ("hin", "hi"), ("jpn", "ja"), ("kor", "ko"), ("chi_sim", "zh-Hans"), ("chi_tra", "zh-Hant"),
+    ("ben", "bn"), ("tam", "ta"), ("mar", "mr"), ("nep", "ne"), ("san", "sa"), ("guj", "gu"),
+    ("pan", "pa"), ("tel", "te"), ("kan", "kn"), ("mal", "ml"), ("ori", "or"),
+];
+
+/// The book language tag of Tesseract language `code`
+pub fn language_tag(code: &str) -> Option<&'static str> {
+    LANGUAGE_TAGS.iter().find(|(c, _)| *c == code).map(|(_, tag)| *tag)
+}
+
+/// The Tesseract language of book language `tag` ("hi", "bn-IN"), by its primary
+/// subtag unless the whole tag is known
+pub fn tesseract_language(tag: &str) -> Option<&'static str> {
+    let primary = tag.split(['-', '_']).next().unwrap_or_default();
+    LANGUAGE_TAGS
+        .iter()
+        .find(|(_, t)| t.eq_ignore_ascii_case(tag))
+        .or_else(|| LANGUAGE_TAGS.iter().find(|(_, t)| t.eq_ignore_ascii_case(primary)))
+        .map(|(code, _)| *code)
+}
+
+/// Tesseract's OCR engine mode; the numbers are Tesseract's own `--oem` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EngineMode {
+    /// The original character-pattern engine; sometimes better on unusual typefaces
+    #[value(alias = "0")]
+    Legacy,
+    /// The LSTM neural network
+    #[value(alias = "1")]
+    Lstm,
+    /// Both engines, taking the better result
+    #[value(alias = "2")]
+    Combined,
+    /// Whatever the language data supports, usually LSTM
+    #[default]
+    #[value(alias = "3")]
+    Default,
+}
+
+impl EngineMode {
+    fn capi(self) -> capi::TessOcrEngineMode {
+        match self {
+            EngineMode::Legacy => capi::TessOcrEngineMode_OEM_TESSERACT_ONLY,
+            EngineMode::Lstm => capi::TessOcrEngineMode_OEM_LSTM_ONLY,
+            EngineMode::Combined => capi::TessOcrEngineMode_OEM_TESSERACT_LSTM_COMBINED,
+            EngineMode::Default => capi::TessOcrEngineMode_OEM_DEFAULT,
+        }
+    }
+}
+
+/// How the Tesseract engine is set up
+#[derive(Debug, Clone)]
+pub struct OcrOptions {
+    /// Tesseract language(s), e.g. `eng` or `deu+eng`
+    pub language: String,
+
+    /// Directory of the `.traineddata` files, instead of Tesseract's default
+    pub tessdata: Option<PathBuf>,
+
+    /// Tesseract page segmentation mode, instead of its default (automatic, 3)
+    pub psm: Option<u8>,
+
+    /// Recognition engine; the legacy and combined modes need language data that includes it
+    pub engine_mode: EngineMode,
+
+    /// Words Tesseract should accept as they are, one per line (names, technical terms)
+    pub user_words: Option<PathBuf>,
+
+    /// Patterns of words Tesseract should accept, e.g. `\d\d\d-\d\d\d\d`, one per line
+    pub user_patterns: Option<PathBuf>,
+
+    /// The only characters Tesseract may recognise
+    pub char_whitelist: Option<String>,
+
+    /// Characters Tesseract must never recognise
+    pub char_blacklist: Option<String>,
+}
+
+impl Default for OcrOptions {
+    fn default() -> Self {
+        OcrOptions {
+            language: DEFAULT_OCR_LANGUAGE.to_string(),
+            tessdata: None,
+            psm: None,
+            engine_mode: EngineMode::Default,
+            user_words: None,
+            user_patterns: None,
+            char_whitelist: None,
+            char_blacklist: None,
+        }
+    }
+}
+
+/// Mean word confidence (0-100) of recognised lines
+pub fn mean_confidence(lines: &[OcrLine]) -> Option<f32> {
+    let confs: Vec<f32> = lines.iter().flat_map(|l| l.words.iter().map(|w| w.conf)).collect();
+    (!confs.is_empty()).then(|| confs.iter().sum::<f32>() / confs.len() as f32)
+}
+
+/// Join lines back into plain text, with a blank line before every paragraph
+/// like Tesseract's own text output
+pub fn lines_to_text(lines: &[OcrLine]) -> String {
+    let mut text = String::new();
+    let mut prev = None;
+    for line in lines {
+        if prev.is_some_and(|p| p != line.paragraph) {
+            text.push('\n');
+        }
+        prev = Some(line.paragraph);
+        text.push_str(&line.text());
+        text.push('\n');
+    }
+    text
+}
+
+/// Encode `img` as PNG, the form Tesseract is handed pages in
+pub fn encode_png(img: &RgbImage) -> Result<Vec<u8>, Pdf2EPubErr> {
+    let mut png_bytes: Vec<u8> = Vec::new();
+    DynamicImage::ImageRgb8(img.clone())
+        .write_to(&mut Cursor::new(&mut png_bytes), ImageFormat::Png)
+        .context("failed to encode PNG")?;
+    Ok(png_bytes)
+}
+
+/// A Tesseract engine. It is set up through the C API rather than `LepTess` because
+/// user words and patterns can only be given to Tesseract when it starts.
+struct Engine(*mut capi::TessBaseAPI);
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from TessBaseAPICreate and is deleted only here
+        unsafe { capi::TessBaseAPIDelete(self.0) }
+    }
+}
+
+fn c_string(s: &str) -> Result<CString, Pdf2EPubErr> {
+    CString::new(s).map_err(|_| Pdf2EPubErr::OcrEngineError(format!("`{}` contains a NUL byte", s)))
+}
+
+fn c_path(path: &Path) -> Result<CString, Pdf2EPubErr> {
+    c_string(path.to_str().ok_or_else(|| Pdf2EPubErr::OcrEngineError(format!("{} is not UTF-8", path.display())))?)
+}
+
+/// Take ownership of a string returned by Tesseract
+///
+/// # Safety
+/// `text` must be null or a string allocated by Tesseract that nothing else frees
+unsafe fn take_text(text: *mut c_char, what: &str) -> Result<String, Pdf2EPubErr> {
+    if text.is_null() {
+        return Err(anyhow::anyhow!("Tesseract failed to recognise {}", what).into());
+    }
+    let owned = CStr::from_ptr(text).to_string_lossy().into_owned();
+    capi::TessDeleteText(text);
+    Ok(owned)
+}
+
+impl Engine {
+    fn new(opts: &OcrOptions) -> Result<Engine, Pdf2EPubErr> {
+        let tessdata = opts.tessdata.as_deref().map(c_path).transpose()?;
+        let language = c_string(&opts.language)?;
+        let mut init_vars: Vec<(CString, CString)> = Vec::new();
+        if let Some(words) = &opts.user_words {
+            init_vars.push((c_string("user_words_file")?, c_path(words)?));
+        }
+        if let Some(patterns) = &opts.user_patterns {
+            init_vars.push((c_string("user_patterns_file")?, c_path(patterns)?));
+        }
+        let mut names: Vec<*mut c_char> = init_vars.iter().map(|(n, _)| n.as_ptr().cast_mut()).collect();
+        let mut values: Vec<*mut c_char> = init_vars.iter().map(|(_, v)| v.as_ptr().cast_mut()).collect();
+
+        // SAFETY: a fresh handle, deleted by `Drop` should Init fail
+        let mut engine = Engine(unsafe { capi::TessBaseAPICreate() });
+        // SAFETY: the strings outlive the call, which only reads them
+        let status = unsafe {
+            capi::TessBaseAPIInit4(
+                engine.0,
+                tessdata.as_deref().map_or(ptr::null(), CStr::as_ptr),
+                language.as_ptr(),
+                opts.engine_mode.capi(),
+                ptr::null_mut(),
+                0,
+                names.as_mut_ptr(),
+                values.as_mut_ptr(),
+                init_vars.len(),
+                0,
+            )
+        };
+        if status != 0 {
+            return Err(Pdf2EPubErr::OcrEngineError(format!(
+                "could not start Tesseract with language `{}` in engine mode {:?}",
+                opts.language, opts.engine_mode
+            )));
+        }
+        if let Some(psm) = opts.psm {
+            engine.set_variable("tessedit_pageseg_mode", &psm.to_string())?;
+        }
+        if let Some(chars) = &opts.char_whitelist {
+            engine.set_variable("tessedit_char_whitelist", chars)?;
+        }
+        if let Some(chars) = &opts.char_blacklist {
+            engine.set_variable("tessedit_char_blacklist", chars)?;
+        }
+        Ok(engine)
+    }
+
+    fn set_variable(&mut self, name: &str, value: &str) -> Result<(), Pdf2EPubErr> {
+        let (c_name, c_value) = (c_string(name)?, c_string(value)?);
+        // SAFETY: valid handle and strings; Tesseract copies the value
+        if unsafe { capi::TessBaseAPISetVariable(self.0, c_name.as_ptr(), c_value.as_ptr()) } == 0 {
+            return Err(Pdf2EPubErr::OcrEngineError(format!("invalid Tesseract setting {} = {}", name, value)));
+        }
+        Ok(())
+    }
+
+    fn set_image_file(&mut self, path: &Path) -> Result<(), Pdf2EPubErr> {
+        let c_path = c_path(path)?;
+        // SAFETY: as for `set_image_png`; Leptonica reads the file itself
+        unsafe {
+            let mut pix = capi::pixRead(c_path.as_ptr());
+            if pix.is_null() {
+                return Err(anyhow::anyhow!("Tesseract failed to load image {}", path.display()).into());
+            }
+            capi::TessBaseAPISetImage2(self.0, pix);
+            capi::pixDestroy(&mut pix);
+        }
+        Ok(())
+    }
+
+    fn set_image_png(&mut self, png: &[u8]) -> Result<(), Pdf2EPubErr> {
+        // SAFETY: Leptonica copies the bytes into a new image, which Tesseract keeps its own
+        // reference to, so ours is released right away
+        unsafe {
+            let mut pix = capi::pixReadMem(png.as_ptr(), png.len());
+            if pix.is_null() {
+                return Err(anyhow::anyhow!("Tesseract failed to load image from memory").into());
+            }
+            capi::TessBaseAPISetImage2(self.0, pix);
+            capi::pixDestroy(&mut pix);
+        }
+        Ok(())
+    }
+
+    /// Recognise the image and return its text
+    fn text(&mut self) -> Result<String, Pdf2EPubErr> {
+        // SAFETY: valid handle; the returned string is ours to free
+        unsafe { take_text(capi::TessBaseAPIGetUTF8Text(self.0), "text") }
+    }
+
+    /// Recognise the image and return Tesseract's TSV output
+    fn tsv(&mut self) -> Result<String, Pdf2EPubErr> {
+        // SAFETY: valid handle; the returned string is ours to free
+        unsafe { take_text(capi::TessBaseAPIGetTsvText(self.0, 0), "text") }
+    }
+
+    /// Mean confidence of the recognised words (0-100)
+    fn mean_conf(&mut self) -> i32 {
+        // SAFETY: valid handle
+        unsafe { capi::TessBaseAPIMeanTextConf(self.0) }
+    }
+
+    /// The symbols of every recognised word, in reading order, each with the other
+    /// choices Tesseract considered for it and whether it is raised or lowered
+    fn symbols(&mut self) -> Vec<Vec<Symbol>> {
+        use capi::{TessPageIteratorLevel_RIL_SYMBOL as SYMBOL, TessPageIteratorLevel_RIL_WORD as WORD};
+        let mut words: Vec<Vec<Symbol>> = Vec::new();
+        // SAFETY: the iterators belong to the recognised page of this handle and are
+        // deleted before returning; texts from GetUTF8Text are ours, choice texts are not
+        unsafe {
+            let it = capi::TessBaseAPIGetIterator(self.0);
+            if it.is_null() {
+                return words;
+            }
+            let page_it = capi::TessResultIteratorGetPageIterator(it);
+            loop {
+                if words.is_empty() || capi::TessPageIteratorIsAtBeginningOf(page_it, WORD) != 0 {
+                    words.push(Vec::new());
+                }
+                if let Ok(best) = take_text(capi::TessResultIteratorGetUTF8Text(it, SYMBOL), "symbol") {
+                    let mut choices = Vec::new();
+                    let ci = capi::TessResultIteratorGetChoiceIterator(it);
+                    if !ci.is_null() {
+                        loop {
+                            let text = capi::TessChoiceIteratorGetUTF8Text(ci);
+                            if !text.is_null() {
+                                let text = CStr::from_ptr(text).to_string_lossy().into_owned();
+                                if text != best {
+                                    choices.push((capi::TessChoiceIteratorConfidence(ci), text));
+                                }
+                            }
+                            if capi::TessChoiceIteratorNext(ci) == 0 {
+                                break;
+                            }
+                        }
+                        capi::TessChoiceIteratorDelete(ci);
+                    }
+                    let script = if capi::TessResultIteratorSymbolIsSuperscript(it) != 0 {
+                        Some(Script::Super)
+                    } else if capi::TessResultIteratorSymbolIsSubscript(it) != 0 {
+                        Some(Script::Sub)
+                    } else {
+                        None
+                    };
+                    words.last_mut().expect("word pushed").push(Symbol { text: best, choices, script });
+                }
+                if capi::TessResultIteratorNext(it, SYMBOL) == 0 {
+                    break;
+                }
+            }
+            capi::TessResultIteratorDelete(it);
+        }
+        words.retain(|w| !w.is_empty());
+        words
+    }
+}
+
+/// A word spelled from its `symbols` and its alternatives: the word with one symbol
+/// replaced by another choice, most confident first
+fn word_alternatives(symbols: &[Symbol]) -> (String, Vec<String>) {
+    let word: String = symbols.iter().map(|s| s.text.as_str()).collect();
+    let mut candidates: Vec<(f32, String)> = Vec::new();
+    for (i, symbol) in symbols.iter().enumerate() {
+        for (conf, choice) in &symbol.choices {
+            let alternative = symbols
+                .iter()
+                .enumerate()
+                .map(|(j, s)| if i == j { choice.as_str() } else { s.text.as_str() })
+                .collect();
+            candidates.push((*conf, alternative));
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut alternatives: Vec<String> = Vec::new();
+    for (_, candidate) in candidates {
+        if candidate != word && !alternatives.contains(&candidate) && alternatives.len() < MAX_ALTERNATIVES {
+            alternatives.push(candidate);
+        }
+    }
+    (word, alternatives)
+}
+
+/// Create a Tesseract engine with the PNG image `png` loaded
+fn tess_with_png(png: &[u8], opts: &OcrOptions) -> Result<Engine, Pdf2EPubErr> {
+    let mut engine = Engine::new(opts)?;
+    engine.set_image_png(png)?;
+    Ok(engine)
+}
+
+/// Create a Tesseract engine with the image file at `path` loaded
+fn tess_with_file(path: &Path, opts: &OcrOptions) -> Result<Engine, Pdf2EPubErr> {
+    let mut engine = Engine::new(opts)?;
+    engine.set_image_file(path)?;
+    Ok(engine)
+}
+
+/// Perform ocr on a PNG-encoded page using Tesseract.
+/// Returns the text and Tesseract's mean confidence (0-100), `None` for pages without text.
+pub fn ocr_png(png: &[u8], opts: &OcrOptions) -> Result<(String, Option<f32>), Pdf2EPubErr> {
+    engine_text(tess_with_png(png, opts)?)
+}
+
+/// The text `engine` recognises in its image and its mean confidence, see `ocr_png`
+fn engine_text(mut engine: Engine) -> Result<(String, Option<f32>), Pdf2EPubErr> {
+    let text = indic::normalize(&engine.text()?);
+    let conf = (!text.trim().is_empty()).then(|| engine.mean_conf() as f32);
+
+    Ok((text, conf))
+}
+
+/// Perform ocr on `RbgImage` using Tesseract, see `ocr_png`
+pub fn ocr_rgb_png(img: &RgbImage, opts: &OcrOptions) -> Result<(String, Option<f32>), Pdf2EPubErr> {
+    ocr_png(&encode_png(img)?, opts)
+}
+
+/// Directory the pages are written to for OCR with `--low-memory`; it is removed with
+/// its files when dropped
+pub struct SpoolDir(PathBuf);
+
+impl SpoolDir {
+    pub fn create() -> Result<SpoolDir, Pdf2EPubErr> {
+        let dir = std::env::temp_dir().join(format!("pdf2epub-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(SpoolDir(dir))
+    }
+
+    /// File of PDF page `page`, or of its half `part` of a split spread, for try `attempt`
+    /// at recognising it (0 for the first)
+    pub fn page_path(&self, page: usize, part: Option<usize>, attempt: u32) -> PathBuf {
+        let mut name = format!("page-{}", page);
+        if let Some(part) = part {
+            name += &format!("-{}", part + 1);
+        }
+        if attempt > 0 {
+            name += &format!("-try-{}", attempt + 1);
+        }
+        self.0.join(name + ".png")
+    }
+}
+
+impl Drop for SpoolDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A preprocessed page on its way to Tesseract: in memory, or written to a PNG file that
+/// Leptonica reads by itself, so that no bitmap of the page is held meanwhile
+pub enum OcrInput {
+    Image(RgbImage),
+
+    /// A file of a `SpoolDir`, removed when the input is dropped
+    File(PathBuf),
+}
+
+impl OcrInput {
+    /// Write `img` to `path` and hand Tesseract the file
+    pub fn spool(img: &GrayImage, path: PathBuf) -> Result<OcrInput, Pdf2EPubErr> {
+        img.save(&path).with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(OcrInput::File(path))
+    }
+
+    /// The text of the page and its mean confidence, see `ocr_png`
+    pub fn text(&self, opts: &OcrOptions) -> Result<(String, Option<f32>), Pdf2EPubErr> {
+        match self {
+            OcrInput::Image(img) => ocr_rgb_png(img, opts),
+            OcrInput::File(path) => engine_text(tess_with_file(path, opts)?),
+        }
+    }
+
+    /// The lines of the page with word boxes, see `ocr_rgb_lines_detailed`
+    pub fn lines(&self, opts: &OcrOptions, details: WordDetails) -> Result<Vec<OcrLine>, Pdf2EPubErr> {
+        match self {
+            OcrInput::Image(img) => ocr_rgb_lines_detailed(img, opts, details),
+            OcrInput::File(path) => engine_lines(tess_with_file(path, opts)?, details),
+        }
+    }
+
+    /// The page as an image, read back from its file if need be
+    pub fn to_rgb(&self) -> Result<Cow<'_, RgbImage>, Pdf2EPubErr> {
+        match self {
+            OcrInput::Image(img) => Ok(Cow::Borrowed(img)),
+            OcrInput::File(path) => {
+                let img = image::open(path).with_context(|| format!("failed to read {}", path.display()))?;
+                Ok(Cow::Owned(img.into_rgb8()))
+            }
+        }
+    }
+}
+
+impl Drop for OcrInput {
+    fn drop(&mut self) {
+        if let OcrInput::File(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Perform ocr on `RgbImage` and return the recognised lines with word boxes
+pub fn ocr_rgb_lines(img: &RgbImage, opts: &OcrOptions) -> Result<Vec<OcrLine>, Pdf2EPubErr> {
+    let tsv = tess_with_png(&encode_png(img)?, opts)?.tsv()?;
+
+    Ok(parse_tsv(&tsv))
+}
+
+/// Like `ocr_rgb_lines`, also filling in the alternative readings of every word
+pub fn ocr_rgb_lines_with_alternatives(img: &RgbImage, opts: &OcrOptions) -> Result<Vec<OcrLine>, Pdf2EPubErr> {
+    ocr_rgb_lines_detailed(img, opts, WordDetails { alternatives: true, scripts: false })
+}
+
+/// Byte ranges of the runs of raised or lowered symbols of a word
+fn symbol_scripts(symbols: &[Symbol]) -> Vec<(Range<usize>, Script)> {
+    let mut runs: Vec<(Range<usize>, Script)> = Vec::new();
+    let mut offset = 0;
+    for symbol in symbols {
+        let range = offset..offset + symbol.text.len();
+        offset = range.end;
+        let Some(script) = symbol.script else { continue };
+        match runs.last_mut() {
+            Some((run, s)) if run.end == range.start && *s == script => run.end = range.end,
+            _ => runs.push((range, script)),
+        }
+    }
+    runs
+}
+
+/// A whole word set as a script, judged by its size and position in the line:
+/// the baseline and size data Tesseract misses on words of their own. Punctuation
+/// (commas, periods, dashes) is small and off the middle of the line without being one.
+fn word_script(word: &OcrWord, line: &OcrLine) -> Option<Script> {
+    if line.words.len() < 3 || !word.text.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let mut heights: Vec<u32> = line.words.iter().map(|w| w.bbox.h).collect();
+    heights.sort_unstable();
+    let median = heights[heights.len() / 2] as f32;
+    if word.bbox.h as f32 >= SCRIPT_HEIGHT_RATIO * median {
+        return None;
+    }
+    let mut bottoms: Vec<u32> = line.words.iter().map(|w| w.bbox.bottom()).collect();
+    bottoms.sort_unstable();
+    let baseline = bottoms[bottoms.len() / 2] as f32;
+    let shift = SCRIPT_SHIFT_RATIO * median;
+    if (word.bbox.bottom() as f32) < baseline - shift {
+        Some(Script::Super)
+    } else if word.bbox.bottom() as f32 > baseline + shift {
+        Some(Script::Sub)
+    } else {
+        None
+    }
+}
+
+/// Like `ocr_rgb_lines`, also filling in the `details` asked for
+pub fn ocr_rgb_lines_detailed(img: &RgbImage, opts: &OcrOptions, details: WordDetails) -> Result<Vec<OcrLine>, Pdf2EPubErr> {
+    engine_lines(tess_with_png(&encode_png(img)?, opts)?, details)
+}
+
+/// The lines `engine` recognises in its image, with the word `details` asked for
+fn engine_lines(mut engine: Engine, details: WordDetails) -> Result<Vec<OcrLine>, Pdf2EPubErr> {
+    if details.alternatives {
+        // have the LSTM engine keep the other symbol choices of its decoder
+        engine.set_variable("lstm_choice_mode", "2")?;
+    }
+    let mut lines = parse_tsv(&engine.tsv()?);
+    if !details.alternatives && !details.scripts {
+        return Ok(lines);
+    }
+
+    let mut words: Vec<WordChoices> = engine
+        .symbols()
+        .iter()
+        .map(|w| {
+            let (raw, alternatives) = word_alternatives(w);
+            let text = indic::normalize(&raw);
+            // script ranges are of the text as recognised
+            let scripts = if text == raw { symbol_scripts(w) } else { Vec::new() };
+            (text, alternatives.iter().map(|a| indic::normalize(a)).collect(), scripts)
+        })
+        .collect();
+    // both list the words in reading order; look a little ahead in case they disagree
+    let mut next = 0;
+    for word in lines.iter_mut().flat_map(|l| &mut l.words) {
+        let end = (next + SYMBOL_LOOKAHEAD).min(words.len());
+        let window = &mut words[next..end];
+        if let Some(i) = window.iter().position(|(text, _, _)| *text == word.text) {
+            if details.alternatives {
+                word.alternatives = std::mem::take(&mut window[i].1);
+            }
+            if details.scripts {
+                word.scripts = std::mem::take(&mut window[i].2);
+            }
+            next += i + 1;
+        }
+    }
+    if details.scripts {
+        for line in &mut lines {
+            let found: Vec<Option<Script>> = line.words.iter().map(|w| word_script(w, line)).collect();
+            for (word, script) in line.words.iter_mut().zip(found) {
+                if let (Some(script), true) = (script, word.scripts.is_empty()) {
+                    word.scripts = vec![(0..word.text.len(), script)];
+                }
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// The words of `lines` recognised with a confidence below `threshold` (0-100)
+pub fn uncertain_words(lines: &[OcrLine], threshold: f32) -> Vec<UncertainWord> {
+    lines
+        .iter()
+        .flat_map(|l| &l.words)
+        .filter(|w| w.conf < threshold)
+        .map(|w| UncertainWord { text: w.text.clone(), alternatives: w.alternatives.clone() })
+        .collect()
+}
+
+/// The words of `lines` with superscripts or subscripts, in reading order
+pub fn scripted_words(lines: &[OcrLine]) -> Vec<ScriptedWord> {
+    lines
+        .iter()
+        .flat_map(|l| &l.words)
+        .filter(|w| !w.scripts.is_empty())
+        .map(|w| ScriptedWord { text: w.text.clone(), scripts: w.scripts.clone() })
+        .collect()
+}
+
+/// Parse Tesseract's TSV output into lines of words.
+/// Columns: level page block par line word left top width height conf text
+pub fn parse_tsv(tsv: &str) -> Vec<OcrLine> {
+    let mut lines: Vec<OcrLine> = Vec::new();
+    let mut cur_key = None;
+
+    for row in tsv.lines() {
+        let cols: Vec<&str> = row.split('\t').collect();
+        // only word rows (level 5) carry text
+        if cols.len() < 12 || cols[0] != "5" {
+            continue;
+        }
+        let text = indic::normalize(cols[11].trim());
+        if text.is_empty() {
+            continue;
+        }
+        let num = |i: usize| cols[i].parse::<u32>().unwrap_or(0);
+        let bbox = Rect { x: num(6), y: num(7), w: num(8), h: num(9) };
+        let conf = cols[10].parse::<f32>().unwrap_or(0.0);
+        let key = (cols[2], cols[3], cols[4]);
+        match lines.last_mut() {
+            Some(line) if cur_key == Some(key) => {
+                line.bbox = line.bbox.union(&bbox);
+                match line.words.last_mut() {
+                    // a vowel sign split off its letter
+                    Some(last) if text.starts_with(indic::is_sign) => {
+                        last.text = indic::normalize(&(last.text.clone() + &text));
+                        last.bbox = last.bbox.union(&bbox);
+                        last.conf = last.conf.min(conf);
+                    }
+                    _ => line.words.push(OcrWord { text, bbox, conf, alternatives: Vec::new(), scripts: Vec::new() }),
+                }
+            }
+            _ => {
+                let paragraph = (num(2), num(3));
+                let word = OcrWord { text, bbox, conf, alternatives: Vec::new(), scripts: Vec::new() };
+                lines.push(OcrLine { words: vec![word], bbox, paragraph });
+                cur_key = Some(key);
+            }
+        }
+    }
+    lines
+}
@@ -0,0 +1,131 @@
+//! Checks for text that was probably put together in the wrong order: pages missing
+//! or swapped, or columns read across instead of down
+use crate::document::{plain_text, Block, Chapter, PageText};
+
+/// Pages with at least this many jumps inside them are suspected of mixed-up columns
+const COLUMN_JUMPS: usize = 2;
+
+/// Characters of text shown on each side of a suspected break
+const CONTEXT: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// A word hyphenated at the end of a page does not continue on the next one
+    BrokenWordAtPageEnd,
+    /// The chapter's first paragraph starts in the middle of a sentence
+    ChapterStartsMidSentence,
+    /// The text inside the page jumps as if columns were read in the wrong order
+    ColumnOrder,
+}
+
+impl IssueKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::BrokenWordAtPageEnd => "broken word at page end",
+            IssueKind::ChapterStartsMidSentence => "chapter starts mid-sentence",
+            IssueKind::ColumnOrder => "column order",
+        }
+    }
+}
+
+/// A place where the reading order looks wrong
+#[derive(Debug, Clone)]
+pub struct ReadingIssue {
+    pub kind: IssueKind,
+
+    /// 1-based PDF page, for issues found on pages
+    pub page: Option<usize>,
+
+    /// Title of the chapter, for issues found in chapters
+    pub chapter: Option<String>,
+
+    /// The text around the problem, `|` marking the break
+    pub context: String,
+}
+
+fn starts_lowercase(s: &str) -> bool {
+    s.trim_start().chars().next().is_some_and(char::is_lowercase)
+}
+
+fn ends_sentence(s: &str) -> bool {
+    s.trim_end().chars().last().is_some_and(|c| ".?!".contains(c))
+}
+
+/// `before|after`, each side cut to `CONTEXT` characters
+fn context(before: &str, after: &str) -> String {
+    let before = before.trim_end();
+    let tail: String = before.chars().skip(before.chars().count().saturating_sub(CONTEXT)).collect();
+    let head: String = after.trim_start().chars().take(CONTEXT).collect();
+    format!("{}|{}", tail, head)
+}
+
+/// Places inside a page where one piece of text is followed by one that cannot continue
+/// it: a hyphenated word followed by a capitalised line, or a finished sentence followed,
+/// after a paragraph break, by a lowercase line
+fn jumps(text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut prev: Option<&str> = None;
+    let mut blank = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if let Some(p) = prev {
+            let starts_upper = line.chars().next().is_some_and(char::is_uppercase);
+            if (p.ends_with('-') && starts_upper) || (blank && ends_sentence(p) && starts_lowercase(line)) {
+                found.push(context(p, line));
+            }
+        }
+        prev = Some(line);
+        blank = false;
+    }
+    found
+}
+
+/// Reading-order issues within and between consecutive `pages`
+pub fn check_pages(pages: &[PageText]) -> Vec<ReadingIssue> {
+    let mut issues = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        let jumps = jumps(&page.text);
+        if jumps.len() >= COLUMN_JUMPS {
+            issues.push(ReadingIssue {
+                kind: IssueKind::ColumnOrder,
+                page: Some(page.page),
+                chapter: None,
+                context: jumps.join(" … "),
+            });
+        }
+        let Some(next) = pages.get(i + 1) else { continue };
+        let last = page.text.trim_end().lines().last().unwrap_or_default();
+        let first = next.text.trim_start().lines().next().unwrap_or_default();
+        if last.ends_with('-') && !starts_lowercase(first) {
+            issues.push(ReadingIssue {
+                kind: IssueKind::BrokenWordAtPageEnd,
+                page: Some(page.page),
+                chapter: None,
+                context: context(last, first),
+            });
+        }
+    }
+    issues
+}
+
+/// Chapters whose text starts in the middle of a sentence
+pub fn check_chapters<'a>(chapters: impl IntoIterator<Item = &'a Chapter>) -> Vec<ReadingIssue> {
+    chapters
+        .into_iter()
+        .filter_map(|c| {
+            let first = c.blocks.iter().find_map(|b| match b {
+                Block::Paragraph(p) | Block::Styled(_, p) => Some(plain_text(p)).filter(|t| !t.trim().is_empty()),
+                Block::Figure(_) | Block::Break => None,
+            })?;
+            starts_lowercase(&first).then(|| ReadingIssue {
+                kind: IssueKind::ChapterStartsMidSentence,
+                page: None,
+                chapter: Some(c.title.clone()),
+                context: context("", &first),
+            })
+        })
+        .collect()
+}
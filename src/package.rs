@@ -0,0 +1,198 @@
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use anyhow::Context;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::Pdf2EPubErr;
+
+/// Path of the package document written by epub-builder
+const OPF_PATH: &str = "OEBPS/content.opf";
+
+/// Path of the navigation document written by epub-builder
+const NAV_PATH: &str = "OEBPS/nav.xhtml";
+
+/// Path of the EPUB 2 table of contents written by epub-builder
+const NCX_PATH: &str = "OEBPS/toc.ncx";
+
+/// Rewrite the package document (`content.opf`) of a generated epub, for metadata
+/// epub-builder cannot express
+pub fn rewrite_opf(epub: &[u8], edit: impl FnOnce(String) -> String) -> Result<Vec<u8>, Pdf2EPubErr> {
+    rewrite_file(epub, OPF_PATH, edit)
+}
+
+/// Rewrite the navigation document (`nav.xhtml`) of a generated epub
+pub fn rewrite_nav(epub: &[u8], edit: impl FnOnce(String) -> String) -> Result<Vec<u8>, Pdf2EPubErr> {
+    rewrite_file(epub, NAV_PATH, edit)
+}
+
+/// Rewrite the text file at `path` inside a generated epub
+fn rewrite_file(epub: &[u8], path: &str, edit: impl FnOnce(String) -> String) -> Result<Vec<u8>, Pdf2EPubErr> {
+    let mut edit = Some(edit);
+    rewrite_files(epub, &[path], |_, text| edit.take().map(|f| f(text)).unwrap_or_default())
+}
+
+/// Rewrite the text files at `paths` inside a generated epub, in one pass; `edit` is
+/// given the path and text of each. Every other file is copied unchanged, in order, so
+/// the uncompressed `mimetype` entry stays first.
+fn rewrite_files(
+    epub: &[u8],
+    paths: &[&str],
+    mut edit: impl FnMut(&str, String) -> String,
+) -> Result<Vec<u8>, Pdf2EPubErr> {
+    let mut archive = ZipArchive::new(Cursor::new(epub)).context("generated epub is not a valid zip")?;
+    let mut out = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).context("failed to read epub entry")?;
+        let Some(path) = paths.iter().copied().find(|p| file.name() == *p) else {
+            out.raw_copy_file(file).context("failed to copy epub entry")?;
+            continue;
+        };
+        drop(file);
+
+        let mut text = String::new();
+        archive.by_index(i).with_context(|| format!("failed to read {}", path))?.read_to_string(&mut text)?;
+        let text = edit(path, text);
+        out.start_file(path, SimpleFileOptions::default().compression_method(CompressionMethod::Deflated))
+            .with_context(|| format!("failed to write {}", path))?;
+        out.write_all(text.as_bytes())?;
+    }
+    Ok(out.finish().context("failed to finish epub")?.into_inner())
+}
+
+/// Write the files of `epub` into the directory `dir` (`mimetype`, `META-INF/`,
+/// `OEBPS/`), to be edited by hand and zipped again
+pub fn unpack(epub: &[u8], dir: &Path) -> Result<(), Pdf2EPubErr> {
+    let mut archive = ZipArchive::new(Cursor::new(epub)).context("generated epub is not a valid zip")?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("failed to read epub entry")?;
+        let Some(name) = file.enclosed_name() else {
+            continue;
+        };
+        let path = dir.join(name);
+        if file.is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        std::fs::write(&path, data)?;
+    }
+    Ok(())
+}
+
+/// The content documents of `epub` whose markup contains `needle`, by their `href` in
+/// the package document
+pub fn documents_containing(epub: &[u8], needle: &str) -> Result<Vec<String>, Pdf2EPubErr> {
+    let mut archive = ZipArchive::new(Cursor::new(epub)).context("generated epub is not a valid zip")?;
+    let mut hrefs = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("failed to read epub entry")?;
+        let Some(href) = file.name().strip_prefix("OEBPS/").filter(|n| n.ends_with(".xhtml")).map(str::to_string) else {
+            continue;
+        };
+        let mut text = String::new();
+        file.read_to_string(&mut text).with_context(|| format!("failed to read {}", href))?;
+        if text.contains(needle) {
+            hrefs.push(href);
+        }
+    }
+    Ok(hrefs)
+}
+
+/// Give the manifest items of `hrefs` the property `property`, such as `mathml` for the
+/// content documents holding MathML
+pub fn add_item_property(mut opf: String, hrefs: &[String], property: &str) -> String {
+    for href in hrefs {
+        let attr = format!(" href=\"{}\"", html_escape::encode_double_quoted_attribute(href));
+        let Some(end) = opf.find(&attr).and_then(|at| opf[at..].find("/>").map(|i| at + i)) else {
+            continue;
+        };
+        opf.insert_str(end, &format!(" properties=\"{}\"", property));
+    }
+    opf
+}
+
+/// Add the manifest items of `hrefs` to the end of the spine as non-linear items: documents
+/// reached by links only, such as the pages of full page scans
+pub fn add_nonlinear_items(mut opf: String, hrefs: &[String]) -> String {
+    let mut itemrefs = String::new();
+    for href in hrefs {
+        let attr = format!(" href=\"{}\"", html_escape::encode_double_quoted_attribute(href));
+        let Some(at) = opf.find(&attr) else {
+            continue;
+        };
+        let item = &opf[opf[..at].rfind("<item").unwrap_or(0)..at];
+        let Some(id) = item.split(" id=\"").nth(1).and_then(|rest| rest.split('"').next()) else {
+            continue;
+        };
+        itemrefs.push_str(&format!("<itemref idref=\"{}\" linear=\"no\"/>", id));
+    }
+    if let Some(end) = opf.find("</spine>") {
+        opf.insert_str(end, &itemrefs);
+    }
+    opf
+}
+
+/// Replace the book's `dc:identifier` (a random UUID URN by default) with `id`
+fn set_identifier(opf: String, id: &str) -> String {
+    let Some(start) = opf.find("<dc:identifier") else {
+        return opf;
+    };
+    let Some(open_end) = opf[start..].find('>').map(|i| start + i + 1) else {
+        return opf;
+    };
+    let Some(close) = opf[open_end..].find("</dc:identifier>").map(|i| open_end + i) else {
+        return opf;
+    };
+    format!("{}{}{}", &opf[..open_end], html_escape::encode_text(id), &opf[close..])
+}
+
+/// Set the `dtb:uid` of the EPUB 2 table of contents, which must match the book's identifier
+fn set_ncx_uid(ncx: String, id: &str) -> String {
+    let meta = format!(r#"<meta name="dtb:uid" content="{}" />"#, html_escape::encode_double_quoted_attribute(id));
+    if let Some(start) = ncx.find(r#"<meta name="dtb:uid""#) {
+        let Some(end) = ncx[start..].find('>').map(|i| start + i + 1) else {
+            return ncx;
+        };
+        return format!("{}{}{}", &ncx[..start], meta, &ncx[end..]);
+    }
+    match ncx.find("<head>").map(|i| i + "<head>".len()) {
+        Some(head) => format!("{}\n    {}{}", &ncx[..head], meta, &ncx[head..]),
+        None => ncx,
+    }
+}
+
+/// Give a generated epub the identifier `id`, in the package document and in the
+/// table of contents alike
+pub fn rewrite_identifier(epub: &[u8], id: &str) -> Result<Vec<u8>, Pdf2EPubErr> {
+    rewrite_files(epub, &[OPF_PATH, NCX_PATH], |path, text| match path {
+        OPF_PATH => set_identifier(text, id),
+        _ => set_ncx_uid(text, id),
+    })
+}
+
+/// Add a page-list `nav` to the navigation document, so readers can show and go to the
+/// pages of the print edition. `pages` are `(name, href)` pairs in reading order.
+pub fn add_page_list(nav: String, pages: &[(String, String)]) -> String {
+    use html_escape::{encode_double_quoted_attribute, encode_text};
+
+    let Some(end) = nav.rfind("</body>") else {
+        return nav;
+    };
+    let items: String = pages
+        .iter()
+        .map(|(name, href)| {
+            format!(
+                "      <li><a href=\"{}\">{}</a></li>\n",
+                encode_double_quoted_attribute(href),
+                encode_text(name)
+            )
+        })
+        .collect();
+    let list = format!("  <nav epub:type=\"page-list\" id=\"page-list\" hidden=\"\">\n    <ol>\n{}    </ol>\n  </nav>\n", items);
+    format!("{}{}{}", &nav[..end], list, &nav[end..])
+}
@@ -0,0 +1,27 @@
+//! Logical page numbering from the PDF's page labels (`/PageLabels`), such as
+//! `i`–`xii` for the front matter followed by `1`–`300`
+use pdfium_render::prelude::*;
+use crate::warn;
+
+/// The page's label, if the PDF assigns it one
+pub fn page_label(page: &PdfPage) -> Option<String> {
+    page.label().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string)
+}
+
+/// The number of an arabic label such as `12`; `None` for `xii` or `A-3`
+pub fn label_number(label: &str) -> Option<u32> {
+    label.trim().parse().ok()
+}
+
+/// The page number to use for the 1-based PDF `page`: the one peeled off its OCR text
+/// (`ocr`) checked against its label. A numeric label wins over a misread number, with
+/// a warning, and stands in for a number that was not found.
+pub fn reconcile(page: usize, ocr: Option<u32>, label: Option<&str>) -> Option<u32> {
+    let Some(labelled) = label.and_then(label_number) else {
+        return ocr;
+    };
+    if let Some(n) = ocr.filter(|&n| n != labelled) {
+        warn(format!("page {}: printed page number {} does not match the PDF's page label {}", page, n, labelled));
+    }
+    Some(labelled)
+}
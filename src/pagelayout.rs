@@ -0,0 +1,237 @@
+//! Layout overrides for the pages automatic segmentation gets wrong (`--layout FILE`): one
+//! line per page range, `FIRST-LAST key=value ...`, e.g. `12-40 columns=2` or
+//! `41 boundaries=0.3,0.65 exclude=0.85,0,0.15,1`. A range may be narrowed to its odd or
+//! even pages (`1-300/odd`), for scans whose recto and verso pages differ. Blank lines and
+//! `#` comments are ignored; where ranges overlap, the later line wins, but for the
+//! excluded regions, which add up.
+use std::ops::RangeInclusive;
+use std::path::Path;
+use crate::mask::Region;
+use crate::Pdf2EPubErr;
+
+/// Most columns a page can be split into
+pub const MAX_COLUMNS: usize = 6;
+
+/// Overrides for some pages of the book
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLayout {
+    /// PDF pages (1-based, inclusive)
+    pub pages: RangeInclusive<usize>,
+
+    /// Only the odd (`Some(1)`) or even (`Some(0)`) pages of `pages`
+    pub parity: Option<usize>,
+
+    /// Columns of equal width the pages are read in
+    pub columns: Option<usize>,
+
+    /// Where the columns of the pages meet, as fractions of the page width, left to right
+    pub boundaries: Option<Vec<f32>>,
+
+    /// Regions of the pages left out of OCR
+    pub exclude: Vec<Region>,
+
+    /// The part of the pages OCR reads, their content area
+    pub crop: Option<Region>,
+
+    /// Degrees clockwise the pages are turned to stand upright
+    pub rotate: Option<u16>,
+}
+
+impl PageLayout {
+    /// Whether the line is about PDF page `page`
+    pub fn applies_to(&self, page: usize) -> bool {
+        self.pages.contains(&page) && self.parity.is_none_or(|p| page % 2 == p)
+    }
+}
+
+/// Parse a range of PDF pages, `FIRST-LAST` or `PAGE` (1-based, inclusive)
+pub fn parse_page_range(range: &str) -> Result<RangeInclusive<usize>, String> {
+    let (first, last) = range.split_once('-').unwrap_or((range, range));
+    let page = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid page range `{}`", range));
+    let (first, last) = (page(first)?, page(last)?);
+    if first == 0 || last < first {
+        return Err(format!("invalid page range `{}`", range));
+    }
+    Ok(first..=last)
+}
+
+/// Parse a number of columns
+pub fn parse_columns(value: &str) -> Result<usize, String> {
+    match value.trim().parse::<usize>() {
+        Ok(n) if (1..=MAX_COLUMNS).contains(&n) => Ok(n),
+        _ => Err(format!("columns must be a number from 1 to {}, got `{}`", MAX_COLUMNS, value)),
+    }
+}
+
+/// Parse a rotation in degrees clockwise: 0, 90, 180 or 270
+pub fn parse_rotation(value: &str) -> Result<u16, String> {
+    match value.trim().parse::<u16>() {
+        Ok(degrees) if degrees % 90 == 0 && degrees < 360 => Ok(degrees),
+        _ => Err(format!("rotation must be 0, 90, 180 or 270 degrees, got `{}`", value)),
+    }
+}
+
+/// Pages to turn upright, see `--rotate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRotation {
+    /// PDF pages (1-based, inclusive)
+    pub pages: RangeInclusive<usize>,
+
+    /// Degrees clockwise
+    pub degrees: u16,
+}
+
+/// Parse a `FIRST-LAST=DEGREES` (or `PAGE=DEGREES`) argument
+pub fn parse_page_rotation(arg: &str) -> Result<PageRotation, String> {
+    let (range, degrees) = arg.split_once('=').ok_or_else(|| format!("expected PAGES=DEGREES, got `{}`", arg))?;
+    Ok(PageRotation { pages: parse_page_range(range)?, degrees: parse_rotation(degrees)? })
+}
+
+/// Parse column boundaries, `0.3,0.65`: increasing fractions of the page width
+fn parse_boundaries(value: &str) -> Result<Vec<f32>, String> {
+    let boundaries = value
+        .split(',')
+        .map(|b| b.trim().parse::<f32>().map_err(|_| format!("invalid column boundary `{}`", b.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+    if boundaries.len() >= MAX_COLUMNS {
+        return Err(format!("at most {} columns", MAX_COLUMNS));
+    }
+    if boundaries.iter().any(|b| !(0.0 < *b && *b < 1.0)) || boundaries.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(format!("boundaries must be increasing fractions between 0 and 1, got `{}`", value));
+    }
+    Ok(boundaries)
+}
+
+/// Parse one `FIRST-LAST[/odd|/even] key=value ...` line
+fn parse_line(line: &str) -> Result<PageLayout, String> {
+    let mut fields = line.split_whitespace();
+    let range = fields.next().unwrap_or_default();
+    let (range, parity) = match range.split_once('/') {
+        Some((range, "odd")) => (range, Some(1)),
+        Some((range, "even")) => (range, Some(0)),
+        Some((_, other)) => return Err(format!("expected `odd` or `even` after the page range, got `{}`", other)),
+        None => (range, None),
+    };
+
+    let mut layout = PageLayout {
+        pages: parse_page_range(range)?,
+        parity,
+        columns: None,
+        boundaries: None,
+        exclude: Vec::new(),
+        crop: None,
+        rotate: None,
+    };
+    for field in fields {
+        let (key, value) = field.split_once('=').ok_or_else(|| format!("expected key=value, got `{}`", field))?;
+        match key {
+            "columns" => layout.columns = Some(parse_columns(value)?),
+            "boundaries" => layout.boundaries = Some(parse_boundaries(value)?),
+            "exclude" => layout.exclude.push(value.parse().map_err(|e| format!("invalid region `{}`: {}", value, e))?),
+            "rotate" => layout.rotate = Some(parse_rotation(value)?),
+            "crop" => layout.crop = Some(value.parse().map_err(|e| format!("invalid crop box `{}`: {}", value, e))?),
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+    }
+    Ok(layout)
+}
+
+/// Read a layout file
+pub fn read_layout(path: &Path) -> Result<Vec<PageLayout>, Pdf2EPubErr> {
+    let text = std::fs::read_to_string(path)?;
+    let mut layouts = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let layout = parse_line(line).map_err(|e| Pdf2EPubErr::LayoutError(format!("{}:{}: {}", path.display(), n + 1, e)))?;
+        layouts.push(layout);
+    }
+    Ok(layouts)
+}
+
+/// Boundaries of `columns` columns of equal width
+pub fn even_boundaries(columns: usize) -> Vec<f32> {
+    (1..columns).map(|i| i as f32 / columns as f32).collect()
+}
+
+/// Where the columns of PDF page `page` meet: as the last line of `layouts` for the page
+/// says, or else as `columns` (`--columns`) does; none for a single column
+pub fn column_boundaries(layouts: &[PageLayout], page: usize, columns: Option<usize>) -> Vec<f32> {
+    let layout = layouts
+        .iter()
+        .rev()
+        .filter(|l| l.applies_to(page))
+        .find(|l| l.boundaries.is_some() || l.columns.is_some());
+    match layout {
+        Some(PageLayout { boundaries: Some(boundaries), .. }) => boundaries.clone(),
+        Some(PageLayout { columns: Some(n), .. }) => even_boundaries(*n),
+        _ => even_boundaries(columns.unwrap_or(1)),
+    }
+}
+
+/// The regions of PDF page `page` left out of OCR, from all lines of `layouts` for the page
+pub fn excluded_regions(layouts: &[PageLayout], page: usize) -> impl Iterator<Item = &Region> {
+    layouts.iter().filter(move |l| l.applies_to(page)).flat_map(|l| &l.exclude)
+}
+
+/// The crop box of PDF page `page`, as the last line of `layouts` for the page with one says
+pub fn crop_box(layouts: &[PageLayout], page: usize) -> Option<Region> {
+    layouts.iter().rev().filter(|l| l.applies_to(page)).find_map(|l| l.crop)
+}
+
+/// Degrees clockwise PDF page `page` is turned to stand upright: as the last line of
+/// `layouts` for the page with a rotation says, or else the last of `rotations` (`--rotate`)
+pub fn rotation(layouts: &[PageLayout], rotations: &[PageRotation], page: usize) -> u16 {
+    layouts
+        .iter()
+        .rev()
+        .filter(|l| l.applies_to(page))
+        .find_map(|l| l.rotate)
+        .or_else(|| rotations.iter().rev().find(|r| r.pages.contains(&page)).map(|r| r.degrees))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_ranges() {
+        assert_eq!(parse_page_range("12-40"), Ok(12..=40));
+        assert_eq!(parse_page_range(" 7 "), Ok(7..=7));
+        assert!(parse_page_range("0-3").is_err());
+        assert!(parse_page_range("5-3").is_err());
+        assert!(parse_page_range("a-b").is_err());
+    }
+
+    #[test]
+    fn layout_lines() {
+        let layout = parse_line("12-40 columns=2").unwrap();
+        assert_eq!(layout.pages, 12..=40);
+        assert_eq!(layout.columns, Some(2));
+        assert_eq!(layout.boundaries, None);
+
+        let layout = parse_line("41 boundaries=0.3,0.65").unwrap();
+        assert_eq!(layout.pages, 41..=41);
+        assert_eq!(layout.boundaries, Some(vec![0.3, 0.65]));
+    }
+
+    #[test]
+    fn odd_and_even_pages() {
+        let layout = parse_line("1-300/odd crop=0.05,0,0.9,1").unwrap();
+        assert_eq!(layout.parity, Some(1));
+        assert_eq!(layout.crop, Some(Region { x: 0.05, y: 0.0, w: 0.9, h: 1.0 }));
+        assert!(layout.applies_to(3) && !layout.applies_to(4));
+        assert!(parse_line("2-300/even").unwrap().applies_to(4));
+        assert!(!parse_line("1-3/odd").unwrap().applies_to(5));
+    }
+
+    #[test]
+    fn invalid_layout_lines() {
+        for line in ["5-3 columns=2", "1-3/both", "1-3 columns", "1-3 columns=9", "1-3 boundaries=0.6,0.3", "1-3 color=red"] {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+    }
+}
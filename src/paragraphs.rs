@@ -0,0 +1,61 @@
+//! The rules `LineUnwrapper` tells paragraph breaks from page and column gaps by
+use crate::sentences::{Ending, SentenceRules, Start};
+
+/// How readily a blank line is taken for a paragraph break
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Aggressiveness {
+    /// Only after a finished sentence
+    Low,
+    /// After a finished sentence, or before a capitalised line
+    #[default]
+    Medium,
+    /// At every blank line
+    High,
+}
+
+/// When a line starts a new paragraph
+#[derive(Debug, Clone, Default)]
+pub struct ParagraphRules {
+    /// Where sentences end and start
+    pub sentences: SentenceRules,
+
+    pub aggressiveness: Aggressiveness,
+
+    /// Indented lines may start a paragraph without a blank line before them; the OCR
+    /// text has no leading whitespace, so indents come from the left x of the line's words
+    /// (`LineInfo::indented`)
+    pub indent: bool,
+
+    /// Lines after one that ends well short of the right margin may start a paragraph
+    /// without a blank line before them, as in justified text
+    pub short_lines: bool,
+
+    /// Blank lines in a row that make a section break (`Block::Break`) rather than a
+    /// paragraph break; `None` never breaks sections
+    pub section_break: Option<usize>,
+}
+
+impl ParagraphRules {
+    /// The default rules for BCP 47 language tag `lang`
+    pub fn for_language(lang: &str) -> Self {
+        Self { sentences: SentenceRules::for_language(lang), ..Self::default() }
+    }
+
+    /// Whether `line`, after a blank, indented or after a short line, starts a new paragraph after `text`
+    pub fn is_break(&self, text: &str, line: &str) -> bool {
+        if self.aggressiveness == Aggressiveness::High {
+            return true;
+        }
+        let medium = self.aggressiveness == Aggressiveness::Medium;
+        match (self.sentences.ending(text), self.sentences.start(line)) {
+            (Ending::Sentence, _) => true,
+            // "Mr." or "pp." before a page break goes on with the name or number
+            (Ending::Title, _) => false,
+            (_, Start::Lower) => false,
+            (_, Start::Upper) => medium,
+            // without case, only an unfinished sentence tells the paragraph goes on
+            (Ending::Abbreviation, Start::Uncased) => medium,
+            (Ending::Open, Start::Uncased) => false,
+        }
+    }
+}
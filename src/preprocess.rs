@@ -0,0 +1,507 @@
+use image::{GrayImage, Luma};
+
+/// How speckle noise is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Despeckle {
+    /// 3x3 median filter, smooths everything a little
+    Median,
+    /// Drop isolated dark blobs no larger than the speckle size, leaves glyphs untouched
+    Components,
+}
+
+/// Image clean-up applied to the grayscale page right before OCR
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessOptions {
+    /// Straighten text lines curved by the book spine
+    pub dewarp: bool,
+
+    /// Lighten the dark band left by the binding on flatbed scans
+    pub remove_gutter: bool,
+
+    /// Remove speckle noise of photocopies and microfilm
+    pub despeckle: Option<Despeckle>,
+
+    /// Largest blob (in pixels) considered a speckle by `Despeckle::Components`
+    pub speckle_size: u32,
+
+    /// Suppress text showing through from the other side of thin paper
+    pub suppress_bleed_through: bool,
+}
+
+/// Run the enabled preprocessing steps on a grayscale page
+pub fn preprocess(img: GrayImage, opts: &PreprocessOptions) -> GrayImage {
+    let mut img = img;
+    if opts.remove_gutter {
+        remove_gutter(&mut img);
+    }
+    if opts.suppress_bleed_through {
+        suppress_bleed_through(&mut img);
+    }
+    match opts.despeckle {
+        Some(Despeckle::Median) => img = median_filter(&img),
+        Some(Despeckle::Components) => remove_speckles(&mut img, opts.speckle_size),
+        None => {}
+    }
+    if opts.dewarp {
+        img = dewarp(&img);
+    }
+    img
+}
+
+/// Otsu's threshold: the gray level that best separates ink from paper
+pub fn otsu_threshold(img: &GrayImage) -> u8 {
+    let mut hist = [0u64; 256];
+    for p in img.pixels() {
+        hist[p[0] as usize] += 1;
+    }
+    let total = img.pixels().len() as f64;
+    let sum_all: f64 = hist.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+
+    let (mut best, mut best_var) = (128u8, 0.0f64);
+    let (mut w_bg, mut sum_bg) = (0.0f64, 0.0f64);
+    for (t, &n) in hist.iter().enumerate() {
+        w_bg += n as f64;
+        if w_bg == 0.0 {
+            continue;
+        }
+        let w_fg = total - w_bg;
+        if w_fg == 0.0 {
+            break;
+        }
+        sum_bg += t as f64 * n as f64;
+        let mean_bg = sum_bg / w_bg;
+        let mean_fg = (sum_all - sum_bg) / w_fg;
+        let var = w_bg * w_fg * (mean_bg - mean_fg).powi(2);
+        if var > best_var {
+            best_var = var;
+            best = t as u8;
+        }
+    }
+    best
+}
+
+/// Number of vertical strips the page is cut into to follow curved lines
+const DEWARP_STRIPS: u32 = 16;
+
+/// Centers (y) of the text lines crossing the vertical strip `[x0, x1)`
+fn strip_line_centers(img: &GrayImage, threshold: u8, x0: u32, x1: u32) -> Vec<f32> {
+    let min_ink = ((x1 - x0) / 50).max(1);
+    let mut centers = Vec::new();
+    let mut run: Option<(u32, u64, u64)> = None; // (start, weight, weighted y)
+
+    for y in 0..img.height() {
+        let ink = (x0..x1).filter(|&x| img.get_pixel(x, y)[0] <= threshold).count() as u64;
+        if ink >= min_ink as u64 {
+            let r = run.get_or_insert((y, 0, 0));
+            r.1 += ink;
+            r.2 += ink * y as u64;
+        } else if let Some((start, weight, wy)) = run.take() {
+            // a couple of pixel rows is noise or a rule, not a text line
+            if y - start >= 3 {
+                centers.push(wy as f32 / weight as f32);
+            }
+        }
+    }
+    centers
+}
+
+/// Least squares fit of `y = a x^2 + b x + c`
+fn fit_quadratic(points: &[(f32, f32)]) -> [f64; 3] {
+    let mut m = [[0.0f64; 4]; 3];
+    for &(x, y) in points {
+        let (x, y) = (x as f64, y as f64);
+        let row = [x * x, x, 1.0];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += row[i] * row[j];
+            }
+            m[i][3] += row[i] * y;
+        }
+    }
+    // Gaussian elimination on the 3x3 normal equations
+    for col in 0..3 {
+        let pivot = (col..3)
+            .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+            .expect("pivot row");
+        m.swap(col, pivot);
+        if m[col][col].abs() < 1e-12 {
+            return [0.0, 0.0, points.first().map(|p| p.1 as f64).unwrap_or(0.0)];
+        }
+        let pivot_row = m[col];
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col {
+                let f = row[col] / pivot_row[col];
+                for (v, p) in row.iter_mut().zip(pivot_row.iter()).skip(col) {
+                    *v -= f * p;
+                }
+            }
+        }
+    }
+    [m[0][3] / m[0][0], m[1][3] / m[1][1], m[2][3] / m[2][2]]
+}
+
+/// Straighten curved text lines (photographed or spine-bound scans).
+///
+/// Text line centers are measured in vertical strips and chained across the page,
+/// each chain is fitted with a parabola, and every column is then shifted vertically
+/// so that the fitted lines become horizontal at the height they have in the middle
+/// of the page. Rows between lines interpolate the neighbouring lines' shifts.
+pub fn dewarp(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    if w < DEWARP_STRIPS * 4 || h < 16 {
+        return img.clone();
+    }
+    let threshold = otsu_threshold(img);
+    let strip_w = w / DEWARP_STRIPS;
+    let strips: Vec<(f32, Vec<f32>)> = (0..DEWARP_STRIPS)
+        .map(|s| {
+            let x0 = s * strip_w;
+            ((x0 + strip_w / 2) as f32, strip_line_centers(img, threshold, x0, x0 + strip_w))
+        })
+        .collect();
+
+    // typical distance between lines bounds how far a line may move between strips
+    let mid = strips.len() / 2;
+    let spacing = {
+        let c = &strips[mid].1;
+        let mut gaps: Vec<f32> = c.windows(2).map(|p| p[1] - p[0]).collect();
+        gaps.sort_by(f32::total_cmp);
+        match gaps.get(gaps.len() / 2) {
+            Some(&g) => g,
+            None => return img.clone(),
+        }
+    };
+    let max_jump = spacing * 0.5;
+
+    // follow every line found in the middle strip outwards in both directions
+    let mut curves: Vec<(f32, [f64; 3])> = Vec::new();
+    for &seed in &strips[mid].1 {
+        let mut points = vec![(strips[mid].0, seed)];
+        for range in [(0..mid).rev().collect::<Vec<_>>(), ((mid + 1)..strips.len()).collect()] {
+            let mut y = seed;
+            for s in range {
+                let next = strips[s].1
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| (a - y).abs().total_cmp(&(b - y).abs()));
+                match next {
+                    Some(n) if (n - y).abs() <= max_jump => {
+                        points.push((strips[s].0, n));
+                        y = n;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        if points.len() as u32 >= DEWARP_STRIPS / 2 {
+            curves.push((seed, fit_quadratic(&points)));
+        }
+    }
+    if curves.is_empty() {
+        return img.clone();
+    }
+    curves.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let x_mid = strips[mid].0 as f64;
+    let eval = |c: &[f64; 3], x: f64| c[0] * x * x + c[1] * x + c[2];
+    let mut out = GrayImage::from_pixel(w, h, Luma([255]));
+    for x in 0..w {
+        // (target y, shift) per line for this column
+        let shifts: Vec<(f32, f32)> = curves
+            .iter()
+            .map(|(_, c)| {
+                let target = eval(c, x_mid) as f32;
+                (target, (eval(c, x as f64) - eval(c, x_mid)) as f32)
+            })
+            .collect();
+
+        let mut i = 0;
+        for y in 0..h {
+            let yf = y as f32;
+            while i + 1 < shifts.len() && shifts[i + 1].0 <= yf {
+                i += 1;
+            }
+            let shift = if yf <= shifts[0].0 {
+                shifts[0].1
+            } else if i + 1 >= shifts.len() {
+                shifts[shifts.len() - 1].1
+            } else {
+                let (t0, d0) = shifts[i];
+                let (t1, d1) = shifts[i + 1];
+                let f = if t1 > t0 { (yf - t0) / (t1 - t0) } else { 0.0 };
+                d0 + (d1 - d0) * f
+            };
+
+            // linear interpolation between the two source rows
+            let src = yf + shift;
+            if src < 0.0 || src > (h - 1) as f32 {
+                continue;
+            }
+            let y0 = src.floor() as u32;
+            let y1 = (y0 + 1).min(h - 1);
+            let f = src - y0 as f32;
+            let v = img.get_pixel(x, y0)[0] as f32 * (1.0 - f) + img.get_pixel(x, y1)[0] as f32 * f;
+            out.put_pixel(x, y, Luma([v.round() as u8]));
+        }
+    }
+    out
+}
+
+/// Columns whose paper is this much darker than the page's are in shadow
+const GUTTER_DELTA: u8 = 30;
+
+/// Paper level (90th percentile of gray) of every column
+fn column_background(img: &GrayImage) -> Vec<u8> {
+    let step = (img.height() / 400).max(1);
+    (0..img.width())
+        .map(|x| {
+            let mut col: Vec<u8> = (0..img.height()).step_by(step as usize).map(|y| img.get_pixel(x, y)[0]).collect();
+            let k = col.len() * 9 / 10;
+            *col.select_nth_unstable(k).1
+        })
+        .collect()
+}
+
+/// Detect and remove the gutter shadow of bound-book scans.
+///
+/// The paper level of each column is estimated (ink is too sparse to move it), and
+/// runs of columns markedly darker than the page near the left/right edges or the
+/// center are flattened back to the page's paper level. Text in the shadow survives,
+/// solid black binding is whitened.
+pub fn remove_gutter(img: &mut GrayImage) {
+    let (w, h) = img.dimensions();
+    if w < 20 {
+        return;
+    }
+    let bg = column_background(img);
+    let mut sorted = bg.clone();
+    sorted.sort_unstable();
+    let paper = sorted[sorted.len() / 2];
+    let limit = paper.saturating_sub(GUTTER_DELTA);
+
+    let in_gutter_zone = |start: u32, end: u32| {
+        start < w * 15 / 100 || end > w * 85 / 100 || (start <= w / 2 && end >= w / 2)
+    };
+
+    let mut x = 0;
+    while x < w {
+        if bg[x as usize] >= limit {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < w && bg[x as usize] < limit {
+            x += 1;
+        }
+        if x - start < (w / 200).max(2) || !in_gutter_zone(start, x) {
+            continue;
+        }
+        for cx in start..x {
+            let b = bg[cx as usize];
+            for y in 0..h {
+                let v = img.get_pixel(cx, y)[0];
+                let fixed = if b < 50 {
+                    255
+                } else {
+                    ((v as u32 * paper as u32) / b as u32).min(255) as u8
+                };
+                img.put_pixel(cx, y, Luma([fixed]));
+            }
+        }
+    }
+}
+
+/// 3x3 median filter
+pub fn median_filter(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let mut out = img.clone();
+    for y in 1..h.saturating_sub(1) {
+        for x in 1..w.saturating_sub(1) {
+            let mut win = [0u8; 9];
+            for (i, (dx, dy)) in (0..3).flat_map(|dy| (0..3).map(move |dx| (dx, dy))).enumerate() {
+                win[i] = img.get_pixel(x + dx - 1, y + dy - 1)[0];
+            }
+            win.sort_unstable();
+            out.put_pixel(x, y, Luma([win[4]]));
+        }
+    }
+    out
+}
+
+/// Whiten connected dark components (8-connectivity) of at most `max_area` pixels
+pub fn remove_speckles(img: &mut GrayImage, max_area: u32) {
+    let (w, h) = img.dimensions();
+    let threshold = otsu_threshold(img);
+    let idx = |x: u32, y: u32| (y * w + x) as usize;
+    let mut seen = vec![false; (w * h) as usize];
+    let mut blob = Vec::new();
+    let mut stack = Vec::new();
+
+    for y in 0..h {
+        for x in 0..w {
+            if seen[idx(x, y)] || img.get_pixel(x, y)[0] > threshold {
+                continue;
+            }
+            blob.clear();
+            stack.push((x, y));
+            seen[idx(x, y)] = true;
+            while let Some((cx, cy)) = stack.pop() {
+                blob.push((cx, cy));
+                for ny in cy.saturating_sub(1)..=(cy + 1).min(h - 1) {
+                    for nx in cx.saturating_sub(1)..=(cx + 1).min(w - 1) {
+                        if !seen[idx(nx, ny)] && img.get_pixel(nx, ny)[0] <= threshold {
+                            seen[idx(nx, ny)] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+            if blob.len() as u32 <= max_area {
+                for &(bx, by) in &blob {
+                    img.put_pixel(bx, by, Luma([255]));
+                }
+            }
+        }
+    }
+}
+
+/// Side (in pixels) of the blocks used to estimate the paper background
+const BACKGROUND_BLOCK: u32 = 32;
+
+/// Estimate the paper level around every pixel: the 90th percentile gray of each
+/// block, bilinearly interpolated between block centers.
+fn estimate_background(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let (bw, bh) = (w.div_ceil(BACKGROUND_BLOCK), h.div_ceil(BACKGROUND_BLOCK));
+    let mut grid = vec![0f32; (bw * bh) as usize];
+    for by in 0..bh {
+        for bx in 0..bw {
+            let mut vals: Vec<u8> = (by * BACKGROUND_BLOCK..((by + 1) * BACKGROUND_BLOCK).min(h))
+                .flat_map(|y| (bx * BACKGROUND_BLOCK..((bx + 1) * BACKGROUND_BLOCK).min(w)).map(move |x| (x, y)))
+                .map(|(x, y)| img.get_pixel(x, y)[0])
+                .collect();
+            let k = vals.len() * 9 / 10;
+            grid[(by * bw + bx) as usize] = *vals.select_nth_unstable(k).1 as f32;
+        }
+    }
+
+    let half = BACKGROUND_BLOCK as f32 / 2.0;
+    GrayImage::from_fn(w, h, |x, y| {
+        let gx = ((x as f32 - half) / BACKGROUND_BLOCK as f32).clamp(0.0, (bw - 1) as f32);
+        let gy = ((y as f32 - half) / BACKGROUND_BLOCK as f32).clamp(0.0, (bh - 1) as f32);
+        let (x0, y0) = (gx.floor() as u32, gy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(bw - 1), (y0 + 1).min(bh - 1));
+        let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+        let g = |x: u32, y: u32| grid[(y * bw + x) as usize];
+        let top = g(x0, y0) * (1.0 - fx) + g(x1, y0) * fx;
+        let bottom = g(x0, y1) * (1.0 - fx) + g(x1, y1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy) as u8])
+    })
+}
+
+/// Suppress bleed-through (verso text showing through thin paper).
+///
+/// The page is divided by its estimated background so that paper becomes uniformly
+/// white; the faint show-through is then lighter than real ink, and everything above
+/// the Otsu threshold of the normalized page is whitened.
+pub fn suppress_bleed_through(img: &mut GrayImage) {
+    if img.width() == 0 || img.height() == 0 {
+        return;
+    }
+    let bg = estimate_background(img);
+    for (p, b) in img.pixels_mut().zip(bg.pixels()) {
+        let b = b[0].max(1) as u32;
+        p[0] = ((p[0] as u32 * 255) / b).min(255) as u8;
+    }
+    let threshold = otsu_threshold(img);
+    for p in img.pixels_mut() {
+        if p[0] > threshold {
+            p[0] = 255;
+        }
+    }
+}
+
+/// A page at least this much wider than tall may be a double-page spread
+const SPREAD_ASPECT: f32 = 1.15;
+
+/// If `img` is a double-page spread, return the x position of its fold.
+///
+/// Only landscape pages are considered. The fold is either a shadowed band
+/// (see `remove_gutter`) or an ink-free gap between the two text blocks, searched
+/// in the middle fifth of the page.
+pub fn find_spread_split(img: &GrayImage) -> Option<u32> {
+    let (w, h) = img.dimensions();
+    if (w as f32) < h as f32 * SPREAD_ASPECT || w < 20 {
+        return None;
+    }
+    let zone = (w * 2 / 5)..(w * 3 / 5);
+
+    // shadowed fold
+    let bg = column_background(img);
+    let mut sorted = bg.clone();
+    sorted.sort_unstable();
+    let paper = sorted[sorted.len() / 2];
+    let darkest = zone.clone().map(|x| bg[x as usize]).min()?;
+    if darkest < paper.saturating_sub(GUTTER_DELTA) {
+        return middle_of(zone.filter(|&x| bg[x as usize] <= darkest.saturating_add(5)));
+    }
+
+    // white gap between the two pages' text blocks
+    let threshold = otsu_threshold(img);
+    let ink: Vec<u32> = (0..w)
+        .map(|x| (0..h).step_by(2).filter(|&y| img.get_pixel(x, y)[0] <= threshold).count() as u32)
+        .collect();
+    let radius = (w / 200).max(1) as usize;
+    let smoothed = |x: usize| {
+        let lo = x.saturating_sub(radius);
+        let hi = (x + radius + 1).min(ink.len());
+        ink[lo..hi].iter().sum::<u32>() as f32 / (hi - lo) as f32
+    };
+    let inked: Vec<u32> = ink.iter().copied().filter(|&n| n > 0).collect();
+    if inked.is_empty() {
+        return None;
+    }
+    let mean_ink = inked.iter().sum::<u32>() as f32 / inked.len() as f32;
+    let least = zone.clone().map(|x| smoothed(x as usize)).min_by(f32::total_cmp)?;
+    if least >= 0.15 * mean_ink {
+        return None;
+    }
+    middle_of(zone.filter(|&x| smoothed(x as usize) <= least + 0.02 * mean_ink))
+}
+
+/// Median of a run of candidate positions
+fn middle_of(xs: impl Iterator<Item = u32>) -> Option<u32> {
+    let xs: Vec<u32> = xs.collect();
+    xs.get(xs.len() / 2).copied()
+}
+
+/// Cut a spread at `x` into its left and right pages
+pub fn split_at(img: &GrayImage, x: u32) -> [GrayImage; 2] {
+    let (w, h) = img.dimensions();
+    [
+        image::imageops::crop_imm(img, 0, 0, x, h).to_image(),
+        image::imageops::crop_imm(img, x, 0, w - x, h).to_image(),
+    ]
+}
+
+/// Cut a page at `boundaries` (fractions of its width, left to right) into its columns
+/// and stack them top to bottom, a blank band between them, so OCR reads them in order
+pub fn stack_columns(img: GrayImage, boundaries: &[f32]) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let mut edges: Vec<u32> = vec![0];
+    edges.extend(boundaries.iter().map(|b| (b * w as f32) as u32));
+    edges.push(w);
+    let columns: Vec<(u32, u32)> = edges.windows(2).map(|e| (e[0], e[1])).filter(|(l, r)| r > l).collect();
+    if columns.len() < 2 {
+        return img;
+    }
+    let gap = (h / 20).max(1);
+    let width = columns.iter().map(|(l, r)| r - l).max().unwrap_or(w);
+    let height = columns.len() as u32 * (h + gap) - gap;
+    let mut stacked = GrayImage::from_pixel(width, height, Luma([255]));
+    for (n, (left, right)) in columns.into_iter().enumerate() {
+        let column = image::imageops::crop_imm(&img, left, 0, right - left, h).to_image();
+        image::imageops::replace(&mut stacked, &column, 0, n as i64 * (h + gap) as i64);
+    }
+    stacked
+}
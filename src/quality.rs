@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+use std::path::Path;
+use crate::document::{plain_text, Block, Chapter};
+use crate::Pdf2EPubErr;
+
+/// Word list used when `--dictionary` is not given, if present
+pub const SYSTEM_DICTIONARY: &str = "/usr/share/dict/words";
+
+/// Characters per thousand outside the usual prose repertoire at which a chapter scores zero
+const MAX_SUSPICIOUS_PER_MILLE: f32 = 20.0;
+
+/// Lowercased words that count as correctly recognised
+pub struct Dictionary(HashSet<String>);
+
+impl Dictionary {
+    /// Read a word list, one word per line
+    pub fn load(path: &Path) -> Result<Dictionary, Pdf2EPubErr> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Dictionary(text.lines().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()).collect()))
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.0.contains(&word.to_lowercase())
+    }
+}
+
+/// How trustworthy the OCR of one chapter looks
+#[derive(Debug, Clone)]
+pub struct ChapterQuality {
+    /// Mean OCR word confidence (0-100)
+    pub confidence: Option<f32>,
+
+    /// Share of words found in the dictionary
+    pub dictionary_hits: Option<f32>,
+
+    /// Suspicious characters per thousand, e.g. stray symbols from misread specks
+    pub suspicious_per_mille: f32,
+}
+
+/// Letters, digits, whitespace and the punctuation of ordinary prose
+fn is_ordinary(c: char) -> bool {
+    c.is_alphanumeric() || c.is_whitespace() || ".,;:!?'\"()-–—‘’“”…&%$/".contains(c)
+}
+
+impl ChapterQuality {
+    pub fn of(chapter: &Chapter, dictionary: Option<&Dictionary>) -> ChapterQuality {
+        let text: String = chapter
+            .blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Paragraph(p) | Block::Styled(_, p) => Some(plain_text(p) + "\n"),
+                Block::Figure(_) | Block::Break => None,
+            })
+            .collect();
+
+        let chars = text.chars().count();
+        let suspicious = text.chars().filter(|&c| !is_ordinary(c)).count();
+        let suspicious_per_mille = if chars == 0 { 0.0 } else { suspicious as f32 * 1000.0 / chars as f32 };
+
+        let dictionary_hits = dictionary.and_then(|dict| {
+            let words: Vec<&str> = text
+                .split_whitespace()
+                .map(|w| w.trim_matches(|c: char| !c.is_alphabetic()))
+                .filter(|w| !w.is_empty() && w.chars().all(char::is_alphabetic))
+                .collect();
+            (!words.is_empty()).then(|| words.iter().filter(|w| dict.contains(w)).count() as f32 / words.len() as f32)
+        });
+
+        ChapterQuality { confidence: chapter.confidence, dictionary_hits, suspicious_per_mille }
+    }
+
+    /// Overall score from 0 (proofread first) to 100, averaging the available measures
+    pub fn score(&self) -> f32 {
+        let clean = 1.0 - (self.suspicious_per_mille / MAX_SUSPICIOUS_PER_MILLE).min(1.0);
+        let measures: Vec<f32> = [self.confidence.map(|c| c / 100.0), self.dictionary_hits, Some(clean)]
+            .into_iter()
+            .flatten()
+            .collect();
+        100.0 * measures.iter().sum::<f32>() / measures.len() as f32
+    }
+}
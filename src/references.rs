@@ -0,0 +1,102 @@
+//! The bibliography or reference list, one paragraph per entry instead of the
+//! blob line unwrapping makes of it
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::backmatter::Section;
+use crate::document::{Block, Inline, PageText, ParagraphStyle};
+
+/// The reference list: pages headed "References" with years on their lines
+static REFERENCES: Section = Section {
+    headings: &[
+        "references",
+        "bibliography",
+        "select bibliography",
+        "selected bibliography",
+        "works cited",
+        "literature cited",
+        "sources",
+        "further reading",
+    ],
+    entry_ratio: 0.25,
+    is_entry: |line| YEAR_RE.is_match(line),
+};
+
+static YEAR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(1[5-9]|20)\d\d[a-z]?\b").unwrap());
+
+/// "[12] ...", "12. ..."
+static NUMBERED_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\[\d{1,3}\]|\d{1,3}\.)\s").unwrap());
+
+/// "Surname, ..." at the start of an author-year entry
+static AUTHOR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\p{Lu}[\p{L}'’-]+,\s").unwrap());
+
+/// URLs and DOIs, without the punctuation that follows them in the sentence
+static LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(https?://\S*[^\s.,;:)\]])|(?i:doi:\s*)?\b(10\.\d{4,9}/\S*[^\s.,;:)\]])").unwrap()
+});
+
+/// Pages of the reference list: the last page in the second half of the book that starts
+/// with a references heading, and the reference-like pages following it
+pub fn find_references(pages: &[PageText]) -> Option<Range<usize>> {
+    REFERENCES.find(pages)
+}
+
+/// The entries of the reference list. A line starts a new entry when it is numbered,
+/// or when the entry before is finished and the line starts with an author's surname.
+pub fn parse_entries(pages: &[PageText]) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    let lines = REFERENCES.lines(pages);
+    for line in lines {
+        let starts_entry = match entries.last() {
+            None => true,
+            Some(last) => NUMBERED_RE.is_match(line) || (last.ends_with('.') && AUTHOR_RE.is_match(line)),
+        };
+        match entries.last_mut() {
+            Some(entry) if !starts_entry => {
+                // a hyphen at the end of a URL or DOI belongs to it
+                let in_link = entry.rsplit(' ').next().is_some_and(|w| w.contains('/'));
+                if entry.ends_with('-') && !in_link {
+                    entry.pop();
+                } else if !entry.ends_with('-') {
+                    entry.push(' ');
+                }
+                entry.push_str(line);
+            }
+            _ => entries.push(line.to_string()),
+        }
+    }
+    entries
+}
+
+/// Split `entry` at its URLs and DOIs, which become links
+fn link_entry(entry: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    for m in LINK_RE.captures_iter(entry) {
+        let (target, href) = match (m.get(1), m.get(2)) {
+            (Some(url), _) => (url, url.as_str().to_string()),
+            (None, Some(doi)) => (doi, format!("https://doi.org/{}", doi.as_str())),
+            (None, None) => continue,
+        };
+        if target.start() > start {
+            inlines.push(Inline::Text(entry[start..target.start()].to_string()));
+        }
+        inlines.push(Inline::Link { href, text: target.as_str().to_string() });
+        start = target.end();
+    }
+    if start < entry.len() {
+        inlines.push(Inline::Text(entry[start..].to_string()));
+    }
+    inlines
+}
+
+/// One `Reference` paragraph per entry; with `link`, URLs and DOIs become links
+pub fn reference_blocks(entries: &[String], link: bool) -> Vec<Block> {
+    entries
+        .iter()
+        .map(|e| {
+            let inlines = if link { link_entry(e) } else { vec![Inline::Text(e.clone())] };
+            Block::Styled(ParagraphStyle::Reference, inlines)
+        })
+        .collect()
+}
@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use serde_json::{json, Value};
+use crate::balance::Unbalanced;
+use crate::order::ReadingIssue;
+use crate::quality::ChapterQuality;
+use crate::stats::{reading_minutes, ReadingStats};
+use crate::Pdf2EPubErr;
+
+/// Machine-readable summary of one written book; `quality` is given per chapter of `stats`,
+/// `issues` are the book's suspected reading-order problems and `unbalanced` its paragraphs
+/// with unbalanced quotation marks or brackets
+pub fn book_report(
+    title: &str,
+    author: &str,
+    stats: &ReadingStats,
+    quality: &[ChapterQuality],
+    issues: &[ReadingIssue],
+    unbalanced: &[Unbalanced],
+) -> Value {
+    let chapters: Vec<Value> = stats
+        .chapters
+        .iter()
+        .zip(quality)
+        .map(|(c, q)| {
+            json!({
+                "title": c.title,
+                "words": c.words,
+                "reading_minutes": reading_minutes(c.words),
+                "quality": {
+                    "score": q.score(),
+                    "mean_confidence": q.confidence,
+                    "dictionary_hit_rate": q.dictionary_hits,
+                    "suspicious_chars_per_mille": q.suspicious_per_mille,
+                },
+            })
+        })
+        .collect();
+
+    // chapters with the lowest score are the ones to proofread first
+    let mut order: Vec<(&str, f32)> = stats
+        .chapters
+        .iter()
+        .zip(quality)
+        .filter(|(c, _)| c.words > 0)
+        .map(|(c, q)| (c.title.as_str(), q.score()))
+        .collect();
+    order.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let proofread_first: Vec<&str> = order.into_iter().map(|(t, _)| t).collect();
+
+    let reading_order: Vec<Value> = issues
+        .iter()
+        .map(|i| {
+            json!({
+                "kind": i.kind.name(),
+                "page": i.page,
+                "chapter": i.chapter,
+                "context": i.context,
+            })
+        })
+        .collect();
+
+    let unbalanced: Vec<Value> = unbalanced
+        .iter()
+        .map(|u| json!({ "page": u.page, "marks": u.marks, "context": u.context }))
+        .collect();
+
+    json!({
+        "title": title,
+        "author": author,
+        "words": stats.words,
+        "reading_minutes": reading_minutes(stats.words),
+        "chapters": chapters,
+        "proofread_first": proofread_first,
+        "warnings": { "reading_order": reading_order, "unbalanced": unbalanced },
+    })
+}
+
+/// Write `report` as pretty-printed JSON
+pub fn write_report(path: &Path, report: &Value) -> Result<(), Pdf2EPubErr> {
+    let text = serde_json::to_string_pretty(report).context("failed to serialize report")?;
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// A page to check by hand after the conversion
+#[derive(Debug, Clone)]
+pub struct ReviewPage {
+    /// 1-based PDF page
+    pub page: usize,
+
+    /// Half of a split spread (0 left, 1 right)
+    pub part: Option<usize>,
+
+    /// Page number printed on the page, when extracted
+    pub number: Option<u32>,
+
+    /// Mean OCR confidence (0-100); `None` when OCR failed on the page
+    pub confidence: Option<f32>,
+
+    /// Debug images of the page, see `--debug-dir`
+    pub images: Vec<PathBuf>,
+}
+
+impl ReviewPage {
+    fn label(&self) -> String {
+        let mut label = format!("page {}", self.page);
+        if let Some(part) = self.part {
+            label.push_str(if part == 0 { " (left)" } else { " (right)" });
+        }
+        if let Some(n) = self.number {
+            label.push_str(&format!(", printed {}", n));
+        }
+        label
+    }
+}
+
+/// The pages to review as JSON, worst first
+pub fn review_json(pages: &[ReviewPage], threshold: f32) -> Value {
+    let pages: Vec<Value> = pages
+        .iter()
+        .map(|p| {
+            json!({
+                "page": p.page,
+                "part": p.part,
+                "printed_number": p.number,
+                "mean_confidence": p.confidence,
+                "failed": p.confidence.is_none(),
+                "images": p.images.iter().map(|i| i.display().to_string()).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    json!({ "threshold": threshold, "pages": pages })
+}
+
+/// The pages to review, one per line, worst first
+pub fn review_text(pages: &[ReviewPage]) -> String {
+    pages
+        .iter()
+        .map(|p| {
+            let confidence = match p.confidence {
+                Some(c) => format!("{:.1}%", c),
+                None => "not recognised".to_string(),
+            };
+            let mut line = format!("{}: {}", p.label(), confidence);
+            for image in &p.images {
+                line.push_str(&format!("\t{}", image.display()));
+            }
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Write the pages to review to `path`, as JSON if it ends in `.json` and as text otherwise
+pub fn write_review(path: &Path, pages: &mut [ReviewPage], threshold: f32) -> Result<(), Pdf2EPubErr> {
+    // pages that failed outright first, then by rising confidence
+    pages.sort_by(|a, b| a.confidence.unwrap_or(-1.0).total_cmp(&b.confidence.unwrap_or(-1.0)));
+    if path.extension().is_some_and(|e| e == "json") {
+        write_report(path, &review_json(pages, threshold))
+    } else {
+        std::fs::write(path, review_text(pages))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Further tries at a page: rendering it again after a failure, and recognising it again
+//! when OCR fails or is unsure of the page, at a higher resolution or with another page
+//! segmentation mode. The policy is set by the `--retry*` flags, and with them in the
+//! settings file (`retries = 2`, `retry-dpi = [400, 600]`).
+use std::time::Duration;
+use crate::Pdf2EPubErr;
+
+/// How often and how a page is tried again
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    /// Tries after the first; none by default
+    pub retries: u32,
+
+    /// Resolutions of the further tries of recognition, in order; the last one stays for
+    /// any tries after them. Left empty, they render at the first resolution.
+    pub dpi_steps: Vec<u16>,
+
+    /// Page segmentation modes the further tries of recognition cycle through
+    pub psm_modes: Vec<u8>,
+
+    /// Wait before the first retry, doubled before each one after it
+    pub backoff: Duration,
+
+    /// Mean OCR confidence (0-100) below which a recognised page is tried again
+    pub below: Option<f32>,
+}
+
+impl RetryPolicy {
+    /// Whether try `attempt` (0 for the first) may be followed by another
+    pub fn can_retry(&self, attempt: u32) -> bool {
+        attempt < self.retries
+    }
+
+    /// Whether a page recognised with `confidence` is worth another try
+    pub fn unsure(&self, confidence: Option<f32>) -> bool {
+        matches!((self.below, confidence), (Some(below), Some(c)) if c < below)
+    }
+
+    /// Resolution of try `attempt`, where the first rendered at `dpi`
+    pub fn dpi(&self, attempt: u32, dpi: u16) -> u16 {
+        match attempt {
+            0 => dpi,
+            n => self.dpi_steps.get(n as usize - 1).or(self.dpi_steps.last()).copied().unwrap_or(dpi),
+        }
+    }
+
+    /// Page segmentation mode of try `attempt`, where the first used `psm`
+    pub fn psm(&self, attempt: u32, psm: Option<u8>) -> Option<u8> {
+        match attempt {
+            0 => psm,
+            _ if self.psm_modes.is_empty() => psm,
+            n => Some(self.psm_modes[(n as usize - 1) % self.psm_modes.len()]),
+        }
+    }
+
+    /// Wait before try `attempt`
+    pub fn wait(&self, attempt: u32) {
+        if attempt > 0 && !self.backoff.is_zero() {
+            std::thread::sleep(self.backoff * 2u32.saturating_pow(attempt - 1));
+        }
+    }
+
+    /// Run `f` (given the number of the try) until it succeeds or the retries are used
+    /// up. A missing OCR engine is not retried: no later try can find it.
+    pub fn run<T>(&self, mut f: impl FnMut(u32) -> Result<T, Pdf2EPubErr>) -> Result<T, Pdf2EPubErr> {
+        let mut attempt = 0;
+        loop {
+            match f(attempt) {
+                Err(e @ Pdf2EPubErr::OcrEngineError(_)) => return Err(e),
+                Err(_) if self.can_retry(attempt) => {
+                    attempt += 1;
+                    self.wait(attempt);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_of_each_try() {
+        let policy = RetryPolicy { dpi_steps: vec![400, 600], ..Default::default() };
+        let dpis: Vec<u16> = (0..4).map(|attempt| policy.dpi(attempt, 300)).collect();
+        assert_eq!(dpis, vec![300, 400, 600, 600]);
+        assert_eq!(RetryPolicy::default().dpi(2, 300), 300);
+    }
+
+    #[test]
+    fn segmentation_mode_of_each_try() {
+        let policy = RetryPolicy { psm_modes: vec![6, 4], ..Default::default() };
+        let modes: Vec<Option<u8>> = (0..4).map(|attempt| policy.psm(attempt, Some(3))).collect();
+        assert_eq!(modes, vec![Some(3), Some(6), Some(4), Some(6)]);
+        assert_eq!(RetryPolicy::default().psm(1, None), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Furigana: the small kana printed above kanji (beside them in vertical text) to give
+//! their reading. Tesseract recognises them as lines or words of their own, which would
+//! interleave the readings with the text; they are taken out of the OCR lines and kept
+//! as ruby annotations of the kanji they stand over.
+use std::collections::BTreeMap;
+use std::ops::Range;
+use crate::document::RubyWord;
+use crate::ocr::{OcrLine, Rect};
+
+/// Readings are at most this fraction of the size of the text they annotate
+const MAX_READING_SIZE: f32 = 0.65;
+
+/// A reading line sits at most this many base text sizes above its base line
+const MAX_GAP: f32 = 0.5;
+
+/// A reading inside a line ends at most this many base text sizes below its top
+const MAX_RAISE: f32 = 0.25;
+
+/// Readings of the characters of a base word: their byte range in it and their text
+type Readings = Vec<(Range<usize>, String)>;
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}')
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '\u{f900}'..='\u{faff}' | '々' | '〆' | 'ヶ')
+}
+
+fn is_reading(text: &str) -> bool {
+    text.chars().all(is_kana)
+}
+
+/// Whether the lines run top to bottom, as in vertical Japanese text
+fn is_vertical(lines: &[OcrLine]) -> bool {
+    let tall = lines.iter().filter(|l| l.bbox.h > 2 * l.bbox.w).count();
+    let wide = lines.iter().filter(|l| l.bbox.w > 2 * l.bbox.h).count();
+    tall > wide
+}
+
+fn median(mut values: Vec<u32>) -> u32 {
+    values.sort_unstable();
+    values.get(values.len() / 2).copied().unwrap_or(0)
+}
+
+/// `lines` with furigana taken out, and the kanji they were the readings of.
+/// Boxes are turned so that lines always run left to right and readings sit above
+/// their base text; in vertical text they stand right of it.
+pub fn split_readings(lines: &mut Vec<OcrLine>) -> Vec<RubyWord> {
+    let vertical = is_vertical(lines);
+    let page_right = lines.iter().map(|l| l.bbox.right()).max().unwrap_or(0);
+    let turn = |r: &Rect| {
+        if vertical {
+            Rect { x: r.y, y: page_right - r.right(), w: r.h, h: r.w }
+        } else {
+            *r
+        }
+    };
+    let line_size = median(lines.iter().map(|l| turn(&l.bbox).h).collect()) as f32;
+
+    // lines of nothing but small kana
+    let reading_lines: Vec<bool> = lines
+        .iter()
+        .map(|l| l.words.iter().all(|w| is_reading(&w.text)) && (turn(&l.bbox).h as f32) <= MAX_READING_SIZE * line_size)
+        .collect();
+    // (reading line, reading word, base line) of every reading found
+    let mut readings: Vec<(usize, usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if reading_lines[i] {
+            let bbox = turn(&line.bbox);
+            // the base line: the next one below, close enough and overlapping it
+            let base = lines.iter().enumerate().filter(|&(j, _)| !reading_lines[j]).find(|(_, l)| {
+                let b = turn(&l.bbox);
+                let gap = b.y as f32 - bbox.bottom() as f32;
+                b.x < bbox.right() && bbox.x < b.right() && (-(bbox.h as f32)..=MAX_GAP * b.h as f32).contains(&gap)
+            });
+            if let Some((j, _)) = base {
+                readings.extend((0..line.words.len()).map(|k| (i, k, j)));
+            }
+            continue;
+        }
+        // small kana raised above the others of the line
+        let size = median(line.words.iter().map(|w| turn(&w.bbox).h).collect()) as f32;
+        let top = median(line.words.iter().map(|w| turn(&w.bbox).y).collect()) as f32;
+        for (k, word) in line.words.iter().enumerate() {
+            let bbox = turn(&word.bbox);
+            if is_reading(&word.text)
+                && (bbox.h as f32) <= MAX_READING_SIZE * size
+                && (bbox.bottom() as f32) <= top + MAX_RAISE * size
+            {
+                readings.push((i, k, i));
+            }
+        }
+    }
+
+    // (base line, base word) → readings of its characters
+    let mut annotated: BTreeMap<(usize, usize), Readings> = BTreeMap::new();
+    let mut taken: Vec<(usize, usize)> = Vec::new();
+    for (i, k, j) in readings {
+        let reading = &lines[i].words[k];
+        let span = turn(&reading.bbox);
+        let base = lines[j]
+            .words
+            .iter()
+            .enumerate()
+            .filter(|&(m, w)| (i, m) != (j, k) && !is_reading(&w.text))
+            .find_map(|(m, w)| base_range(&w.text, turn(&w.bbox), span).map(|r| (m, r)));
+        let Some((m, range)) = base else { continue };
+        let entry = annotated.entry((j, m)).or_default();
+        match entry.iter_mut().find(|(r, _)| r.start < range.end && range.start < r.end) {
+            // a reading split in two over the same kanji
+            Some((r, text)) => {
+                *r = r.start.min(range.start)..r.end.max(range.end);
+                text.push_str(&reading.text);
+            }
+            None => entry.push((range, reading.text.clone())),
+        }
+        taken.push((i, k));
+    }
+
+    let ruby = annotated
+        .into_iter()
+        .map(|((j, m), mut readings)| {
+            readings.sort_by_key(|(r, _)| r.start);
+            RubyWord { text: lines[j].words[m].text.clone(), readings }
+        })
+        .collect();
+    for (i, line) in lines.iter_mut().enumerate() {
+        let mut k = 0;
+        line.words.retain(|_| {
+            k += 1;
+            !taken.contains(&(i, k - 1))
+        });
+    }
+    lines.retain(|l| !l.words.is_empty());
+    ruby
+}
+
+/// Byte range of the kanji of `word`, whose turned box is `bbox`, standing under the
+/// reading at `span`; the characters of a word are taken to be equally wide
+fn base_range(word: &str, bbox: Rect, span: Rect) -> Option<Range<usize>> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let width = bbox.w as f32 / chars.len() as f32;
+    // readings may run half a character past their kanji on either side
+    let (from, to) = (span.x as f32 - width / 2.0, span.right() as f32 + width / 2.0);
+    let under: Vec<usize> = (0..chars.len())
+        .filter(|&n| {
+            let centre = bbox.x as f32 + width * (n as f32 + 0.5);
+            is_kanji(chars[n].1) && (from..=to).contains(&centre)
+        })
+        .collect();
+    let (&first, &last) = (under.first()?, under.last()?);
+    if last - first + 1 != under.len() {
+        return None;
+    }
+    let end = chars.get(last + 1).map_or(word.len(), |&(b, _)| b);
+    Some(chars[first].0..end)
+}
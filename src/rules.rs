@@ -0,0 +1,60 @@
+//! User-defined substitutions applied to the OCR text of every page, before lines are
+//! unwrapped. The rules file lists them in the order they are applied:
+//!
+//! ```toml
+//! [[rule]]
+//! pattern = "\\bHarrv\\b"
+//! replace = "Harry"
+//!
+//! [[rule]]
+//! pattern = "(?m)^Chapter (\\d+)$"
+//! replace = "CHAPTER $1"
+//! ```
+//!
+//! `replace` may refer to capture groups as `$1` or `${name}`.
+use std::path::Path;
+use regex::Regex;
+use toml::{Table, Value};
+use crate::Pdf2EPubErr;
+
+/// Ordered regex substitutions
+#[derive(Debug, Clone, Default)]
+pub struct Rules(Vec<(Regex, String)>);
+
+impl Rules {
+    pub fn load(path: &Path) -> Result<Rules, Pdf2EPubErr> {
+        let err = |msg: String| Pdf2EPubErr::RulesError(format!("{}: {}", path.display(), msg));
+        let table: Table = std::fs::read_to_string(path)?.parse().map_err(|e: toml::de::Error| err(e.to_string()))?;
+        let rules = match table.get("rule") {
+            None => return Ok(Rules::default()),
+            Some(Value::Array(rules)) => rules,
+            Some(_) => return Err(err("`rule` must be an array of [[rule]] tables".to_string())),
+        };
+        rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let field = |name: &str| {
+                    rule.as_table()
+                        .and_then(|t| t.get(name))
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| err(format!("rule {} needs a string `{}`", i + 1, name)))
+                };
+                let pattern = Regex::new(field("pattern")?).map_err(|e| err(format!("rule {}: {}", i + 1, e)))?;
+                Ok((pattern, field("replace")?.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Rules)
+    }
+
+    /// `text` with every rule applied in turn
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (pattern, replacement) in &self.0 {
+            if let std::borrow::Cow::Owned(replaced) = pattern.replace_all(&text, replacement.as_str()) {
+                text = replaced;
+            }
+        }
+        text
+    }
+}
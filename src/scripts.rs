@@ -0,0 +1,228 @@
+//! Pages mixing writing systems: Greek or Cyrillic quotations in a Latin text, the
+//! headwords of a bilingual dictionary. Pages can be recognised with languages of
+//! their own (`--page-lang`), and runs of words in another script than the book's are
+//! tagged with their language (`--script-runs`).
+use std::ops::{Range, RangeInclusive};
+use crate::document::{Block, Inline};
+use crate::ocr;
+use crate::pagelayout::parse_page_range;
+
+/// A writing system, as far as telling runs of text apart needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WritingScript {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Tamil,
+    Thai,
+    Georgian,
+    Hangul,
+    /// Chinese characters and the Japanese kana
+    Han,
+}
+
+/// The scripts of some Tesseract languages
+const LANGUAGE_SCRIPTS: &[(&str, WritingScript)] = &[
+    ("eng", WritingScript::Latin), ("deu", WritingScript::Latin), ("fra", WritingScript::Latin),
+    ("spa", WritingScript::Latin), ("ita", WritingScript::Latin), ("por", WritingScript::Latin),
+    ("nld", WritingScript::Latin), ("swe", WritingScript::Latin), ("dan", WritingScript::Latin),
+    ("nor", WritingScript::Latin), ("fin", WritingScript::Latin), ("pol", WritingScript::Latin),
+    ("ces", WritingScript::Latin), ("tur", WritingScript::Latin), ("lat", WritingScript::Latin),
+    ("ell", WritingScript::Greek), ("grc", WritingScript::Greek),
+    ("rus", WritingScript::Cyrillic), ("ukr", WritingScript::Cyrillic), ("bel", WritingScript::Cyrillic),
+    ("bul", WritingScript::Cyrillic), ("srp", WritingScript::Cyrillic), ("mkd", WritingScript::Cyrillic),
+    ("hye", WritingScript::Armenian), ("heb", WritingScript::Hebrew), ("yid", WritingScript::Hebrew),
+    ("ara", WritingScript::Arabic), ("fas", WritingScript::Arabic), ("urd", WritingScript::Arabic),
+    ("hin", WritingScript::Devanagari), ("mar", WritingScript::Devanagari), ("nep", WritingScript::Devanagari),
+    ("san", WritingScript::Devanagari), ("ben", WritingScript::Bengali), ("asm", WritingScript::Bengali),
+    ("tam", WritingScript::Tamil), ("tha", WritingScript::Thai), ("kat", WritingScript::Georgian),
+    ("kor", WritingScript::Hangul), ("chi_sim", WritingScript::Han), ("chi_tra", WritingScript::Han),
+    ("jpn", WritingScript::Han), ("jpn_vert", WritingScript::Han),
+];
+
+impl WritingScript {
+    /// The script of letter `c`; `None` for digits, punctuation and symbols
+    pub fn of(c: char) -> Option<WritingScript> {
+        use WritingScript::*;
+        Some(match c {
+            'A'..='Z' | 'a'..='z' | 'ª' | 'º' => Latin,
+            '\u{00c0}'..='\u{024f}' if c != '×' && c != '÷' => Latin,
+            '\u{1e00}'..='\u{1eff}' => Latin,
+            '\u{0370}'..='\u{03ff}' | '\u{1f00}'..='\u{1fff}' if c.is_alphabetic() => Greek,
+            '\u{0400}'..='\u{052f}' => Cyrillic,
+            '\u{0530}'..='\u{058f}' if c.is_alphabetic() => Armenian,
+            '\u{0590}'..='\u{05ff}' if c.is_alphabetic() => Hebrew,
+            '\u{0600}'..='\u{06ff}' | '\u{0750}'..='\u{077f}' if c.is_alphabetic() => Arabic,
+            '\u{0900}'..='\u{097f}' if c.is_alphabetic() => Devanagari,
+            '\u{0980}'..='\u{09ff}' if c.is_alphabetic() => Bengali,
+            '\u{0b80}'..='\u{0bff}' if c.is_alphabetic() => Tamil,
+            '\u{0e00}'..='\u{0e7f}' if c.is_alphabetic() => Thai,
+            '\u{10a0}'..='\u{10ff}' => Georgian,
+            '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => Hangul,
+            '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => Han,
+            _ => return None,
+        })
+    }
+
+    /// The script most letters of `word` are in
+    pub fn of_word(word: &str) -> Option<WritingScript> {
+        let mut counts: Vec<(WritingScript, usize)> = Vec::new();
+        for script in word.chars().filter_map(WritingScript::of) {
+            match counts.iter_mut().find(|(s, _)| *s == script) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((script, 1)),
+            }
+        }
+        counts.into_iter().max_by_key(|&(_, n)| n).map(|(s, _)| s)
+    }
+
+    /// The script of Tesseract language `code`, when known
+    pub fn of_language(code: &str) -> Option<WritingScript> {
+        LANGUAGE_SCRIPTS.iter().find(|(c, _)| *c == code).map(|&(_, s)| s)
+    }
+
+    /// ISO 15924 code of the script
+    fn code(self) -> &'static str {
+        use WritingScript::*;
+        match self {
+            Latin => "Latn",
+            Greek => "Grek",
+            Cyrillic => "Cyrl",
+            Armenian => "Armn",
+            Hebrew => "Hebr",
+            Arabic => "Arab",
+            Devanagari => "Deva",
+            Bengali => "Beng",
+            Tamil => "Taml",
+            Thai => "Thai",
+            Georgian => "Geor",
+            Hangul => "Hang",
+            Han => "Hani",
+        }
+    }
+}
+
+/// Tesseract languages for some pages of the book, see `--page-lang`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLanguages {
+    /// PDF pages (1-based, inclusive)
+    pub pages: RangeInclusive<usize>,
+
+    /// Tesseract language(s), e.g. `eng+ell`
+    pub languages: String,
+}
+
+/// Parse a `FIRST-LAST=LANGS` (or `PAGE=LANGS`) argument
+pub fn parse_page_languages(arg: &str) -> Result<PageLanguages, String> {
+    let (range, languages) = arg.split_once('=').ok_or_else(|| format!("expected PAGES=LANGS, got `{}`", arg))?;
+    let pages = parse_page_range(range)?;
+    if languages.trim().is_empty() {
+        return Err(format!("no languages given for pages `{}`", range));
+    }
+    Ok(PageLanguages { pages, languages: languages.trim().to_string() })
+}
+
+/// The languages to recognise PDF page `page` with, when given; later ranges win
+pub fn page_languages(specs: &[PageLanguages], page: usize) -> Option<&str> {
+    specs.iter().rev().find(|s| s.pages.contains(&page)).map(|s| s.languages.as_str())
+}
+
+/// Tags the runs of words in another script than the book's with their language
+#[derive(Debug, Clone)]
+pub struct ScriptRuns {
+    /// The script of the book's language
+    main: WritingScript,
+
+    /// Language tags of the other scripts, from the languages the book was recognised with
+    tags: Vec<(WritingScript, String)>,
+}
+
+impl ScriptRuns {
+    /// Runs of a book in language `lang` (a BCP 47 tag), recognised with the Tesseract
+    /// `languages` (`eng+ell`, one entry per `--ocr-lang` and `--page-lang`)
+    pub fn new<'a>(lang: &str, languages: impl IntoIterator<Item = &'a str>) -> Self {
+        let main = ocr::tesseract_language(lang).and_then(WritingScript::of_language).unwrap_or(WritingScript::Latin);
+        let mut tags: Vec<(WritingScript, String)> = Vec::new();
+        for code in languages.into_iter().flat_map(|l| l.split('+')) {
+            if let (Some(script), Some(tag)) = (WritingScript::of_language(code), ocr::language_tag(code)) {
+                if script != main && !tags.iter().any(|(s, _)| *s == script) {
+                    tags.push((script, tag.to_string()));
+                }
+            }
+        }
+        Self { main, tags }
+    }
+
+    /// The script of the book
+    pub fn main(&self) -> WritingScript {
+        self.main
+    }
+
+    /// Language tag of text in `script`: the language it was recognised as, or else the
+    /// undetermined language in that script (`und-Grek`)
+    fn tag(&self, script: WritingScript) -> String {
+        match self.tags.iter().find(|(s, _)| *s == script) {
+            Some((_, tag)) => tag.clone(),
+            None => format!("und-{}", script.code()),
+        }
+    }
+
+    /// Byte ranges of the runs of `text` in another script and their scripts. Runs span
+    /// the letters of consecutive words of one script, with numbers and punctuation
+    /// between them; a word of the book's script ends them.
+    fn runs(&self, text: &str) -> Vec<(Range<usize>, WritingScript)> {
+        let mut runs: Vec<(Range<usize>, WritingScript)> = Vec::new();
+        let mut in_run = false;
+        for word in text.split_whitespace() {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            match WritingScript::of_word(word) {
+                Some(script) if script != self.main => {
+                    // the letters of the word, without the punctuation around them
+                    let first = word.find(|c: char| WritingScript::of(c).is_some()).unwrap_or(0);
+                    let last = word.rfind(|c: char| WritingScript::of(c).is_some()).unwrap_or(0);
+                    let last = last + word[last..].chars().next().map_or(0, char::len_utf8);
+                    let letters = start + first..start + last;
+                    match runs.last_mut() {
+                        Some((run, s)) if in_run && *s == script => run.end = letters.end,
+                        _ => runs.push((letters, script)),
+                    }
+                    in_run = true;
+                }
+                Some(_) => in_run = false,
+                None => {}
+            }
+        }
+        runs
+    }
+
+    /// The paragraphs of `blocks` with their runs in other scripts as `Inline::Foreign`
+    pub fn mark_blocks(&self, mut blocks: Vec<Block>) -> Vec<Block> {
+        for block in &mut blocks {
+            let Some(inlines) = block.inlines_mut() else { continue };
+            let old = std::mem::take(inlines);
+            for inline in old {
+                let Inline::Text(text) = inline else {
+                    inlines.push(inline);
+                    continue;
+                };
+                let mut start = 0;
+                for (run, script) in self.runs(&text) {
+                    if run.start > start {
+                        inlines.push(Inline::Text(text[start..run.start].to_string()));
+                    }
+                    inlines.push(Inline::Foreign { lang: self.tag(script), text: text[run.clone()].to_string() });
+                    start = run.end;
+                }
+                if start < text.len() {
+                    inlines.push(Inline::Text(text[start..].to_string()));
+                }
+            }
+        }
+        blocks
+    }
+}
@@ -0,0 +1,186 @@
+//! Where sentences end and start, for the paragraph-break heuristics of `LineUnwrapper`
+
+/// Characters ending a sentence, in the scripts that have them
+pub const TERMINATORS: &[char] = &['.', '?', '!', '…', '。', '？', '！', '।', '॥', '؟', '۔', '።', '፧'];
+
+/// Closing quotes and brackets that may follow the terminator of a sentence
+const CLOSERS: &[char] = &['"', '\'', '”', '’', '»', '›', ')', ']', '」', '』', '）'];
+
+/// Opening quotes and brackets, dialogue dashes and the inverted marks of Spanish that
+/// may come before the first word of a sentence
+const OPENERS: &[char] = &['"', '\'', '“', '‘', '„', '«', '‹', '(', '[', '¿', '¡', '「', '『', '（', '—', '–'];
+
+/// The abbreviations of a language that end in a period, lowercase and without it
+#[derive(Debug)]
+struct Abbreviations {
+    /// BCP 47 primary language subtag
+    lang: &'static str,
+
+    /// Titles and abbreviations always followed by a name or number ("Mr.", "pp.", "e.g.")
+    titles: &'static [&'static str],
+
+    /// Other abbreviations, which also end sentences ("etc.")
+    other: &'static [&'static str],
+}
+
+const ABBREVIATIONS: &[Abbreviations] = &[
+    Abbreviations {
+        lang: "en",
+        titles: &[
+            "mr", "mrs", "ms", "messrs", "dr", "prof", "rev", "hon", "gen", "col", "capt", "lt", "sgt", "mt", "nos",
+            "vol", "vols", "pp", "ch", "chap", "fig", "figs", "cf", "e.g", "i.e", "viz", "vs",
+        ],
+        // "no.", "p.", "sec." and "st." are also words or end sentences ("said no.", "Main St.")
+        other: &[
+            "etc", "jr", "sr", "inc", "ltd", "co", "corp", "al", "ed", "eds", "ibid", "approx", "esp", "ca", "no", "p",
+            "sec", "st",
+        ],
+    },
+    Abbreviations {
+        lang: "de",
+        titles: &[
+            "hr", "fr", "dr", "prof", "st", "nr", "bd", "s", "abb", "vgl", "z.b", "d.h", "bzw", "ca", "evtl", "ggf",
+            "sog", "bspw",
+        ],
+        other: &["usw", "etc", "u.a", "u.ä", "o.ä", "ff", "f"],
+    },
+    Abbreviations {
+        lang: "fr",
+        titles: &[
+            "m", "mm", "mme", "mmes", "mlle", "mlles", "dr", "pr", "me", "st", "ste", "p", "pp", "vol", "chap", "fig",
+            "cf", "ex", "env",
+        ],
+        other: &["etc", "cie", "av", "apr"],
+    },
+    Abbreviations {
+        lang: "es",
+        titles: &[
+            "sr", "sra", "srta", "sres", "d", "dª", "dr", "dra", "lic", "ud", "uds", "vd", "vds", "p", "pág", "núm",
+            "vol", "cap", "fig", "cf", "p.ej",
+        ],
+        other: &["etc", "cía", "s.a"],
+    },
+    Abbreviations {
+        lang: "it",
+        titles: &[
+            "sig", "sigg", "sig.ra", "dott", "dott.ssa", "prof", "avv", "ing", "on", "s", "p", "pag", "n", "vol", "cap",
+            "fig", "cfr", "es",
+        ],
+        other: &["ecc", "ca"],
+    },
+    Abbreviations {
+        lang: "nl",
+        titles: &[
+            "dhr", "mevr", "mw", "dr", "prof", "ir", "mr", "drs", "ing", "st", "blz", "nr", "vol", "hfst", "fig", "bijv",
+            "d.w.z", "vgl", "zgn",
+        ],
+        other: &["enz", "etc", "o.a"],
+    },
+];
+
+/// How the text before a line break ends, as far as paragraphs are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    /// A sentence ends
+    Sentence,
+
+    /// An abbreviation that may or may not end the sentence
+    Abbreviation,
+
+    /// A title or other abbreviation that never ends a sentence
+    Title,
+
+    /// Mid-sentence
+    Open,
+}
+
+/// How the line after a line break starts, by the case of its first letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Start {
+    Lower,
+    Upper,
+
+    /// A digit, or a letter of a script without case (CJK, Arabic, Hebrew, Indic scripts)
+    Uncased,
+}
+
+/// The sentence rules of the book's language
+#[derive(Debug, Clone)]
+pub struct SentenceRules {
+    abbreviations: Option<&'static Abbreviations>,
+    terminators: Vec<char>,
+}
+
+impl Default for SentenceRules {
+    fn default() -> Self {
+        Self::for_language("en")
+    }
+}
+
+impl SentenceRules {
+    /// The rules for BCP 47 language tag `lang` (e.g. en, de, fr-CA); languages without
+    /// an abbreviation list only end sentences at terminators
+    pub fn for_language(lang: &str) -> Self {
+        let primary = lang.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+        Self { abbreviations: ABBREVIATIONS.iter().find(|a| a.lang == primary), terminators: TERMINATORS.to_vec() }
+    }
+
+    /// End sentences at the characters of `terminators` instead of `TERMINATORS`
+    pub fn with_terminators(self, terminators: &str) -> Self {
+        Self { terminators: terminators.chars().collect(), ..self }
+    }
+
+    /// How `text` ends, looking through closing quotes and brackets (`end.”`, `fin. »`)
+    pub fn ending(&self, text: &str) -> Ending {
+        let text = text.trim_end_matches(|c: char| c.is_whitespace() || CLOSERS.contains(&c));
+        match text.chars().next_back() {
+            Some(c) if !self.terminators.contains(&c) => return Ending::Open,
+            Some('.') => {}
+            Some(_) => return Ending::Sentence,
+            None => return Ending::Open,
+        }
+        let word = text.rsplit(char::is_whitespace).next().unwrap_or_default();
+        let word = word.trim_start_matches(|c: char| !c.is_alphanumeric()).trim_end_matches('.').to_lowercase();
+        match self.abbreviations {
+            Some(a) if a.titles.contains(&word.as_str()) => Ending::Title,
+            Some(a) if a.other.contains(&word.as_str()) => Ending::Abbreviation,
+            _ => Ending::Sentence,
+        }
+    }
+
+    /// How `line` starts, looking through opening quotes and brackets (`« Oui`, `¿por qué`)
+    pub fn start(&self, line: &str) -> Start {
+        match line.chars().find(|c| !c.is_whitespace() && !OPENERS.contains(c)) {
+            Some(c) if c.is_lowercase() => Start::Lower,
+            Some(c) if c.is_uppercase() => Start::Upper,
+            _ => Start::Uncased,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_endings() {
+        let rules = SentenceRules::default();
+        assert_eq!(rules.ending("It was over."), Ending::Sentence);
+        assert_eq!(rules.ending("He said “Stop.”"), Ending::Sentence);
+        assert_eq!(rules.ending("Why?"), Ending::Sentence);
+        assert_eq!(rules.ending("asked Mr."), Ending::Title);
+        assert_eq!(rules.ending("see pp."), Ending::Title);
+        assert_eq!(rules.ending("and so on, etc."), Ending::Abbreviation);
+        assert_eq!(rules.ending("on Main St."), Ending::Abbreviation);
+        assert_eq!(rules.ending("and then"), Ending::Open);
+        assert_eq!(rules.ending(""), Ending::Open);
+    }
+
+    #[test]
+    fn endings_of_other_languages() {
+        assert_eq!(SentenceRules::for_language("de-AT").ending("siehe Abb."), Ending::Title);
+        assert_eq!(SentenceRules::for_language("fi").ending("asked Mr."), Ending::Sentence);
+        assert_eq!(SentenceRules::for_language("ja").ending("終わり。"), Ending::Sentence);
+        assert_eq!(SentenceRules::default().with_terminators("!").ending("It was over."), Ending::Open);
+    }
+}
@@ -0,0 +1,112 @@
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use crate::document::{plain_text, Block};
+use crate::sentences::{Ending, SentenceRules, Start};
+
+/// Pause inserted between paragraphs
+const PARAGRAPH_BREAK: &str = "750ms";
+
+/// Pause inserted at a section break, longer than between paragraphs
+const SECTION_BREAK: &str = "2s";
+
+/// Split a paragraph into sentences.
+/// A sentence ends where `rules` say the text before a space ends one (looking through
+/// closing quotes and brackets, and not after titles such as "Dr."), unless the word
+/// after it starts lowercase, or at the end of the paragraph.
+pub fn split_sentences<'a>(para: &'a str, rules: &SentenceRules) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (i, c) in para.char_indices() {
+        // sentences end where a run of whitespace starts
+        if !c.is_whitespace() || para[..i].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let sentence = para[start..i].trim();
+        let ends = matches!(rules.ending(sentence), Ending::Sentence | Ending::Abbreviation);
+        if ends && rules.start(&para[i..]) != Start::Lower {
+            out.push(sentence);
+            start = i;
+        }
+    }
+
+    let rest = para[start..].trim();
+    if !rest.is_empty() {
+        out.push(rest);
+    }
+    out
+}
+
+/// Render the content blocks as an SSML document for TTS engines.
+/// Each paragraph becomes a `<p>` of `<s>` sentences, separated by explicit pauses, a
+/// longer one at section breaks. Figures are not spoken.
+pub fn text_to_ssml<'a>(blocks: impl IntoIterator<Item = &'a Block>, lang: &str) -> String {
+    let rules = SentenceRules::for_language(lang);
+    let mut paras = String::new();
+    let mut section_break = false;
+    for block in blocks {
+        let text = match block {
+            Block::Paragraph(p) | Block::Styled(_, p) => plain_text(p),
+            Block::Break => {
+                section_break = true;
+                continue;
+            }
+            Block::Figure(_) => continue,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        if !paras.is_empty() {
+            let pause = if section_break { SECTION_BREAK } else { PARAGRAPH_BREAK };
+            paras.push_str(&format!("  <break time=\"{}\"/>\n", pause));
+        }
+        section_break = false;
+        let sentences = split_sentences(&text, &rules)
+            .into_iter()
+            .map(|s| format!("<s>{}</s>", encode_text(s)))
+            .collect::<String>();
+        paras.push_str(&format!("  <p>{}</p>\n", sentences));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<speak version="1.1" xmlns="http://www.w3.org/2001/10/synthesis" xml:lang="{}">
+{}</speak>
+"#,
+        encode_double_quoted_attribute(lang),
+        paras
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Inline;
+
+    #[test]
+    fn sentences() {
+        let rules = SentenceRules::for_language("en");
+        let para = "Dr. Watson came in. “Who is it?” he asked. He left, etc. The end";
+        assert_eq!(
+            split_sentences(para, &rules),
+            vec!["Dr. Watson came in.", "“Who is it?” he asked.", "He left, etc.", "The end"]
+        );
+    }
+
+    #[test]
+    fn pauses() {
+        let para = |text: &str| Block::Paragraph(vec![Inline::Text(text.to_string())]);
+        let blocks = [Block::Break, para("One."), para("Two."), Block::Break, para("Three.")];
+        let ssml = text_to_ssml(&blocks, "en\"");
+        assert!(ssml.contains(r#"xml:lang="en&quot;""#));
+        let body: Vec<&str> = ssml.lines().filter(|l| l.starts_with("  ")).map(str::trim).collect();
+        assert_eq!(
+            body,
+            vec![
+                "<p><s>One.</s></p>",
+                r#"<break time="750ms"/>"#,
+                "<p><s>Two.</s></p>",
+                r#"<break time="2s"/>"#,
+                "<p><s>Three.</s></p>",
+            ]
+        );
+    }
+}
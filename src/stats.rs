@@ -0,0 +1,80 @@
+use crate::document::{plain_text, Block, Chapter};
+
+/// Average adult silent reading speed
+const WORDS_PER_MINUTE: usize = 250;
+
+/// Length of one chapter
+#[derive(Debug, Clone)]
+pub struct ChapterLength {
+    pub title: String,
+    pub words: usize,
+}
+
+/// Word counts and reading time of a book
+#[derive(Debug, Clone, Default)]
+pub struct ReadingStats {
+    pub words: usize,
+    pub chapters: Vec<ChapterLength>,
+}
+
+/// Estimated minutes to read `words`, rounded up
+pub fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// "1,234,567"
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// "about 45 min", "about 3 h 20 min"
+fn duration(minutes: usize) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("about {} min", m),
+        (h, 0) => format!("about {} h", h),
+        (h, m) => format!("about {} h {} min", h, m),
+    }
+}
+
+impl ReadingStats {
+    /// Count the words of the paragraphs of `chapters`; captions are not counted
+    pub fn of<'a>(chapters: impl IntoIterator<Item = &'a Chapter>) -> ReadingStats {
+        let chapters: Vec<ChapterLength> = chapters
+            .into_iter()
+            .map(|c| ChapterLength {
+                title: c.title.clone(),
+                words: c
+                    .blocks
+                    .iter()
+                    .map(|b| match b {
+                        Block::Paragraph(p) | Block::Styled(_, p) => plain_text(p).split_whitespace().count(),
+                        Block::Figure(_) | Block::Break => 0,
+                    })
+                    .sum(),
+            })
+            .collect();
+        ReadingStats { words: chapters.iter().map(|c| c.words).sum(), chapters }
+    }
+
+    /// One line for the title page: "84,312 words, about 5 h 38 min of reading"
+    pub fn summary(&self) -> String {
+        format!("{} words, {} of reading", thousands(self.words), duration(reading_minutes(self.words)))
+    }
+
+    /// The summary followed by one line per chapter, for the colophon
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Length: {}", self.summary())];
+        lines.extend(self.chapters.iter().map(|c| {
+            format!("{}: {} words ({})", c.title, thousands(c.words), duration(reading_minutes(c.words)))
+        }));
+        lines
+    }
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+use crate::Pdf2EPubErr;
+
+/// Bundled stylesheets for the generated book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Theme {
+    /// Justified serif text with indented paragraphs, like a printed novel
+    ClassicSerif,
+    /// Ragged-right sans-serif text with space between paragraphs
+    ModernSans,
+    /// Tight margins and line spacing to fit more on small screens
+    Compact,
+    /// Big type and generous spacing for low-vision readers
+    LargePrint,
+}
+
+impl Theme {
+    pub fn css(self) -> &'static str {
+        match self {
+            Theme::ClassicSerif => CLASSIC_SERIF,
+            Theme::ModernSans => MODERN_SANS,
+            Theme::Compact => COMPACT,
+            Theme::LargePrint => LARGE_PRINT,
+        }
+    }
+}
+
+const CLASSIC_SERIF: &str = r#"
+body { font-family: Georgia, "Times New Roman", serif; line-height: 1.45; margin: 0 5%; }
+h1, h2 { text-align: center; font-weight: normal; font-variant: small-caps; margin: 2em 0 1em 0; }
+p { text-align: justify; text-indent: 1.5em; margin: 0; }
+figure { margin: 1.5em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption { font-size: 0.9em; font-style: italic; text-indent: 0; }
+"#;
+
+const MODERN_SANS: &str = r#"
+body { font-family: "Helvetica Neue", Arial, sans-serif; line-height: 1.5; margin: 0 5%; }
+h1, h2 { font-weight: bold; margin: 1.5em 0 0.75em 0; }
+p { text-align: left; text-indent: 0; margin: 0 0 0.9em 0; }
+figure { margin: 1.5em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption { font-size: 0.85em; color: #555; }
+"#;
+
+const COMPACT: &str = r#"
+body { font-family: serif; font-size: 0.95em; line-height: 1.25; margin: 0 2%; }
+h1, h2 { font-size: 1.2em; margin: 1em 0 0.5em 0; }
+p { text-align: justify; text-indent: 1em; margin: 0; }
+figure { margin: 0.75em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption { font-size: 0.85em; }
+"#;
+
+const LARGE_PRINT: &str = r#"
+body { font-family: Verdana, Tahoma, sans-serif; font-size: 1.4em; line-height: 1.6; margin: 0 4%; }
+h1, h2 { font-size: 1.5em; margin: 1.5em 0 1em 0; }
+p { text-align: left; text-indent: 0; margin: 0 0 1.2em 0; }
+figure { margin: 1.5em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption { font-size: 1em; }
+"#;
+
+/// Alignment of body paragraphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TextAlign {
+    /// Ragged right
+    Left,
+    /// Flush with both margins
+    Justify,
+}
+
+impl TextAlign {
+    fn css(self) -> &'static str {
+        match self {
+            TextAlign::Left => "left",
+            TextAlign::Justify => "justify",
+        }
+    }
+}
+
+/// A font file embedded in the book and used as the default body face
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    /// File name inside the epub's `fonts/` directory
+    pub file_name: String,
+
+    /// Family name the stylesheet refers to the font by
+    pub family: String,
+
+    pub data: Vec<u8>,
+    pub mime: &'static str,
+}
+
+impl EmbeddedFont {
+    /// Read a TrueType, OpenType or WOFF font; its family is named after the file
+    pub fn load(path: &Path) -> Result<EmbeddedFont, Pdf2EPubErr> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+        let mime = match ext.as_str() {
+            "ttf" => "font/ttf",
+            "otf" => "font/otf",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            _ => return Err(Pdf2EPubErr::FontError(format!("{}: not a .ttf, .otf or .woff font", path.display()))),
+        };
+        let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let family = path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(EmbeddedFont { file_name, family, data: std::fs::read(path)?, mime })
+    }
+
+    /// Path of the font inside the epub, relative to the stylesheet
+    pub fn epub_path(&self) -> String {
+        format!("fonts/{}", self.file_name)
+    }
+}
+
+/// Styling options from the command line
+#[derive(Debug, Clone, Copy)]
+pub struct StyleOptions<'a> {
+    pub theme: Option<Theme>,
+    pub font: Option<&'a EmbeddedFont>,
+
+    /// Paragraph alignment, overriding the theme's
+    pub text_align: Option<TextAlign>,
+
+    /// Let the reading system hyphenate words, in the language of the `lang` attribute
+    pub hyphenate: bool,
+
+    /// Highlight words marked as uncertain OCR results
+    pub mark_uncertain: bool,
+
+    /// Set the entries of the reference list with a hanging indent
+    pub references: bool,
+
+    /// Set notes smaller than the body text
+    pub notes: bool,
+
+    /// Set epigraphs, their attributions and the dedication apart from the body text
+    pub epigraphs: bool,
+}
+
+/// Assemble the book's stylesheet from the theme, the embedded font and the
+/// paragraph settings; `font_url` tells where the stylesheet finds the font data.
+/// Returns `None` when nothing is set, leaving the reader's defaults alone.
+pub fn stylesheet(opts: &StyleOptions, font_url: impl Fn(&EmbeddedFont) -> String) -> Option<String> {
+    if opts.theme.is_none() && opts.font.is_none() && opts.text_align.is_none() && !opts.hyphenate && !opts.mark_uncertain
+        && !opts.references
+        && !opts.notes
+        && !opts.epigraphs
+    {
+        return None;
+    }
+    let mut css = opts.theme.map(Theme::css).unwrap_or_default().to_string();
+    if let Some(font) = opts.font {
+        css.push_str(&format!(
+            "@font-face {{ font-family: \"{family}\"; src: url(\"{url}\"); }}\nbody {{ font-family: \"{family}\", serif; }}\n",
+            family = font.family,
+            url = font_url(font),
+        ));
+    }
+    if let Some(align) = opts.text_align {
+        css.push_str(&format!("p {{ text-align: {}; }}\n", align.css()));
+    }
+    if opts.hyphenate {
+        css.push_str("p { -webkit-hyphens: auto; -epub-hyphens: auto; hyphens: auto; }\n");
+    }
+    if opts.mark_uncertain {
+        // the underline also shows on e-ink screens that drop the background
+        css.push_str(".ocr-uncertain { background-color: #fff0b3; text-decoration: underline dotted; }\n");
+    }
+    if opts.references {
+        css.push_str(".reference { text-align: left; text-indent: -2em; margin: 0 0 0.5em 2em; }\n");
+    }
+    if opts.notes {
+        css.push_str(".note { font-size: 0.85em; text-indent: 0; margin: 0.5em 0; }\n");
+    }
+    if opts.epigraphs {
+        css.push_str(".epigraph { font-style: italic; text-indent: 0; margin: 1em 2em 0 2em; }\n");
+        css.push_str(".attribution { text-align: right; text-indent: 0; margin: 0 2em 2em 2em; }\n");
+        css.push_str(".dedication { font-style: italic; text-align: center; text-indent: 0; margin-top: 30%; }\n");
+    }
+    Some(css)
+}
@@ -0,0 +1,33 @@
+//! Small images of every page of a PDF (`pdf2epub thumbs`), to find one's way around a
+//! scan: for the review of its pages, or to pick the page to make the cover of
+use std::path::{Path, PathBuf};
+use pdfium_render::prelude::*;
+use crate::{img_source_from_page, Pdf2EPubErr};
+
+/// Width of a thumbnail, in pixels, unless asked otherwise
+pub const DEFAULT_WIDTH: u32 = 200;
+
+/// The thumbnail of PDF page `page` (1-based) in `dir`: `page-0012.png`
+pub fn thumbnail_path(dir: &Path, page: usize) -> PathBuf {
+    dir.join(format!("page-{:04}.png", page))
+}
+
+/// Resolution at which `page` renders `width` pixels wide, however small the page
+fn thumbnail_dpi(page: &PdfPage, width: u32) -> u16 {
+    let inches = page.paper_size().width().to_inches().max(0.1);
+    (width as f32 / inches).round().clamp(1.0, u16::MAX as f32) as u16
+}
+
+/// Render the first `max_pages` pages of `pdf` (all when `None`) `width` pixels wide into
+/// `dir`, in color; returns the number of pages rendered
+pub fn render_thumbnails(pdf: &PdfDocument, dir: &Path, width: u32, max_pages: Option<usize>) -> Result<usize, Pdf2EPubErr> {
+    std::fs::create_dir_all(dir)?;
+    let mut count = 0;
+    for (index, page) in pdf.pages().iter().take(max_pages.unwrap_or(usize::MAX)).enumerate() {
+        let img = img_source_from_page(&page, thumbnail_dpi(&page, width), false)?;
+        let path = thumbnail_path(dir, index + 1);
+        img.save(&path).map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))?;
+        count += 1;
+    }
+    Ok(count)
+}
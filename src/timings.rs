@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::metrics::Metrics;
+
+/// Wall-clock time spent in each stage of the pipeline, in the order stages first ran
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    stages: Vec<(&'static str, Duration)>,
+
+    /// Also record every stage run here, for the `/metrics` endpoint
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Timings {
+    pub fn with_metrics(metrics: Arc<Metrics>) -> Timings {
+        Timings { stages: Vec::new(), metrics: Some(metrics) }
+    }
+
+    /// Add `elapsed` to the time of `stage`
+    pub fn add(&mut self, stage: &'static str, elapsed: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_stage(stage, elapsed);
+        }
+        match self.stages.iter_mut().find(|(s, _)| *s == stage) {
+            Some((_, total)) => *total += elapsed,
+            None => self.stages.push((stage, elapsed)),
+        }
+    }
+
+    /// Run `f`, counting its duration towards `stage`
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let out = f();
+        self.add(stage, start.elapsed());
+        out
+    }
+
+    /// The stages in the order they first ran, with their time
+    pub fn stages(&self) -> &[(&'static str, Duration)] {
+        &self.stages
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// A table of the total and per-page time of each stage and its share of the whole
+    pub fn summary(&self, pages: usize) -> String {
+        let total = self.total().as_secs_f64().max(f64::EPSILON);
+        let width = self.stages.iter().map(|(s, _)| s.len()).max().unwrap_or(0).max("total".len());
+        let row = |name: &str, d: Duration| {
+            format!(
+                "{:<width$}  {:>9.2} s  {:>9.1} ms/page  {:>5.1}%\n",
+                name,
+                d.as_secs_f64(),
+                d.as_secs_f64() * 1000.0 / pages.max(1) as f64,
+                100.0 * d.as_secs_f64() / total,
+                width = width,
+            )
+        };
+        let mut out: String = self.stages.iter().map(|(s, d)| row(s, *d)).collect();
+        out.push_str(&row("total", self.total()));
+        out
+    }
+}
@@ -0,0 +1,235 @@
+//! Chapter titles as printed made presentable: ALL CAPS headings set in title case,
+//! and bare numbers ("XIV", "14") turned into "Chapter 14"
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::document::{plain_text, Block, Inline};
+
+/// Words left lowercase inside a title: articles, conjunctions and short prepositions
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "but", "or", "nor", "for", "so", "yet", "as", "at", "by", "in", "of", "off", "on",
+    "per", "to", "up", "via", "vs",
+];
+
+/// Blocks at the start of a chapter looked through for its printed title
+const HEADING_SEARCH: usize = 3;
+
+/// A well-formed roman numeral, in capitals
+static ROMAN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^M{0,4}(CM|CD|D?C{0,3})(XC|XL|L?X{0,3})(IX|IV|V?I{0,3})$").unwrap());
+
+/// Capitalise the first letter of `word` and lowercase the rest, each part of a
+/// hyphenated word on its own ("WELL-KNOWN" → "Well-Known", "“DON'T" → "“Don't")
+fn capitalise(word: &str) -> String {
+    word.split('-')
+        .map(|part| {
+            let mut first = true;
+            part.chars()
+                .flat_map(|c| {
+                    let upper = first && c.is_alphabetic();
+                    first &= !c.is_alphabetic();
+                    if upper { c.to_uppercase().collect::<Vec<_>>() } else { c.to_lowercase().collect() }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// `title` in title case when it is all capitals, else unchanged. Small words stay
+/// lowercase except first, last and after a colon or dash; roman numerals stay capitals.
+pub fn title_case(title: &str) -> String {
+    if title.chars().any(char::is_lowercase) || !title.chars().any(char::is_uppercase) {
+        return title.to_string();
+    }
+    let words: Vec<&str> = title.split_whitespace().collect();
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+        let after_break = i > 0 && words[i - 1].ends_with([':', '—', '–']);
+        let inner = i > 0 && i + 1 < words.len() && !after_break;
+        out.push(if !core.is_empty() && ROMAN_RE.is_match(core) {
+            word.to_string()
+        } else if inner && SMALL_WORDS.contains(&core.to_lowercase().as_str()) {
+            word.to_lowercase()
+        } else {
+            capitalise(word)
+        });
+    }
+    out.join(" ")
+}
+
+/// How chapter numbers are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Numerals {
+    /// 14
+    #[default]
+    Arabic,
+    /// XIV
+    Roman,
+    /// Fourteen
+    Words,
+}
+
+/// Titles already numbered: "Chapter One", "Part 2", "3. The Sea", "IV: Home". A roman
+/// numeral in capitals needs a period or colon after it, or to be the whole title, so that
+/// "I Remember" or "MIX TAPES" are not taken for numbered.
+static NUMBERED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i:(chapter|part|book|section|chapitre|kapitel|cap[ií]tulo)\b|\d+\b)|^[IVXLCDM]+(?:[.:]|$)").unwrap()
+});
+
+const ONES: &[&str] = &[
+    "", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+    "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+
+const TENS: &[&str] = &["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+/// The value of roman numeral `s`, when well-formed
+pub fn parse_roman(s: &str) -> Option<u32> {
+    let s = s.to_uppercase();
+    if s.is_empty() || !ROMAN_RE.is_match(&s) {
+        return None;
+    }
+    let value = |c| match c {
+        'I' => 1,
+        'V' => 5,
+        'X' => 10,
+        'L' => 50,
+        'C' => 100,
+        'D' => 500,
+        _ => 1000,
+    };
+    let digits: Vec<i64> = s.chars().map(value).collect();
+    // a digit before a larger one is subtracted: IV, XC
+    let sum = digits.iter().enumerate().fold(0, |sum, (i, &d)| {
+        if digits.get(i + 1).is_some_and(|&next| next > d) { sum - d } else { sum + d }
+    });
+    u32::try_from(sum).ok()
+}
+
+/// The number of a title that is nothing but one: "14", "14.", "XIV". Roman numerals are
+/// only read in capitals, as words such as "Mix" or "Dim" are well-formed numerals too.
+fn bare_number(title: &str) -> Option<u32> {
+    let title = title.trim().trim_end_matches(['.', ':']);
+    let roman = || parse_roman(title).filter(|_| title.chars().all(|c| c.is_ascii_uppercase()));
+    title.parse().ok().or_else(roman)
+}
+
+fn roman(mut n: u32) -> String {
+    const NUMERALS: &[(u32, &str)] = &[
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// `n` in English words, capitalised; numbers from 1000 on stay digits
+fn words(n: u32) -> String {
+    fn below_hundred(n: u32) -> String {
+        match n {
+            0..=19 => ONES[n as usize].to_string(),
+            _ if n.is_multiple_of(10) => TENS[n as usize / 10].to_string(),
+            _ => format!("{}-{}", TENS[n as usize / 10], ONES[n as usize % 10]),
+        }
+    }
+    let text = match n {
+        0 => "zero".to_string(),
+        1..=99 => below_hundred(n),
+        100..=999 if n.is_multiple_of(100) => format!("{} hundred", ONES[n as usize / 100]),
+        100..=999 => format!("{} hundred and {}", ONES[n as usize / 100], below_hundred(n % 100)),
+        _ => return n.to_string(),
+    };
+    let mut chars = text.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// Numbers the chapters of a book in order, see `--chapter-numbers`
+#[derive(Debug, Clone)]
+pub struct ChapterNumbering {
+    /// The title of a numbered chapter, `{n}` standing for its number
+    template: String,
+    numerals: Numerals,
+
+    /// Number chapters whose titles have no number too, as "TEMPLATE: Title"
+    unnumbered: bool,
+
+    /// Number of the next chapter
+    next: u32,
+}
+
+impl ChapterNumbering {
+    pub fn new(template: &str, numerals: Numerals, unnumbered: bool) -> Self {
+        Self { template: template.to_string(), numerals, unnumbered, next: 1 }
+    }
+
+    fn format(&self, n: u32) -> String {
+        let n = match self.numerals {
+            Numerals::Arabic => n.to_string(),
+            Numerals::Roman => roman(n),
+            Numerals::Words => words(n),
+        };
+        self.template.replace("{n}", &n)
+    }
+
+    /// The title of the next chapter, titled `title` as printed. Bare numbers become
+    /// the template and set the count; other titles are numbered on request.
+    pub fn number(&mut self, title: &str) -> String {
+        if let Some(n) = bare_number(title) {
+            self.next = n + 1;
+            return self.format(n);
+        }
+        let n = self.next;
+        self.next += 1;
+        if self.unnumbered && !NUMBERED_RE.is_match(title.trim()) {
+            format!("{}: {}", self.format(n), title)
+        } else {
+            title.to_string()
+        }
+    }
+}
+
+/// Put `title` in place of the printed title `printed` at the start of the chapter `blocks`,
+/// so that the heading in the text reads as the table of contents does. Only a paragraph
+/// holding nothing but the printed title is changed; it keeps its anchors.
+pub fn retitle(blocks: &mut [Block], printed: &str, title: &str) {
+    let key = |s: &str| {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        words.join(" ").trim_end_matches(['.', ':']).to_lowercase()
+    };
+    let printed = key(printed);
+    let heading = blocks
+        .iter_mut()
+        .take(HEADING_SEARCH)
+        .filter_map(|b| match b {
+            Block::Paragraph(p) => Some(p),
+            _ => None,
+        })
+        .find(|p| key(&plain_text(p)) == printed);
+    if let Some(p) = heading {
+        p.retain(|i| matches!(i, Inline::Anchor(_)));
+        p.push(Inline::Text(title.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_numbers() {
+        assert_eq!(bare_number("14"), Some(14));
+        assert_eq!(bare_number(" 14. "), Some(14));
+        assert_eq!(bare_number("XIV"), Some(14));
+        assert_eq!(bare_number("IV:"), Some(4));
+        assert_eq!(bare_number("Mix"), None);
+        assert_eq!(bare_number("xiv"), None);
+        assert_eq!(bare_number("Chapter 14"), None);
+    }
+}
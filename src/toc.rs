@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::backmatter::content_lines;
+use crate::document::PageText;
+
+/// One line of the printed table of contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    pub title: String,
+
+    /// Printed page number the entry points to
+    pub page: u32,
+}
+
+/// The table of contents is only looked for in this many leading pages
+const MAX_TOC_SEARCH_PAGES: usize = 30;
+
+/// Share of lines that must be entries for a page to continue the table of contents
+const TOC_LINE_RATIO: f32 = 0.5;
+
+/// Headings of the table of contents page (matched lowercase)
+const TOC_HEADINGS: &[&str] = &["contents", "table of contents", "content"];
+
+/// "Title ........ 123", "Title . . . 123", "Title 123", or a roman page number
+static ENTRY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*\p{L}.*?)(?:\s*[.·…_]+\s*|\s+)(\d{1,4}|[ivxlc]+)$").unwrap());
+
+fn is_toc_heading(line: &str) -> bool {
+    TOC_HEADINGS.contains(&line.trim_end_matches(['.', ':']).to_lowercase().as_str())
+}
+
+/// Parse an entry line. Entries of the front matter (roman page numbers) are
+/// recognised, but give no `TocEntry` as they do not start chapters.
+fn parse_entry(line: &str) -> Option<Option<TocEntry>> {
+    let caps = ENTRY_RE.captures(line)?;
+    let title = caps[1].trim_end_matches(['.', '·', '…', '_', ' ']).trim();
+    Some(caps[2].parse().ok().map(|page| TocEntry { title: title.to_string(), page }))
+}
+
+/// Parse the entries of a contents page. A title wrapped over several lines is
+/// joined with the line carrying its page number.
+pub fn parse_entries(text: &str) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    let mut pending = String::new();
+    for line in content_lines(text).filter(|l| !is_toc_heading(l)) {
+        let joined = if pending.is_empty() { line.to_string() } else { format!("{} {}", pending, line) };
+        match parse_entry(&joined) {
+            Some(entry) => {
+                entries.extend(entry);
+                pending.clear();
+            }
+            // lines without a page number either wrap or are part headings; keep one line at most
+            None => pending = line.to_string(),
+        }
+    }
+    entries
+}
+
+fn entry_ratio(text: &str) -> f32 {
+    let total = content_lines(text).count();
+    if total == 0 {
+        return 0.0;
+    }
+    content_lines(text).filter(|l| parse_entry(l).is_some()).count() as f32 / total as f32
+}
+
+/// Pages of the printed table of contents: a page headed "Contents" and the
+/// entry-like pages following it
+pub fn find_toc(pages: &[PageText]) -> Option<Range<usize>> {
+    let start = pages
+        .iter()
+        .take(MAX_TOC_SEARCH_PAGES)
+        .position(|p| content_lines(&p.text).next().is_some_and(is_toc_heading))?;
+    let more = pages[start + 1..]
+        .iter()
+        .take_while(|p| entry_ratio(&p.text) >= TOC_LINE_RATIO && !content_lines(&p.text).next().is_some_and(is_toc_heading))
+        .count();
+    Some(start..start + 1 + more)
+}
+
+/// Lowercase letters and digits only, to compare OCRed titles
+fn normalize(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// The typical difference between PDF page index and printed page number
+fn page_offset(pages: &[PageText]) -> Option<i64> {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for p in pages {
+        if let Some(n) = p.number {
+            *counts.entry(p.page as i64 - n as i64).or_default() += 1;
+        }
+    }
+    counts.into_iter().max_by_key(|&(_, c)| c).map(|(offset, _)| offset)
+}
+
+/// Find the position in `pages` where each entry starts.
+///
+/// Printed page numbers (see `--extract-pagenum`) are used when known, else the
+/// first page (after the previous chapter) that opens with the entry's title.
+/// Entries that cannot be placed, or would go backwards, are skipped.
+pub fn locate_entries(entries: &[TocEntry], pages: &[PageText]) -> Vec<(String, usize)> {
+    let offset = page_offset(pages);
+    let mut starts: Vec<(String, usize)> = Vec::new();
+    for entry in entries {
+        let from = starts.last().map(|&(_, i)| i + 1).unwrap_or(0);
+        let found = match offset {
+            Some(offset) => {
+                let target = entry.page as i64 + offset;
+                pages[from..].iter().position(|p| p.page as i64 == target)
+            }
+            None => {
+                let title = normalize(&entry.title);
+                pages[from..].iter().position(|p| {
+                    let head: String = content_lines(&p.text).take(3).collect();
+                    !title.is_empty() && normalize(&head).contains(&title)
+                })
+            }
+        };
+        if let Some(i) = found {
+            starts.push((entry.title.clone(), from + i));
+        }
+    }
+    starts
+}
+
+/// Cut `pages` at the chapter starts. Pages before the first chapter form a
+/// leading group without a title.
+pub fn split_pages(pages: Vec<PageText>, starts: &[(String, usize)]) -> Vec<(Option<String>, Vec<PageText>)> {
+    let mut groups: Vec<(Option<String>, Vec<PageText>)> = vec![(None, Vec::new())];
+    let mut next = starts.iter().peekable();
+    for (i, page) in pages.into_iter().enumerate() {
+        if let Some((title, _)) = next.next_if(|(_, start)| *start == i) {
+            groups.push((Some(title.clone()), Vec::new()));
+        }
+        groups.last_mut().expect("group").1.push(page);
+    }
+    if groups[0].1.is_empty() {
+        groups.remove(0);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, page: u32) -> TocEntry {
+        TocEntry { title: title.to_string(), page }
+    }
+
+    #[test]
+    fn entries() {
+        let text = "Contents\n\nPreface ix\n1 The Start ........ 1\nPart One\n2 A Title Wrapped\nover Two Lines . . . 17\n3 The End 203";
+        assert_eq!(
+            parse_entries(text),
+            vec![entry("1 The Start", 1), entry("2 A Title Wrapped over Two Lines", 17), entry("3 The End", 203)]
+        );
+    }
+
+    #[test]
+    fn entries_by_page_number() {
+        let page = |page, number| PageText { page, number, ..Default::default() };
+        let pages = [page(1, None), page(2, None), page(3, Some(1)), page(4, Some(2)), page(5, Some(3))];
+        let entries = [entry("One", 1), entry("Two", 3), entry("Gone", 2)];
+        assert_eq!(locate_entries(&entries, &pages), vec![("One".to_string(), 2), ("Two".to_string(), 4)]);
+    }
+
+    #[test]
+    fn entries_by_title() {
+        let page = |text: &str| PageText { text: text.to_string(), ..Default::default() };
+        let pages = [page("Contents"), page("CHAPTER ONE\nThe Start\nIt began."), page("More."), page("The End\nAt last.")];
+        let entries = [entry("The Start", 1), entry("The End", 9)];
+        assert_eq!(locate_entries(&entries, &pages), vec![("The Start".to_string(), 1), ("The End".to_string(), 3)]);
+    }
+}
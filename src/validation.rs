@@ -0,0 +1,324 @@
+//! Structural checks of a written epub, of the kind some readers are strict about: the
+//! uncompressed `mimetype` entry first, a `container.xml` pointing at the package document,
+//! a manifest listing every file and nothing more, a spine of manifest items, and
+//! well-formed XML throughout. Problems are warnings, or with `--strict` fail the run.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Cursor, Read};
+use anyhow::Context;
+use zip::{CompressionMethod, ZipArchive};
+use crate::Pdf2EPubErr;
+
+const MIMETYPE: &str = "application/epub+zip";
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// The predefined XML entities; named HTML entities such as `&nbsp;` are not XML
+const XML_ENTITIES: &[&str] = &["amp", "lt", "gt", "quot", "apos"];
+
+/// One thing wrong with an epub
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// File of the epub the problem is in
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+/// Why `text` is not well-formed XML, if it is not
+pub fn check_xml(text: &str) -> Result<(), String> {
+    let mut open: Vec<&str> = Vec::new();
+    let mut roots = 0;
+    let mut rest = text.strip_prefix('\u{feff}').unwrap_or(text);
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            check_text(rest, &open)?;
+            break;
+        };
+        check_text(&rest[..lt], &open)?;
+        rest = &rest[lt..];
+        let skip = |rest: &str, end: &str, what: &str| {
+            rest.find(end).map(|i| i + end.len()).ok_or_else(|| format!("unterminated {}", what))
+        };
+        if rest.starts_with("<?") {
+            rest = &rest[skip(rest, "?>", "processing instruction")?..];
+        } else if rest.starts_with("<!--") {
+            rest = &rest[skip(rest, "-->", "comment")?..];
+        } else if rest.starts_with("<![CDATA[") {
+            if open.is_empty() {
+                return Err("character data outside the root element".to_string());
+            }
+            rest = &rest[skip(rest, "]]>", "CDATA section")?..];
+        } else if rest.starts_with("<!DOCTYPE") {
+            rest = &rest[skip(rest, ">", "DOCTYPE")?..];
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>').ok_or("unterminated end tag")?;
+            let name = tag[..end].trim_end();
+            match open.pop() {
+                Some(expected) if expected == name => {}
+                Some(expected) => return Err(format!("</{}> closes <{}>", name, expected)),
+                None => return Err(format!("</{}> closes no element", name)),
+            }
+            rest = &tag[end + 1..];
+        } else {
+            let (name, self_closing, after) = start_tag(&rest[1..])?;
+            if open.is_empty() {
+                roots += 1;
+                if roots > 1 {
+                    return Err(format!("<{}> is a second root element", name));
+                }
+            }
+            if !self_closing {
+                open.push(name);
+            }
+            rest = after;
+        }
+    }
+    match open.last() {
+        Some(name) => Err(format!("<{}> is never closed", name)),
+        None if roots == 0 => Err("no root element".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Check the text between tags: entities must be XML ones, and only whitespace may stand
+/// outside the root element
+fn check_text(text: &str, open: &[&str]) -> Result<(), String> {
+    if open.is_empty() && !text.trim().is_empty() {
+        return Err("text outside the root element".to_string());
+    }
+    check_entities(text)
+}
+
+fn check_entities(text: &str) -> Result<(), String> {
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        rest = &rest[amp + 1..];
+        let end = rest.find(';').filter(|&i| i > 0 && !rest[..i].contains(char::is_whitespace));
+        let Some(end) = end else {
+            return Err("unescaped `&`".to_string());
+        };
+        let name = &rest[..end];
+        let valid = match name.strip_prefix('#') {
+            Some(hex) if hex.starts_with('x') => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32).is_some(),
+            Some(dec) => dec.parse::<u32>().ok().and_then(char::from_u32).is_some(),
+            None => XML_ENTITIES.contains(&name),
+        };
+        if !valid {
+            return Err(format!("unknown entity `&{};`", name));
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(())
+}
+
+/// Parse the start tag `tag` (after its `<`): its name, whether it closes itself, and
+/// the text after it
+fn start_tag(tag: &str) -> Result<(&str, bool, &str), String> {
+    let name_end = tag.find(|c: char| c.is_whitespace() || c == '>' || c == '/').ok_or("unterminated start tag")?;
+    let name = &tag[..name_end];
+    if name.is_empty() {
+        return Err("unescaped `<`".to_string());
+    }
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut rest = &tag[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return Ok((name, true, after));
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Ok((name, false, after));
+        }
+        let eq = rest.find('=').ok_or_else(|| format!("attribute without a value in <{}>", name))?;
+        let attr = rest[..eq].trim_end();
+        if attr.is_empty() || attr.contains(|c: char| c.is_whitespace() || c == '>' || c == '<') {
+            return Err(format!("malformed attribute in <{}>", name));
+        }
+        if !seen.insert(attr) {
+            return Err(format!("attribute `{}` repeated in <{}>", attr, name));
+        }
+        rest = rest[eq + 1..].trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'');
+        let quote = quote.ok_or_else(|| format!("unquoted value of `{}` in <{}>", attr, name))?;
+        let end = rest[1..].find(quote).ok_or_else(|| format!("unterminated value of `{}` in <{}>", attr, name))?;
+        let value = &rest[1..1 + end];
+        if value.contains('<') {
+            return Err(format!("`<` in the value of `{}` in <{}>", attr, name));
+        }
+        check_entities(value)?;
+        rest = &rest[end + 2..];
+    }
+}
+
+/// The value of attribute `name` of the start tag `element`
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = element;
+    loop {
+        let i = rest.find(name)?;
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+        if before.is_some_and(char::is_whitespace) {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+                let end = value[1..].find(quote)?;
+                return Some(&value[1..1 + end]);
+            }
+        }
+    }
+}
+
+/// The start tags of the elements named `tag` in `xml`
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}", tag);
+    xml.match_indices(&open)
+        .filter(|(i, _)| xml[i + open.len()..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/'))
+        .filter_map(|(i, _)| xml[i..].find('>').map(|end| &xml[i..i + end + 1]))
+        .collect()
+}
+
+/// `href`, relative to the directory of `base`, as a path inside the epub
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or(href);
+    let mut parts: Vec<&str> = base.split('/').collect();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Check the structure of the epub `epub`
+pub fn validate(epub: &[u8]) -> Result<Vec<Problem>, Pdf2EPubErr> {
+    let mut problems = Vec::new();
+    let mut problem = |file: &str, message: String| problems.push(Problem { file: file.to_string(), message });
+    let mut archive = ZipArchive::new(Cursor::new(epub)).context("epub is not a valid zip")?;
+
+    let mut texts: HashMap<String, String> = HashMap::new();
+    let mut names: Vec<String> = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("failed to read epub entry")?;
+        let name = file.name().to_string();
+        if i == 0 {
+            if name != "mimetype" {
+                problem(&name, "the first entry is not `mimetype`".to_string());
+            } else if file.compression() != CompressionMethod::Stored {
+                problem(&name, "is compressed".to_string());
+            }
+        }
+        if name == "mimetype" || name.ends_with(".xml") || name.ends_with(".opf") || name.ends_with(".xhtml") || name.ends_with(".ncx") {
+            let mut text = String::new();
+            if file.read_to_string(&mut text).is_err() {
+                problem(&name, "is not UTF-8".to_string());
+            }
+            texts.insert(name.clone(), text);
+        }
+        names.push(name);
+    }
+    match texts.get("mimetype") {
+        Some(mimetype) if mimetype != MIMETYPE => problem("mimetype", format!("reads `{}`, not `{}`", mimetype, MIMETYPE)),
+        Some(_) => {}
+        None => problem("mimetype", "is missing".to_string()),
+    }
+
+    let Some(container) = texts.get(CONTAINER_PATH) else {
+        problem(CONTAINER_PATH, "is missing".to_string());
+        return Ok(problems);
+    };
+    if let Err(e) = check_xml(container) {
+        problem(CONTAINER_PATH, e);
+    }
+    let rootfile = elements(container, "rootfile")
+        .into_iter()
+        .find(|e| attribute(e, "media-type") == Some("application/oebps-package+xml"))
+        .and_then(|e| attribute(e, "full-path"));
+    let Some(opf_path) = rootfile else {
+        problem(CONTAINER_PATH, "names no package document".to_string());
+        return Ok(problems);
+    };
+    let Some(opf) = texts.get(opf_path) else {
+        problem(CONTAINER_PATH, format!("names `{}`, which is missing", opf_path));
+        return Ok(problems);
+    };
+    if let Err(e) = check_xml(opf) {
+        problem(opf_path, e);
+    }
+
+    let mut ids: HashSet<&str> = HashSet::new();
+    let mut listed: HashSet<String> = HashSet::new();
+    let mut xml_items: Vec<String> = Vec::new();
+    for item in elements(opf, "item") {
+        let (Some(id), Some(href)) = (attribute(item, "id"), attribute(item, "href")) else {
+            problem(opf_path, format!("manifest item without an id or href: {}", item));
+            continue;
+        };
+        if !ids.insert(id) {
+            problem(opf_path, format!("manifest id `{}` repeated", id));
+        }
+        let path = resolve(opf_path, href);
+        if !names.contains(&path) {
+            problem(opf_path, format!("manifest item `{}` names `{}`, which is missing", id, href));
+        }
+        let media_type = attribute(item, "media-type").unwrap_or_default();
+        if media_type == "application/xhtml+xml" || media_type == "application/x-dtbncx+xml" {
+            xml_items.push(path.clone());
+        }
+        listed.insert(path);
+    }
+    let spine: Vec<&str> = elements(opf, "itemref").into_iter().filter_map(|e| attribute(e, "idref")).collect();
+    if spine.is_empty() {
+        problem(opf_path, "the spine is empty".to_string());
+    }
+    for idref in spine.into_iter().filter(|idref| !ids.contains(idref)) {
+        problem(opf_path, format!("spine item `{}` is not in the manifest", idref));
+    }
+    for name in &names {
+        let packaging = name == "mimetype" || name.starts_with("META-INF/") || name == opf_path || name.ends_with('/');
+        if !packaging && !listed.contains(name) {
+            problem(name, "is not in the manifest".to_string());
+        }
+    }
+    for path in xml_items {
+        if let Some(Err(e)) = texts.get(&path).map(|text| check_xml(text)) {
+            problem(&path, e);
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_formed_xml() {
+        let xhtml = r#"<?xml version="1.0"?><!DOCTYPE html><html a="1" b='2'><!-- c --><p>x &amp; y&#233;<br/></p></html>"#;
+        assert_eq!(check_xml(xhtml), Ok(()));
+    }
+
+    #[test]
+    fn malformed_xml() {
+        let err = |text: &str| check_xml(text).unwrap_err();
+        assert_eq!(err("<p><b>x</p>"), "</p> closes <b>");
+        assert_eq!(err("<p>x"), "<p> is never closed");
+        assert_eq!(err("<p/><p/>"), "<p> is a second root element");
+        assert_eq!(err("<p>&nbsp;</p>"), "unknown entity `&nbsp;`");
+        assert_eq!(err("<p>a & b</p>"), "unescaped `&`");
+        assert_eq!(err("<p a=1/>"), "unquoted value of `a` in <p>");
+        assert_eq!(err("<p a='1' a='2'/>"), "attribute `a` repeated in <p>");
+        assert_eq!(err("text<p/>"), "text outside the root element");
+        assert_eq!(err(""), "no root element");
+    }
+}
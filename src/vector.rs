@@ -0,0 +1,236 @@
+use std::fmt::Write;
+use html_escape::encode_text;
+use pdfium_render::prelude::*;
+use image::RgbImage;
+use crate::document::Figure;
+use crate::figures::{attach_caption, mask_region, MAX_FIGURE_AREA, MIN_FIGURE_SIDE};
+use crate::ocr::{OcrOptions, Rect};
+use crate::Pdf2EPubErr;
+
+/// Path objects closer than this (in points) are considered part of the same drawing
+const CLUSTER_GAP: f32 = 6.0;
+
+/// A drawing needs at least this many path objects, so lone rules and boxes are skipped
+const MIN_PATHS: usize = 3;
+
+/// Bounding box in PDF points (origin bottom left)
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    left: f32,
+    bottom: f32,
+    right: f32,
+    top: f32,
+}
+
+impl Bounds {
+    fn of(obj: &PdfPageObject) -> Option<Bounds> {
+        let b = obj.bounds().ok()?;
+        Some(Bounds { left: b.left().value, bottom: b.bottom().value, right: b.right().value, top: b.top().value })
+    }
+
+    fn near(&self, other: &Bounds, gap: f32) -> bool {
+        self.left - gap <= other.right && other.left - gap <= self.right
+            && self.bottom - gap <= other.top && other.bottom - gap <= self.top
+    }
+
+    fn contains(&self, other: &Bounds) -> bool {
+        other.left >= self.left && other.right <= self.right
+            && other.bottom >= self.bottom && other.top <= self.top
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            bottom: self.bottom.min(other.bottom),
+            right: self.right.max(other.right),
+            top: self.top.max(other.top),
+        }
+    }
+
+    fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    fn height(&self) -> f32 {
+        self.top - self.bottom
+    }
+}
+
+/// Group nearby boxes into drawings; returns the member indices and extent of each group
+fn cluster(boxes: &[Bounds]) -> Vec<(Vec<usize>, Bounds)> {
+    let mut groups: Vec<(Vec<usize>, Bounds)> = boxes
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (vec![i], *b))
+        .collect();
+
+    // merge until no two groups touch
+    loop {
+        let mut merged = false;
+        'outer: for i in 0..groups.len() {
+            for j in (i + 1)..groups.len() {
+                if groups[i].1.near(&groups[j].1, CLUSTER_GAP) {
+                    let (members, b) = groups.swap_remove(j);
+                    groups[i].0.extend(members);
+                    groups[i].1 = groups[i].1.union(&b);
+                    merged = true;
+                    break 'outer;
+                }
+            }
+        }
+        if !merged {
+            return groups;
+        }
+    }
+}
+
+fn svg_color(c: &PdfColor) -> String {
+    format!("rgb({},{},{})", c.red(), c.green(), c.blue())
+}
+
+/// Convert one path object into an SVG `<path>`, in a coordinate system whose
+/// origin is the top left corner of `frame`.
+fn path_to_svg(obj: &PdfPageObject, frame: &Bounds) -> Option<String> {
+    let path = obj.as_path_object()?;
+    let segments = path.segments().transform(obj.matrix().ok()?);
+    let pt = |x: PdfPoints, y: PdfPoints| (x.value - frame.left, frame.top - y.value);
+
+    let mut d = String::new();
+    let mut bezier: Vec<(f32, f32)> = Vec::new();
+    for i in 0..segments.len() {
+        let seg = segments.get(i).ok()?;
+        let (x, y) = pt(seg.x(), seg.y());
+        match seg.segment_type() {
+            PdfPathSegmentType::MoveTo => write!(d, "M{:.2} {:.2} ", x, y).ok()?,
+            PdfPathSegmentType::LineTo => write!(d, "L{:.2} {:.2} ", x, y).ok()?,
+            PdfPathSegmentType::BezierTo => {
+                // pdfium reports a cubic curve as three consecutive points
+                bezier.push((x, y));
+                if bezier.len() == 3 {
+                    let [(x1, y1), (x2, y2), (x3, y3)] = [bezier[0], bezier[1], bezier[2]];
+                    write!(d, "C{:.2} {:.2} {:.2} {:.2} {:.2} {:.2} ", x1, y1, x2, y2, x3, y3).ok()?;
+                    bezier.clear();
+                }
+            }
+            PdfPathSegmentType::Unknown => continue,
+        }
+        if seg.is_close() {
+            d.push_str("Z ");
+        }
+    }
+    if d.is_empty() {
+        return None;
+    }
+
+    let mut attrs = match path.fill_mode() {
+        Ok(PdfPathFillMode::None) | Err(_) => r#" fill="none""#.to_string(),
+        Ok(mode) => {
+            let color = obj.fill_color().map(|c| svg_color(&c)).unwrap_or_else(|_| "black".to_string());
+            let rule = if mode == PdfPathFillMode::EvenOdd { r#" fill-rule="evenodd""# } else { "" };
+            format!(r#" fill="{}"{}"#, color, rule)
+        }
+    };
+    if path.is_stroked().unwrap_or(false) {
+        let color = obj.stroke_color().map(|c| svg_color(&c)).unwrap_or_else(|_| "black".to_string());
+        let width = obj.stroke_width().map(|w| w.value).unwrap_or(1.0).max(0.1);
+        write!(attrs, r#" stroke="{}" stroke-width="{:.2}""#, color, width).ok()?;
+    }
+
+    Some(format!(r#"<path d="{}"{}/>"#, d.trim_end(), attrs))
+}
+
+/// Convert a text object (a label inside a drawing) into an SVG `<text>`
+fn text_to_svg(obj: &PdfPageObject, frame: &Bounds) -> Option<String> {
+    let text = obj.as_text_object()?;
+    let b = Bounds::of(obj)?;
+    // text object bounds sit on the baseline
+    Some(format!(
+        r#"<text x="{:.2}" y="{:.2}" font-size="{:.2}" font-family="sans-serif">{}</text>"#,
+        b.left - frame.left,
+        frame.top - b.bottom,
+        text.scaled_font_size().value,
+        encode_text(&text.text())
+    ))
+}
+
+/// Build a standalone SVG document for the drawing covering `frame`
+fn drawing_to_svg(objects: &[PdfPageObject], paths: &[usize], frame: &Bounds) -> String {
+    let mut body: String = paths
+        .iter()
+        .filter_map(|&i| path_to_svg(&objects[i], frame))
+        .collect();
+    body.extend(
+        objects
+            .iter()
+            .filter(|o| o.object_type() == PdfPageObjectType::Text)
+            .filter(|o| Bounds::of(o).map(|b| frame.contains(&b)).unwrap_or(false))
+            .filter_map(|o| text_to_svg(o, frame)),
+    );
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{w:.2}pt" height="{h:.2}pt" viewBox="0 0 {w:.2} {h:.2}">{body}</svg>
+"#,
+        w = frame.width(),
+        h = frame.height(),
+        body = body
+    )
+}
+
+/// Pull drawings made of vector paths out of `page` as SVG figures.
+/// Like `figures::extract_figures`, the drawing and its caption are masked in `img`.
+pub fn extract_vector_figures(
+    page: &PdfPage,
+    page_index: usize,
+    img: &mut RgbImage,
+    target_dpi: u16,
+    ocr: &OcrOptions,
+) -> Result<Vec<Figure>, Pdf2EPubErr> {
+    let scale = target_dpi as f32 / 72.0;
+    let page_w = page.width().value;
+    let page_h = page.height().value;
+
+    let objects: Vec<PdfPageObject> = page.objects().iter().collect();
+    let (path_idx, path_bounds): (Vec<usize>, Vec<Bounds>) = objects
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| o.object_type() == PdfPageObjectType::Path)
+        .filter_map(|(i, o)| Bounds::of(o).map(|b| (i, b)))
+        // page frames and background fills are not drawings
+        .filter(|(_, b)| b.width() * b.height() < MAX_FIGURE_AREA * page_w * page_h)
+        .unzip();
+
+    let mut figures = Vec::new();
+    let drawings = cluster(&path_bounds)
+        .into_iter()
+        .filter(|(members, b)| {
+            members.len() >= MIN_PATHS
+                && b.width() > MIN_FIGURE_SIDE * page_w
+                && b.height() > MIN_FIGURE_SIDE * page_h
+        });
+    for (n, (members, frame)) in drawings.enumerate() {
+        let paths: Vec<usize> = members.iter().map(|&m| path_idx[m]).collect();
+        let svg = drawing_to_svg(&objects, &paths, &frame);
+        let mut fig = Figure {
+            name: format!("page-{}-drawing-{}.svg", page_index + 1, n + 1),
+            data: svg.into_bytes(),
+            mime: "image/svg+xml",
+            page: page_index + 1,
+            caption: None,
+            alt: None,
+            full: None,
+            mathml: None,
+        };
+
+        let region = Rect {
+            x: (frame.left.max(0.0) * scale) as u32,
+            y: ((page_h - frame.top).max(0.0) * scale) as u32,
+            w: (frame.width() * scale) as u32,
+            h: (frame.height() * scale) as u32,
+        };
+        attach_caption(img, &region, &mut fig, ocr)?;
+        mask_region(img, &region);
+        figures.push(fig);
+    }
+    Ok(figures)
+}
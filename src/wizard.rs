@@ -0,0 +1,112 @@
+//! `pdf2epub init`: write a settings file by asking a few questions about a sample page
+use std::io::{BufRead, Write};
+use std::path::Path;
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+use toml::Value;
+use crate::ocr::{language_tag, ocr_rgb_png, OcrOptions};
+use crate::{img_source_from_page, Pdf2EPubErr};
+
+/// Lines of the recognised sample page shown as the preview
+const PREVIEW_LINES: usize = 12;
+
+/// Mean OCR confidence below which cleaning up the scan is suggested
+const POOR_SCAN_CONFIDENCE: f32 = 70.0;
+
+/// Recognise `page` as the conversion would by default
+pub fn preview(page: &PdfPage, dpi: u16, ocr: &OcrOptions) -> Result<(String, Option<f32>), Pdf2EPubErr> {
+    let img = img_source_from_page(page, dpi, true)?;
+    let rgb = DynamicImage::ImageLuma8(image::imageops::grayscale(&img)).into_rgb8();
+    ocr_rgb_png(&rgb, ocr)
+}
+
+/// Asks questions on `out` and reads the answers from `input`
+struct Prompt<R, W> {
+    input: R,
+    out: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    /// The answer to `question`, with `hint` shown in brackets; empty when none was given
+    fn read_answer(&mut self, question: &str, hint: &str) -> std::io::Result<String> {
+        write!(self.out, "{} [{}]: ", question, hint)?;
+        self.out.flush()?;
+        let mut answer = String::new();
+        self.input.read_line(&mut answer)?;
+        Ok(answer.trim().to_string())
+    }
+
+    fn ask(&mut self, question: &str, default: &str) -> std::io::Result<String> {
+        let answer = self.read_answer(question, default)?;
+        Ok(if answer.is_empty() { default.to_string() } else { answer })
+    }
+
+    fn yes_no(&mut self, question: &str, default: bool) -> std::io::Result<bool> {
+        loop {
+            let answer = self.read_answer(question, if default { "Y/n" } else { "y/N" })?;
+            match answer.to_ascii_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.out, "Please answer yes or no.")?,
+            }
+        }
+    }
+}
+
+/// Show the `preview` of the sample page, then ask about the book.
+/// Returns the settings, keyed by long flag names.
+pub fn interview(
+    input: impl BufRead,
+    out: impl Write,
+    preview: &(String, Option<f32>),
+    ocr_lang: &str,
+) -> std::io::Result<Vec<(&'static str, Value)>> {
+    let mut p = Prompt { input, out };
+    let (text, confidence) = preview;
+    writeln!(p.out, "Sample page as recognised:\n")?;
+    for line in text.lines().filter(|l| !l.trim().is_empty()).take(PREVIEW_LINES) {
+        writeln!(p.out, "    {}", line)?;
+    }
+    match confidence {
+        Some(c) => writeln!(p.out, "\nMean OCR confidence: {:.1}%\n", c)?,
+        None => writeln!(p.out, "\nNo text found on the sample page.\n")?,
+    }
+
+    let mut settings = Vec::new();
+    let ocr_lang = p.ask("Language(s) of the book, as Tesseract codes (e.g. eng, deu, fra+eng)", ocr_lang)?;
+    let primary = ocr_lang.split('+').next().unwrap_or_default();
+    if let Some(tag) = language_tag(primary) {
+        settings.push(("lang", Value::String(tag.to_string())));
+    }
+    settings.push(("ocr-lang", Value::String(ocr_lang)));
+    if p.yes_no("Remove the page numbers printed on the pages?", true)? {
+        settings.push(("extract-pagenum", Value::Boolean(true)));
+    }
+    if p.yes_no("Split chapters at the book's table of contents?", true)? {
+        settings.push(("parse-toc", Value::Boolean(true)));
+    }
+    if p.yes_no("Keep the pictures and illustrations?", false)? {
+        settings.push(("extract-images", Value::Boolean(true)));
+    }
+    let poor = confidence.is_some_and(|c| c < POOR_SCAN_CONFIDENCE);
+    if p.yes_no("Clean up the scan (speckles, curved lines, gutter shadow) before OCR?", poor)? {
+        settings.push(("despeckle", Value::String("components".to_string())));
+        settings.push(("dewarp", Value::Boolean(true)));
+        settings.push(("remove-gutter", Value::Boolean(true)));
+    }
+    Ok(settings)
+}
+
+/// Write `settings` as a settings file, see `config`
+pub fn write_settings(path: &Path, settings: &[(&'static str, Value)]) -> Result<(), Pdf2EPubErr> {
+    let mut text = String::from("# written by `pdf2epub init`\n");
+    for (key, value) in settings {
+        text.push_str(&format!("{} = {}\n", key, value));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! Cross-references in the text ("see page 123", "cf. p. 45") linked to the pages they name
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::document::{label_anchor, Block, Inline};
+
+/// `see page 123`, `see also pp. 12`, `cf. p. xii`, `compare page 7`; group 1 is the
+/// part that becomes the link, group 2 the page name
+static XREF_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:see|cf\.|compare)(?:\s+also)?\s+((?:pages?|pp?\.)\s*(\d{1,4}|[ivxlcdm]{1,7}))\b").unwrap()
+});
+
+/// Split `text` at its cross-references to the pages in `known`
+fn link_text(text: &str, known: &HashSet<String>) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut start = 0;
+    for m in XREF_RE.captures_iter(text) {
+        let (Some(part), Some(name)) = (m.get(1), m.get(2)) else { continue };
+        let name = if known.contains(name.as_str()) {
+            name.as_str().to_string()
+        } else if known.contains(&name.as_str().to_lowercase()) {
+            name.as_str().to_lowercase()
+        } else {
+            continue;
+        };
+        if part.start() > start {
+            inlines.push(Inline::Text(text[start..part.start()].to_string()));
+        }
+        inlines.push(Inline::Link { href: format!("#{}", label_anchor(&name)), text: part.as_str().to_string() });
+        start = part.end();
+    }
+    if start < text.len() {
+        inlines.push(Inline::Text(text[start..].to_string()));
+    }
+    inlines
+}
+
+/// Link the cross-references of `blocks` to the page anchors of the pages named in
+/// `known` (see `PageText::page_name`); references to other pages stay text
+pub fn link_page_refs(mut blocks: Vec<Block>, known: &HashSet<String>) -> Vec<Block> {
+    if known.is_empty() {
+        return blocks;
+    }
+    for block in &mut blocks {
+        if let Some(inlines) = block.inlines_mut() {
+            *inlines = inlines
+                .drain(..)
+                .flat_map(|i| match i {
+                    Inline::Text(t) => link_text(&t, known),
+                    other => vec![other],
+                })
+                .collect();
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(name: &str, text: &str) -> Inline {
+        Inline::Link { href: format!("#{}", label_anchor(name)), text: text.to_string() }
+    }
+
+    #[test]
+    fn cross_references() {
+        let known = HashSet::from(["12".to_string(), "xii".to_string()]);
+        assert_eq!(
+            link_text("(see also pp. 12), cf. p. XII, see page 40.", &known),
+            vec![
+                Inline::Text("(see also ".to_string()),
+                link("12", "pp. 12"),
+                Inline::Text("), cf. ".to_string()),
+                link("xii", "p. XII"),
+                Inline::Text(", see page 40.".to_string()),
+            ]
+        );
+        assert_eq!(link_text("page 12", &known), vec![Inline::Text("page 12".to_string())]);
+    }
+}